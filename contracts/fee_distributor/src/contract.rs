@@ -1,7 +1,8 @@
 use cosmwasm_std::{
-    coin, coins, entry_point, to_binary, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo,
-    Order, Response, StdError, StdResult, Uint128,
+    entry_point, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut,
+    Env, MessageInfo, Order, Response, StdError, StdResult, Storage, Uint128, WasmMsg,
 };
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw_storage_plus::Bound;
 use fee_contract_export::state::FeeType;
 use itertools::Itertools;
@@ -9,17 +10,24 @@ use itertools::Itertools;
 use std::convert::TryInto;
 use utils::state::maybe_addr;
 
-use fee_distributor_export::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
-use fee_distributor_export::state::ContractInfo;
+use fee_distributor_export::msg::{AssetBalance, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use fee_distributor_export::state::{AssetInfo, ContractInfo};
 
 use crate::error::ContractError;
+use crate::staking::{
+    _allocate_to_stakers, claim_rewards, query_pending_rewards, query_staker_info,
+    query_total_staked, stake, unstake, withdraw_unbonded,
+};
 use crate::state::{
-    is_admin, is_admin_or_address, ALLOCATED_FUNDS, ASSOCIATED_FEE_ADDRESS, CONTRACT_INFO,
+    is_admin, is_admin_or_address, ALLOCATED_ASSETS, ASSOCIATED_FEE_ADDRESS, CONTRACT_INFO,
+    LEGACY_ALLOCATED_FUNDS, PROJECT_ADDRESSES, VESTING_SCHEDULES,
 };
+use crate::vesting::{_add_vesting_assets, claim, query_vesting, revoke, set_vesting_schedule};
 
 const PROJECTS_ALLOCATION_FOR_ASSETS_FEE: u128 = 600u128;
 const PROJECTS_ALLOCATION_FOR_FUNDS_FEE: u128 = 666u128;
 const PROJECT_ALLOCATION_MAX_PERCENTAGE: u128 = 1000u128;
+const UNBONDING_PERIOD: u64 = 604_800u64; // One week
 const DEFAULT_LIMIT: u32 = 10u32;
 const MAX_LIMIT: u32 = 30u32;
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -42,6 +50,12 @@ pub fn instantiate(
         treasury: deps.api.addr_validate(&msg.treasury)?,
         projects_allocation_for_funds_fee: Uint128::from(PROJECTS_ALLOCATION_FOR_FUNDS_FEE),
         projects_allocation_for_assets_fee: Uint128::from(PROJECTS_ALLOCATION_FOR_ASSETS_FEE),
+        staking_token: msg
+            .staking_token
+            .map(|x| deps.api.addr_validate(&x))
+            .transpose()?,
+        stakers_allocation: Uint128::zero(),
+        unbonding_period: UNBONDING_PERIOD,
     };
     CONTRACT_INFO.save(deps.storage, &data)?;
     Ok(Response::default()
@@ -62,6 +76,9 @@ pub fn execute(
             treasury,
             projects_allocation_for_assets_fee,
             projects_allocation_for_funds_fee,
+            staking_token,
+            stakers_allocation,
+            unbonding_period,
         } => modify_contract_info(
             deps,
             env,
@@ -70,6 +87,9 @@ pub fn execute(
             treasury,
             projects_allocation_for_assets_fee,
             projects_allocation_for_funds_fee,
+            staking_token,
+            stakers_allocation,
+            unbonding_period,
         ),
         ExecuteMsg::AddAssociatedAddress {
             address,
@@ -79,29 +99,68 @@ pub fn execute(
             addresses,
             fee_type,
         } => deposit_fees(deps, env, info, fee_type, addresses),
+        ExecuteMsg::Receive(wrapper) => receive(deps, env, info, wrapper),
         ExecuteMsg::WithdrawFees { addresses } => withdraw_fees(deps, env, info, addresses),
+        // Staking is only possible by sending tokens through the Receive hook
+        ExecuteMsg::Stake {} => Err(ContractError::Unauthorized {}),
+        ExecuteMsg::Unstake { amount } => unstake(deps, env, info, amount),
+        ExecuteMsg::WithdrawUnbonded {} => withdraw_unbonded(deps, env, info),
+        ExecuteMsg::ClaimRewards {} => claim_rewards(deps, env, info),
+        ExecuteMsg::SetVestingSchedule {
+            address,
+            start,
+            cliff,
+            duration,
+        } => set_vesting_schedule(deps, env, info, address, start, cliff, duration),
+        ExecuteMsg::Claim { address } => claim(deps, env, info, address),
+        ExecuteMsg::Revoke { address } => revoke(deps, env, info, address),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    // No state migrations performed, just returned a Response
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Native balances used to be stored as a list of coins per project
+    // We move them to the per-asset storage
+    let legacy_funds = LEGACY_ALLOCATED_FUNDS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Addr, Vec<Coin>)>>>()?;
+    for (address, funds) in legacy_funds {
+        let assets = funds
+            .into_iter()
+            .map(|fund| (AssetInfo::Coin(fund.denom), fund.amount))
+            .collect();
+        _allocate_assets(deps.storage, &address, assets)?;
+        LEGACY_ALLOCATED_FUNDS.remove(deps.storage, &address);
+    }
+
+    // Contracts instantiated before staking was introduced have no unbonding period
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if contract_info.unbonding_period == 0 {
+        contract_info.unbonding_period = UNBONDING_PERIOD;
+        CONTRACT_INFO.save(deps.storage, &contract_info)?;
+    }
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::ContractInfo {} => to_binary(&contract_info(deps)?),
         QueryMsg::Amount { address } => to_binary(&query_amount(deps, address)?),
         QueryMsg::Addresses { start_after, limit } => {
             to_binary(&query_addresses(deps, start_after, limit)?)
         }
+        QueryMsg::StakerInfo { address } => to_binary(&query_staker_info(deps, address)?),
+        QueryMsg::PendingRewards { address } => to_binary(&query_pending_rewards(deps, address)?),
+        QueryMsg::TotalStaked {} => to_binary(&query_total_staked(deps)?),
+        QueryMsg::Vesting { address } => to_binary(&query_vesting(deps, env, address)?),
     }
 }
 
 /// Modify all contract info using this function
 /// Must be the admin to change the parameters
+/// The staking token can only be set once
+#[allow(clippy::too_many_arguments)]
 pub fn modify_contract_info(
     deps: DepsMut,
     _env: Env,
@@ -110,6 +169,9 @@ pub fn modify_contract_info(
     treasury: Option<String>,
     projects_allocation_for_assets_fee: Option<Uint128>,
     projects_allocation_for_funds_fee: Option<Uint128>,
+    staking_token: Option<String>,
+    stakers_allocation: Option<Uint128>,
+    unbonding_period: Option<u64>,
 ) -> Result<Response, ContractError> {
     is_admin(deps.as_ref(), info.sender)?;
 
@@ -120,6 +182,15 @@ pub fn modify_contract_info(
         .unwrap_or(contract_info.projects_allocation_for_assets_fee);
     contract_info.projects_allocation_for_funds_fee = projects_allocation_for_funds_fee
         .unwrap_or(contract_info.projects_allocation_for_funds_fee);
+    if let Some(staking_token) = maybe_addr(deps.api, staking_token)? {
+        if contract_info.staking_token.is_some() {
+            return Err(ContractError::StakingTokenAlreadySet {});
+        }
+        contract_info.staking_token = Some(staking_token);
+    }
+    contract_info.stakers_allocation =
+        stakers_allocation.unwrap_or(contract_info.stakers_allocation);
+    contract_info.unbonding_period = unbonding_period.unwrap_or(contract_info.unbonding_period);
 
     if contract_info.projects_allocation_for_funds_fee.u128() > PROJECT_ALLOCATION_MAX_PERCENTAGE {
         return Err(ContractError::AllocationTooHigh {});
@@ -127,6 +198,9 @@ pub fn modify_contract_info(
     if contract_info.projects_allocation_for_assets_fee.u128() > PROJECT_ALLOCATION_MAX_PERCENTAGE {
         return Err(ContractError::AllocationTooHigh {});
     }
+    if contract_info.stakers_allocation.u128() > PROJECT_ALLOCATION_MAX_PERCENTAGE {
+        return Err(ContractError::AllocationTooHigh {});
+    }
     CONTRACT_INFO.save(deps.storage, &contract_info)?;
 
     Ok(Response::new().add_attribute("action", "parameter_update"))
@@ -160,6 +234,7 @@ pub fn add_associated_address(
 
 /// Main Function of this contract
 /// Deposit Fees and distribute them according to the addresses provided
+/// Multiple native coins can be deposited at once, cw20 tokens are deposited using the Receive hook
 pub fn deposit_fees(
     deps: DepsMut,
     env: Env,
@@ -167,12 +242,52 @@ pub fn deposit_fees(
     fee_type: FeeType,
     addresses: Vec<String>,
 ) -> Result<Response, ContractError> {
-    // The deposited funds must be a unique fund type
-    if info.funds.len() != 1 {
+    // At least one fund type must be deposited
+    if info.funds.is_empty() {
         return Err(ContractError::DepositNotCorrect {});
     }
 
-    let fund = info.funds[0].clone();
+    let funds = info
+        .funds
+        .iter()
+        .map(|fund| (AssetInfo::Coin(fund.denom.clone()), fund.amount))
+        .collect();
+    _deposit_fees(deps, env, info, fee_type, addresses, funds)
+}
+
+/// Deposit cw20 fees by sending them to the contract using a send message
+/// The message sent along with the tokens has to be a DepositFees message
+pub fn receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_binary(&wrapper.msg)? {
+        ExecuteMsg::DepositFees {
+            addresses,
+            fee_type,
+        } => {
+            // The token contract is the sender of this message
+            let funds = vec![(AssetInfo::Cw20(info.sender.to_string()), wrapper.amount)];
+            _deposit_fees(deps, env, info, fee_type, addresses, funds)
+        }
+        ExecuteMsg::Stake {} => stake(deps, env, info.sender, wrapper.sender, wrapper.amount),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// Internal function
+/// Splits each deposited asset between the indicated addresses and the treasury
+/// A share of the treasury allocation goes to the ILIQ stakers
+pub fn _deposit_fees(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    fee_type: FeeType,
+    addresses: Vec<String>,
+    funds: Vec<(AssetInfo, Uint128)>,
+) -> Result<Response, ContractError> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
     let n_addresses: u128 = addresses.len().try_into().unwrap();
 
@@ -181,38 +296,33 @@ pub fn deposit_fees(
         FeeType::Assets => contract_info.projects_allocation_for_assets_fee,
     };
 
-    let each_project_allocation = if n_addresses > 0 {
-        fund.amount * projects_allocation
-            / Uint128::from(PROJECT_ALLOCATION_MAX_PERCENTAGE)
-            / Uint128::from(n_addresses)
-    } else {
-        Uint128::zero()
-    };
+    let mut each_project_funds = vec![];
+    let mut treasury_funds = vec![];
+    for (asset, amount) in funds {
+        let each_project_allocation = if n_addresses > 0 {
+            amount * projects_allocation
+                / Uint128::from(PROJECT_ALLOCATION_MAX_PERCENTAGE)
+                / Uint128::from(n_addresses)
+        } else {
+            Uint128::zero()
+        };
+        let treasury_allocation = amount - each_project_allocation * Uint128::from(n_addresses);
+        let stakers_allocation = _allocate_to_stakers(
+            deps.storage,
+            &asset,
+            treasury_allocation * contract_info.stakers_allocation
+                / Uint128::from(PROJECT_ALLOCATION_MAX_PERCENTAGE),
+        )?;
+        let treasury_allocation = treasury_allocation - stakers_allocation;
+
+        each_project_funds.push((asset.clone(), each_project_allocation));
+        treasury_funds.push((asset, treasury_allocation));
+    }
 
-    let treasury_allocation = fund.amount - each_project_allocation * Uint128::from(n_addresses);
-    let each_project_fund = coin(each_project_allocation.u128(), fund.denom.clone());
     // First we save the fees that just arrived into the contract memory
     for address in &addresses {
         let valid_address = deps.api.addr_validate(address)?;
-        ALLOCATED_FUNDS.update::<_, StdError>(deps.storage, &valid_address, |x| {
-            match x {
-                Some(mut funds) => {
-                    // We check the sent funds are with the right format
-                    let existing_denom = funds.iter_mut().find(|c| c.denom == fund.denom.clone());
-
-                    if let Some(existing_fund) = existing_denom {
-                        *existing_fund = Coin {
-                            denom: fund.denom.clone(),
-                            amount: existing_fund.amount + each_project_fund.amount,
-                        };
-                    } else {
-                        funds.push(each_project_fund.clone());
-                    }
-                    Ok(funds)
-                }
-                None => Ok(vec![each_project_fund.clone()]),
-            }
-        })?;
+        _allocate_assets(deps.storage, &valid_address, each_project_funds.clone())?;
     }
     // Then we try to distribute the fees from the addresses that were just credited (if they have an associated address)
     let fee_withdrawal_messages = if !addresses.is_empty() {
@@ -222,15 +332,12 @@ pub fn deposit_fees(
     };
 
     // We send the treasury allocation
-    let treasury_message = BankMsg::Send {
-        to_address: contract_info.treasury.to_string(),
-        amount: coins(treasury_allocation.u128(), fund.denom.clone()),
-    };
+    let treasury_messages = _transfer_assets_messages(&contract_info.treasury, treasury_funds)?;
 
     Ok(Response::new()
         .add_attribute("action", "saved_fee")
         .add_attribute("action", "distributed_fee")
-        .add_message(treasury_message)
+        .add_messages(treasury_messages)
         .add_messages(fee_withdrawal_messages))
 }
 
@@ -251,26 +358,118 @@ pub fn withdraw_fees(
 
 /// Internal function
 /// It withdraws the fees for tokens with associated addresses in the list provided in argument
+/// Fees of addresses with a vesting schedule are not sent, they start vesting instead
+/// (or go back to the treasury if the schedule was revoked)
 pub fn _withdraw_registered_addresses(
     deps: DepsMut,
     _env: Env,
     _info: MessageInfo,
     addresses: Vec<String>,
-) -> StdResult<Vec<BankMsg>> {
+) -> StdResult<Vec<CosmosMsg>> {
     let addresses: Vec<String> = addresses.into_iter().unique().collect();
     let mut messages = vec![];
     for address in &addresses {
         let valid_address = deps.api.addr_validate(address)?;
-        let loaded_funds = ALLOCATED_FUNDS.load(deps.storage, &valid_address);
-        let associated_address = ASSOCIATED_FEE_ADDRESS.load(deps.storage, &valid_address);
-        if let (Ok(loaded_funds), Ok(associated_address)) = (loaded_funds, associated_address) {
-            messages.push(BankMsg::Send {
-                to_address: associated_address.to_string(),
-                amount: loaded_funds,
-            });
-            ALLOCATED_FUNDS.save(deps.storage, &valid_address, &vec![])?;
+        if let Some(schedule) = VESTING_SCHEDULES.may_load(deps.storage, &valid_address)? {
+            let loaded_assets = _take_allocated_assets(deps.storage, &valid_address)?;
+            if schedule.revoked {
+                let treasury = CONTRACT_INFO.load(deps.storage)?.treasury;
+                messages.extend(_transfer_assets_messages(&treasury, loaded_assets)?);
+            } else {
+                _add_vesting_assets(deps.storage, &valid_address, loaded_assets)?;
+            }
+        } else if let Ok(associated_address) =
+            ASSOCIATED_FEE_ADDRESS.load(deps.storage, &valid_address)
+        {
+            let loaded_assets = _take_allocated_assets(deps.storage, &valid_address)?;
+            messages.extend(_transfer_assets_messages(
+                &associated_address,
+                loaded_assets,
+            )?);
+        }
+    }
+    Ok(messages)
+}
+
+/// Internal function
+/// Adds the assets to the balances of a project address
+pub fn _allocate_assets(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    assets: Vec<(AssetInfo, Uint128)>,
+) -> StdResult<()> {
+    PROJECT_ADDRESSES.save(storage, address, &true)?;
+    for (asset, amount) in assets {
+        ALLOCATED_ASSETS.update::<_, StdError>(storage, (address, &asset.to_string()), |x| {
+            Ok(x.unwrap_or_default() + amount)
+        })?;
+    }
+    Ok(())
+}
+
+/// Internal function
+/// Loads all the asset balances of a project address
+pub fn _load_allocated_assets(
+    storage: &dyn Storage,
+    address: &Addr,
+) -> StdResult<Vec<(AssetInfo, Uint128)>> {
+    ALLOCATED_ASSETS
+        .prefix(address)
+        .range(storage, None, None, Order::Ascending)
+        .map(|x| x.and_then(|(asset, amount)| Ok((asset.parse()?, amount))))
+        .collect()
+}
+
+/// Internal function
+/// Loads and removes all the asset balances of a project address
+pub fn _take_allocated_assets(
+    storage: &mut dyn Storage,
+    address: &Addr,
+) -> StdResult<Vec<(AssetInfo, Uint128)>> {
+    let loaded_assets = _load_allocated_assets(storage, address)?;
+    for (asset, _) in &loaded_assets {
+        ALLOCATED_ASSETS.remove(storage, (address, &asset.to_string()));
+    }
+    Ok(loaded_assets)
+}
+
+/// Internal function
+/// Creates the messages needed to send assets to a recipient
+/// Native coins are grouped in a single bank message and empty amounts are not sent
+pub fn _transfer_assets_messages(
+    recipient: &Addr,
+    assets: Vec<(AssetInfo, Uint128)>,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut coins = vec![];
+    let mut cw20_messages: Vec<CosmosMsg> = vec![];
+    for (asset, amount) in assets.into_iter().filter(|(_, amount)| !amount.is_zero()) {
+        match asset {
+            AssetInfo::Coin(denom) => coins.push(Coin { denom, amount }),
+            AssetInfo::Cw20(address) => cw20_messages.push(
+                WasmMsg::Execute {
+                    contract_addr: address,
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: recipient.to_string(),
+                        amount,
+                    })?,
+                    funds: vec![],
+                }
+                .into(),
+            ),
         }
     }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !coins.is_empty() {
+        messages.push(
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins,
+            }
+            .into(),
+        );
+    }
+    messages.extend(cw20_messages);
     Ok(messages)
 }
 
@@ -279,9 +478,13 @@ pub fn contract_info(deps: Deps) -> StdResult<ContractInfo> {
 }
 
 /// Query the amount of fee deposited in the contract for a given token address (cw721 and cw1155 supposedly)
-pub fn query_amount(deps: Deps, address: String) -> StdResult<Vec<Coin>> {
+/// Returns the balance of every asset type deposited for this address
+pub fn query_amount(deps: Deps, address: String) -> StdResult<Vec<AssetBalance>> {
     let address = deps.api.addr_validate(&address)?;
-    ALLOCATED_FUNDS.load(deps.storage, &address).or(Ok(vec![]))
+    Ok(_load_allocated_assets(deps.storage, &address)?
+        .into_iter()
+        .map(|(asset, amount)| AssetBalance { asset, amount })
+        .collect())
 }
 
 pub fn query_addresses(
@@ -293,7 +496,7 @@ pub fn query_addresses(
     let addr = maybe_addr(deps.api, start_after)?;
     let start = addr.as_ref().map(Bound::exclusive);
 
-    ALLOCATED_FUNDS
+    PROJECT_ADDRESSES
         .keys(deps.storage, start, None, Order::Ascending)
         .take(limit)
         .map(|x| x.map(|s| s.to_string()))
@@ -304,10 +507,12 @@ pub fn query_addresses(
 pub mod tests {
     use super::*;
     use cosmwasm_std::{
-        from_binary,
+        coin, coins, from_binary,
         testing::{mock_dependencies, mock_env, mock_info},
         SubMsg,
     };
+    use fee_distributor_export::msg::{VestingAssetResponse, VestingResponse};
+    use fee_distributor_export::state::StakerInfo;
     //use cosmwasm_std::{coins, Coin, SubMsg};
 
     fn init_helper(deps: DepsMut) -> Response {
@@ -315,6 +520,7 @@ pub mod tests {
             name: "fee_contract".to_string(),
             owner: None,
             treasury: "treasury".to_string(),
+            staking_token: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -346,6 +552,9 @@ pub mod tests {
                 treasury: Some("newtreasury".to_string()),
                 projects_allocation_for_assets_fee: Some(Uint128::from(34u128)),
                 projects_allocation_for_funds_fee: Some(Uint128::from(34u128)),
+                staking_token: None,
+                stakers_allocation: None,
+                unbonding_period: None,
             },
         )
         .unwrap();
@@ -359,6 +568,9 @@ pub mod tests {
                 treasury: Some("newtreasury".to_string()),
                 projects_allocation_for_assets_fee: Some(Uint128::from(34u128)),
                 projects_allocation_for_funds_fee: Some(Uint128::from(34u128)),
+                staking_token: None,
+                stakers_allocation: None,
+                unbonding_period: None,
             },
         )
         .unwrap_err();
@@ -391,11 +603,13 @@ pub mod tests {
         )
         .unwrap();
         assert_eq!(
-            from_binary::<Vec<Coin>>(&response).unwrap(),
-            coins(
-                54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE / PROJECT_ALLOCATION_MAX_PERCENTAGE,
-                "uluna"
-            )
+            from_binary::<Vec<AssetBalance>>(&response).unwrap(),
+            vec![AssetBalance {
+                asset: AssetInfo::Coin("uluna".to_string()),
+                amount: Uint128::from(
+                    54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE / PROJECT_ALLOCATION_MAX_PERCENTAGE
+                )
+            }]
         );
 
         let response = execute(
@@ -461,13 +675,15 @@ pub mod tests {
         )
         .unwrap();
         assert_eq!(
-            from_binary::<Vec<Coin>>(&response).unwrap(),
-            coins(
-                54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE / PROJECT_ALLOCATION_MAX_PERCENTAGE
-                    + 54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE
-                        / PROJECT_ALLOCATION_MAX_PERCENTAGE,
-                "uluna"
-            )
+            from_binary::<Vec<AssetBalance>>(&response).unwrap(),
+            vec![AssetBalance {
+                asset: AssetInfo::Coin("uluna".to_string()),
+                amount: Uint128::from(
+                    54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE / PROJECT_ALLOCATION_MAX_PERCENTAGE
+                        + 54u128 * PROJECTS_ALLOCATION_FOR_ASSETS_FEE
+                            / PROJECT_ALLOCATION_MAX_PERCENTAGE
+                )
+            }]
         );
 
         execute(
@@ -524,7 +740,7 @@ pub mod tests {
             },
         )
         .unwrap();
-        assert_eq!(from_binary::<Vec<Coin>>(&response).unwrap(), vec![]);
+        assert_eq!(from_binary::<Vec<AssetBalance>>(&response).unwrap(), vec![]);
 
         let response = execute(
             deps.as_mut(),
@@ -568,7 +784,7 @@ pub mod tests {
             },
         )
         .unwrap();
-        assert_eq!(from_binary::<Vec<Coin>>(&response).unwrap(), vec![]);
+        assert_eq!(from_binary::<Vec<AssetBalance>>(&response).unwrap(), vec![]);
     }
 
     #[test]
@@ -629,4 +845,685 @@ pub mod tests {
             ["test4", "test5", "test6", "test7", "test8", "test9"]
         );
     }
+
+    #[test]
+    fn test_deposit_multiple_denoms() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        let info = mock_info("creator", &[coin(1000u128, "uluna"), coin(500u128, "uusd")]);
+        let env = mock_env();
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(400u128, "uluna"), coin(200u128, "uusd")]
+            })]
+        );
+
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Amount {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<Vec<AssetBalance>>(&response).unwrap(),
+            vec![
+                AssetBalance {
+                    asset: AssetInfo::Coin("uluna".to_string()),
+                    amount: Uint128::from(600u128)
+                },
+                AssetBalance {
+                    asset: AssetInfo::Coin("uusd".to_string()),
+                    amount: Uint128::from(300u128)
+                }
+            ]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::DepositNotCorrect {})
+    }
+
+    #[test]
+    fn test_deposit_cw20() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        let env = mock_env();
+        let receive_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128::from(1000u128),
+            msg: to_binary(&ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            })
+            .unwrap(),
+        });
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("token", &[]),
+            receive_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "treasury".to_string(),
+                    amount: Uint128::from(400u128)
+                })
+                .unwrap(),
+                funds: vec![]
+            })]
+        );
+
+        // Native and cw20 balances are kept side by side
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(1000u128, "uluna")),
+            ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::AddAssociatedAddress {
+                address: "test".to_string(),
+                fee_address: "fee".to_string(),
+            },
+        )
+        .unwrap();
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::WithdrawFees {
+                addresses: vec!["test".to_string()],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "fee".to_string(),
+                    amount: coins(600u128, "uluna")
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "fee".to_string(),
+                        amount: Uint128::from(600u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
+        );
+
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Amount {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(from_binary::<Vec<AssetBalance>>(&response).unwrap(), vec![]);
+
+        // Only deposit messages can be sent along with cw20 tokens
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("token", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "creator".to_string(),
+                amount: Uint128::from(1000u128),
+                msg: to_binary(&ExecuteMsg::WithdrawFees {
+                    addresses: vec!["test".to_string()],
+                })
+                .unwrap(),
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {})
+    }
+
+    fn stake_helper(deps: DepsMut, staker: &str, amount: u128) -> Result<Response, ContractError> {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("iliq", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: staker.to_string(),
+                amount: Uint128::from(amount),
+                msg: to_binary(&ExecuteMsg::Stake {}).unwrap(),
+            }),
+        )
+    }
+
+    fn setup_staking(deps: DepsMut) {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ModifyContractInfo {
+                owner: None,
+                treasury: None,
+                projects_allocation_for_assets_fee: None,
+                projects_allocation_for_funds_fee: None,
+                staking_token: Some("iliq".to_string()),
+                stakers_allocation: Some(Uint128::from(500u128)),
+                unbonding_period: Some(100u64),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_staking_rewards() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+        setup_staking(deps.as_mut());
+        let env = mock_env();
+
+        // Only the staking token can be staked
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other_token", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "staker".to_string(),
+                amount: Uint128::from(100u128),
+                msg: to_binary(&ExecuteMsg::Stake {}).unwrap(),
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongStakingToken {});
+
+        // Without stakers, everything goes to the treasury
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(1000u128, "uluna")),
+            ExecuteMsg::DepositFees {
+                addresses: vec![],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(1000u128, "uluna")
+            })]
+        );
+
+        stake_helper(deps.as_mut(), "staker", 100u128).unwrap();
+        stake_helper(deps.as_mut(), "staker1", 300u128).unwrap();
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(1000u128, "uluna")),
+            ExecuteMsg::DepositFees {
+                addresses: vec![],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(500u128, "uluna")
+            })]
+        );
+
+        // A new staker doesn't get the rewards distributed before
+        stake_helper(deps.as_mut(), "staker2", 400u128).unwrap();
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::PendingRewards {
+                address: "staker2".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(from_binary::<Vec<AssetBalance>>(&response).unwrap(), vec![]);
+
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::PendingRewards {
+                address: "staker1".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<Vec<AssetBalance>>(&response).unwrap(),
+            vec![AssetBalance {
+                asset: AssetInfo::Coin("uluna".to_string()),
+                amount: Uint128::from(375u128)
+            }]
+        );
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staker", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "staker".to_string(),
+                amount: coins(125u128, "uluna")
+            })]
+        );
+        let response = execute(
+            deps.as_mut(),
+            env,
+            mock_info("staker", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(response.messages, vec![]);
+    }
+
+    #[test]
+    fn test_unstake() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+        setup_staking(deps.as_mut());
+        let mut env = mock_env();
+
+        stake_helper(deps.as_mut(), "staker", 100u128).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staker", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::from(101u128),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotEnoughStaked {
+                staked: Uint128::from(100u128)
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staker", &[]),
+            ExecuteMsg::Unstake {
+                amount: Uint128::from(100u128),
+            },
+        )
+        .unwrap();
+        let response = query(deps.as_ref(), env.clone(), QueryMsg::TotalStaked {}).unwrap();
+        assert_eq!(from_binary::<Uint128>(&response).unwrap(), Uint128::zero());
+
+        // Unstaked tokens don't earn rewards anymore
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &coins(1000u128, "uluna")),
+            ExecuteMsg::DepositFees {
+                addresses: vec![],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::PendingRewards {
+                address: "staker".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(from_binary::<Vec<AssetBalance>>(&response).unwrap(), vec![]);
+
+        // The tokens are released after the unbonding period
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staker", &[]),
+            ExecuteMsg::WithdrawUnbonded {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToWithdraw {});
+
+        env.block.time = env.block.time.plus_seconds(100u64);
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staker", &[]),
+            ExecuteMsg::WithdrawUnbonded {},
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "iliq".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "staker".to_string(),
+                    amount: Uint128::from(100u128)
+                })
+                .unwrap(),
+                funds: vec![]
+            })]
+        );
+
+        let response = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::StakerInfo {
+                address: "staker".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<StakerInfo>(&response).unwrap(),
+            StakerInfo::default()
+        );
+    }
+
+    #[test]
+    fn test_staking_rewards_dust() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+        setup_staking(deps.as_mut());
+
+        stake_helper(deps.as_mut(), "staker", 3u128).unwrap();
+
+        // 500 can't be split evenly between 3 tokens, the remainder goes to the treasury
+        let response = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(1000u128, "uluna")),
+            ExecuteMsg::DepositFees {
+                addresses: vec![],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(501u128, "uluna")
+            })]
+        );
+
+        let response = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("staker", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "staker".to_string(),
+                amount: coins(499u128, "uluna")
+            })]
+        );
+    }
+
+    #[test]
+    fn test_migrate_unbonding_period() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        // Contracts stored before staking have no unbonding period
+        let mut contract_info = CONTRACT_INFO.load(&deps.storage).unwrap();
+        contract_info.unbonding_period = 0;
+        CONTRACT_INFO
+            .save(deps.as_mut().storage, &contract_info)
+            .unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let contract_info = CONTRACT_INFO.load(&deps.storage).unwrap();
+        assert_eq!(contract_info.unbonding_period, UNBONDING_PERIOD);
+    }
+
+    #[test]
+    fn test_vesting() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+        let mut env = mock_env();
+        let info = mock_info("creator", &coins(1000u128, "uluna"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::AddAssociatedAddress {
+                address: "test".to_string(),
+                fee_address: "fee".to_string(),
+            },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::SetVestingSchedule {
+                address: "test".to_string(),
+                start: None,
+                cliff: 1001u64,
+                duration: 1000u64,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongVestingSchedule {});
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::SetVestingSchedule {
+                address: "test".to_string(),
+                start: None,
+                cliff: 100u64,
+                duration: 1000u64,
+            },
+        )
+        .unwrap();
+
+        // The project fees are not sent anymore
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(400u128, "uluna")
+            })]
+        );
+
+        // Nothing is released before the cliff
+        env.block.time = env.block.time.plus_seconds(50u64);
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Claim {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(response.messages, vec![]);
+
+        env.block.time = env.block.time.plus_seconds(450u64);
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Claim {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "fee".to_string(),
+                amount: coins(300u128, "uluna")
+            })]
+        );
+
+        // Only the admin can revoke a vesting schedule
+        env.block.time = env.block.time.plus_seconds(250u64);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Revoke {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Revoke {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(150u128, "uluna")
+            })]
+        );
+
+        let response = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Vesting {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        let vesting = from_binary::<VestingResponse>(&response).unwrap();
+        assert!(vesting.schedule.unwrap().revoked);
+        assert_eq!(
+            vesting.balances,
+            vec![VestingAssetResponse {
+                asset: AssetInfo::Coin("uluna".to_string()),
+                total: Uint128::from(450u128),
+                released: Uint128::from(300u128),
+                claimable: Uint128::from(150u128),
+            }]
+        );
+
+        // The vested fees can still be claimed, new fees go to the treasury
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Claim {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "fee".to_string(),
+                amount: coins(150u128, "uluna")
+            })]
+        );
+
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositFees {
+                addresses: vec!["test".to_string()],
+                fee_type: FeeType::Assets,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(400u128, "uluna")
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(600u128, "uluna")
+                })
+            ]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            ExecuteMsg::Revoke {
+                address: "test".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::VestingRevoked {});
+    }
 }
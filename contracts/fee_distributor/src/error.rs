@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -9,9 +9,36 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
-    #[error("Fee not paid correctly, please provide only one native asset at a time")]
+    #[error("Fee not paid correctly, please provide at least one asset")]
     DepositNotCorrect {},
 
     #[error("Projects fee allocation cannot be higher than 100%")]
     AllocationTooHigh {},
+
+    #[error("Only the staking token can be staked")]
+    WrongStakingToken {},
+
+    #[error("The staking token can't be changed once it is set")]
+    StakingTokenAlreadySet {},
+
+    #[error("You can't unstake more than what you staked. Currently staked : {staked:?}")]
+    NotEnoughStaked { staked: Uint128 },
+
+    #[error("No unstaked tokens are ready to be withdrawn")]
+    NothingToWithdraw {},
+
+    #[error("No vesting schedule is registered for this address")]
+    NoVestingSchedule {},
+
+    #[error("A vesting schedule is already registered for this address")]
+    VestingScheduleExists {},
+
+    #[error("The vesting cliff can't be longer than the vesting duration")]
+    WrongVestingSchedule {},
+
+    #[error("The vesting schedule of this address was revoked")]
+    VestingRevoked {},
+
+    #[error("No associated address is registered to receive the fees of this address")]
+    NoAssociatedAddress {},
 }
@@ -1,3 +1,5 @@
 pub mod contract;
 mod error;
+pub mod staking;
 pub mod state;
+pub mod vesting;
@@ -0,0 +1,236 @@
+use cosmwasm_std::{
+    to_binary, Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError,
+    StdResult, Storage, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+
+use fee_distributor_export::msg::AssetBalance;
+use fee_distributor_export::state::{AssetInfo, StakerInfo, UnbondingInfo};
+
+use crate::contract::_transfer_assets_messages;
+use crate::error::ContractError;
+use crate::state::{
+    CONTRACT_INFO, PENDING_REWARDS, REWARD_PER_TOKEN, STAKERS, STAKER_REWARD_PER_TOKEN,
+    TOTAL_STAKED,
+};
+
+/// Stake tokens to get a share of the fees deposited in the contract
+/// This function is called when the staking token (ILIQ) is sent to the contract using a send message
+/// The rewards accrued before this function is called are saved for the staker
+pub fn stake(
+    deps: DepsMut,
+    _env: Env,
+    token: Addr,
+    staker: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if contract_info.staking_token != Some(token) {
+        return Err(ContractError::WrongStakingToken {});
+    }
+    let staker = deps.api.addr_validate(&staker)?;
+
+    let mut staker_info = STAKERS.may_load(deps.storage, &staker)?.unwrap_or_default();
+    _update_staker_rewards(deps.storage, &staker, staker_info.staked)?;
+
+    staker_info.staked += amount;
+    STAKERS.save(deps.storage, &staker, &staker_info)?;
+    let total_staked = TOTAL_STAKED.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_STAKED.save(deps.storage, &(total_staked + amount))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "stake")
+        .add_attribute("staker", staker)
+        .add_attribute("amount", amount))
+}
+
+/// Unstake tokens from the staking pool
+/// The tokens stop earning rewards immediately
+/// They can be withdrawn using WithdrawUnbonded once the unbonding period has passed
+pub fn unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let mut staker_info = STAKERS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if amount.is_zero() || amount > staker_info.staked {
+        return Err(ContractError::NotEnoughStaked {
+            staked: staker_info.staked,
+        });
+    }
+    _update_staker_rewards(deps.storage, &info.sender, staker_info.staked)?;
+
+    let release_at = env.block.time.plus_seconds(contract_info.unbonding_period);
+    staker_info.staked -= amount;
+    staker_info
+        .unbonding
+        .push(UnbondingInfo { amount, release_at });
+    STAKERS.save(deps.storage, &info.sender, &staker_info)?;
+    let total_staked = TOTAL_STAKED.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_STAKED.save(deps.storage, &(total_staked - amount))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unstake")
+        .add_attribute("staker", info.sender)
+        .add_attribute("amount", amount)
+        .add_attribute("release_at", release_at.to_string()))
+}
+
+/// Withdraw all the unstaked tokens for which the unbonding period has passed
+pub fn withdraw_unbonded(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let mut staker_info = STAKERS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+
+    let (released, unbonding): (Vec<UnbondingInfo>, Vec<UnbondingInfo>) = staker_info
+        .unbonding
+        .into_iter()
+        .partition(|unbonding| unbonding.release_at <= env.block.time);
+    let amount: Uint128 = released.iter().map(|unbonding| unbonding.amount).sum();
+    if amount.is_zero() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+    staker_info.unbonding = unbonding;
+    STAKERS.save(deps.storage, &info.sender, &staker_info)?;
+
+    // The staking token can't be changed once it's set, so it's necessarily defined here
+    let staking_token = contract_info
+        .staking_token
+        .ok_or(ContractError::WrongStakingToken {})?;
+    let message = WasmMsg::Execute {
+        contract_addr: staking_token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: info.sender.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_unbonded")
+        .add_attribute("staker", info.sender)
+        .add_attribute("amount", amount)
+        .add_message(message))
+}
+
+/// Claim the staking rewards accrued in every fee asset
+pub fn claim_rewards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let staker_info = STAKERS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    _update_staker_rewards(deps.storage, &info.sender, staker_info.staked)?;
+
+    let rewards = PENDING_REWARDS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, Uint128)>>>()?;
+    let mut claimed = vec![];
+    for (asset, amount) in rewards {
+        PENDING_REWARDS.remove(deps.storage, (&info.sender, &asset));
+        claimed.push((asset.parse::<AssetInfo>()?, amount));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_rewards")
+        .add_attribute("staker", info.sender.clone())
+        .add_messages(_transfer_assets_messages(&info.sender, claimed)?))
+}
+
+/// Internal function
+/// Adds the stakers share of a deposit to the reward accumulator of the asset
+/// Returns the amount that was actually allocated to stakers
+/// Nothing is allocated when no tokens are staked, the funds then go to the treasury
+pub fn _allocate_to_stakers(
+    storage: &mut dyn Storage,
+    asset: &AssetInfo,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let total_staked = TOTAL_STAKED.may_load(storage)?.unwrap_or_default();
+    if total_staked.is_zero() || amount.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    // The rounding dust of this division is not allocated and stays with the treasury
+    let reward_per_token_delta = Decimal::from_ratio(amount, total_staked);
+    REWARD_PER_TOKEN.update::<_, StdError>(storage, &asset.to_string(), |x| {
+        Ok(x.unwrap_or_default() + reward_per_token_delta)
+    })?;
+    Ok(total_staked * reward_per_token_delta)
+}
+
+/// Internal function
+/// Saves the rewards earned by a staker since its last update, for every fee asset
+/// This has to be called before any change to the staked amount
+pub fn _update_staker_rewards(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    staked: Uint128,
+) -> StdResult<()> {
+    let rewards_per_token = REWARD_PER_TOKEN
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, Decimal)>>>()?;
+    for (asset, reward_per_token) in rewards_per_token {
+        let staker_reward_per_token = STAKER_REWARD_PER_TOKEN
+            .may_load(storage, (staker, &asset))?
+            .unwrap_or_default();
+        let earned = staked * (reward_per_token - staker_reward_per_token);
+        if !earned.is_zero() {
+            PENDING_REWARDS.update::<_, StdError>(storage, (staker, &asset), |x| {
+                Ok(x.unwrap_or_default() + earned)
+            })?;
+        }
+        STAKER_REWARD_PER_TOKEN.save(storage, (staker, &asset), &reward_per_token)?;
+    }
+    Ok(())
+}
+
+pub fn query_staker_info(deps: Deps, address: String) -> StdResult<StakerInfo> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(STAKERS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default())
+}
+
+/// Query the rewards a staker can claim, in every fee asset
+pub fn query_pending_rewards(deps: Deps, address: String) -> StdResult<Vec<AssetBalance>> {
+    let address = deps.api.addr_validate(&address)?;
+    let staked = STAKERS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default()
+        .staked;
+
+    REWARD_PER_TOKEN
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (asset, reward_per_token) = item?;
+            let staker_reward_per_token = STAKER_REWARD_PER_TOKEN
+                .may_load(deps.storage, (&address, &asset))?
+                .unwrap_or_default();
+            let pending = PENDING_REWARDS
+                .may_load(deps.storage, (&address, &asset))?
+                .unwrap_or_default();
+            Ok(AssetBalance {
+                asset: asset.parse()?,
+                amount: pending + staked * (reward_per_token - staker_reward_per_token),
+            })
+        })
+        .filter(|balance| !matches!(balance, Ok(AssetBalance { amount, .. }) if amount.is_zero()))
+        .collect()
+}
+
+pub fn query_total_staked(deps: Deps) -> StdResult<Uint128> {
+    Ok(TOTAL_STAKED.may_load(deps.storage)?.unwrap_or_default())
+}
@@ -1,10 +1,26 @@
 use crate::error::ContractError;
-use cosmwasm_std::{Addr, Coin, Deps};
+use cosmwasm_std::{Addr, Coin, Decimal, Deps, Uint128};
 use cw_storage_plus::{Item, Map};
-use fee_distributor_export::state::ContractInfo;
+use fee_distributor_export::state::{ContractInfo, StakerInfo, VestingBalance, VestingSchedule};
 
 pub const CONTRACT_INFO: Item<ContractInfo> = Item::new("contract_info");
-pub const ALLOCATED_FUNDS: Map<&Addr, Vec<Coin>> = Map::new("allocated_funds");
+// Balances are indexed by project address and asset string representation (see AssetInfo)
+pub const ALLOCATED_ASSETS: Map<(&Addr, &str), Uint128> = Map::new("allocated_assets");
+pub const PROJECT_ADDRESSES: Map<&Addr, bool> = Map::new("project_addresses");
+// Balances were stored as a list of native coins before cw20 support, only read during migration
+pub const LEGACY_ALLOCATED_FUNDS: Map<&Addr, Vec<Coin>> = Map::new("allocated_funds");
+
+// Staking pool, rewards are indexed by asset string representation as well
+pub const TOTAL_STAKED: Item<Uint128> = Item::new("total_staked");
+pub const STAKERS: Map<&Addr, StakerInfo> = Map::new("stakers");
+pub const REWARD_PER_TOKEN: Map<&str, Decimal> = Map::new("reward_per_token");
+pub const STAKER_REWARD_PER_TOKEN: Map<(&Addr, &str), Decimal> =
+    Map::new("staker_reward_per_token");
+pub const PENDING_REWARDS: Map<(&Addr, &str), Uint128> = Map::new("pending_rewards");
+
+// Project fees are moved to the vesting balances instead of being paid out when a schedule exists
+pub const VESTING_SCHEDULES: Map<&Addr, VestingSchedule> = Map::new("vesting_schedules");
+pub const VESTING_BALANCES: Map<(&Addr, &str), VestingBalance> = Map::new("vesting_balances");
 pub const ASSOCIATED_FEE_ADDRESS: Map<&Addr, Addr> = Map::new("associated_fee_address");
 
 pub fn is_admin(deps: Deps, addr: Addr) -> Result<(), ContractError> {
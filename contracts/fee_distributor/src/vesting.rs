@@ -0,0 +1,187 @@
+use cosmwasm_std::{
+    Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Storage,
+    Timestamp, Uint128,
+};
+
+use fee_distributor_export::msg::{VestingAssetResponse, VestingResponse};
+use fee_distributor_export::state::{AssetInfo, VestingBalance, VestingSchedule};
+
+use crate::contract::{
+    _take_allocated_assets, _transfer_assets_messages, _withdraw_registered_addresses,
+};
+use crate::error::ContractError;
+use crate::state::{
+    is_admin, ASSOCIATED_FEE_ADDRESS, CONTRACT_INFO, VESTING_BALANCES, VESTING_SCHEDULES,
+};
+
+/// Register a vesting schedule for the fees of a project address
+/// Admin only action. A schedule can only be registered once per address
+/// The fees allocated to this address will then be released linearly, using the Claim function
+#[allow(clippy::too_many_arguments)]
+pub fn set_vesting_schedule(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+    start: Option<Timestamp>,
+    cliff: u64,
+    duration: u64,
+) -> Result<Response, ContractError> {
+    is_admin(deps.as_ref(), info.sender)?;
+    let address = deps.api.addr_validate(&address)?;
+
+    if VESTING_SCHEDULES.has(deps.storage, &address) {
+        return Err(ContractError::VestingScheduleExists {});
+    }
+    if cliff > duration {
+        return Err(ContractError::WrongVestingSchedule {});
+    }
+
+    let start = start.unwrap_or(env.block.time);
+    VESTING_SCHEDULES.save(
+        deps.storage,
+        &address,
+        &VestingSchedule {
+            start,
+            cliff,
+            duration,
+            revoked: false,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_vesting_schedule")
+        .add_attribute("project_address", address)
+        .add_attribute("start", start.to_string())
+        .add_attribute("cliff", cliff.to_string())
+        .add_attribute("duration", duration.to_string()))
+}
+
+/// Release the vested fees of a project address to its associated address
+/// This function can be called by anyone
+/// The fees waiting in the contract for this address start vesting before anything is released
+pub fn claim(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let valid_address = deps.api.addr_validate(&address)?;
+    let associated_address = ASSOCIATED_FEE_ADDRESS
+        .load(deps.storage, &valid_address)
+        .map_err(|_| ContractError::NoAssociatedAddress {})?;
+    let schedule = VESTING_SCHEDULES
+        .load(deps.storage, &valid_address)
+        .map_err(|_| ContractError::NoVestingSchedule {})?;
+
+    let mut messages =
+        _withdraw_registered_addresses(deps.branch(), env.clone(), info, vec![address])?;
+
+    let mut released_assets = vec![];
+    for (asset, mut balance) in _load_vesting_assets(deps.storage, &valid_address)? {
+        let vested = schedule.vested_amount(balance.total, env.block.time);
+        released_assets.push((asset.clone(), vested - balance.released));
+        balance.released = vested;
+        VESTING_BALANCES.save(deps.storage, (&valid_address, &asset.to_string()), &balance)?;
+    }
+    messages.extend(_transfer_assets_messages(
+        &associated_address,
+        released_assets,
+    )?);
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_vested")
+        .add_attribute("project_address", valid_address)
+        .add_messages(messages))
+}
+
+/// Revoke the vesting schedule of a project address
+/// Admin only action. The fees that are not vested yet are sent back to the treasury
+/// The vested fees can still be claimed, the fees allocated afterwards go to the treasury
+pub fn revoke(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    is_admin(deps.as_ref(), info.sender)?;
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+
+    let mut schedule = VESTING_SCHEDULES
+        .load(deps.storage, &address)
+        .map_err(|_| ContractError::NoVestingSchedule {})?;
+    if schedule.revoked {
+        return Err(ContractError::VestingRevoked {});
+    }
+
+    // The fees waiting in the contract are not vested at all
+    let mut unvested_assets = _take_allocated_assets(deps.storage, &address)?;
+    for (asset, mut balance) in _load_vesting_assets(deps.storage, &address)? {
+        let vested = schedule.vested_amount(balance.total, env.block.time);
+        unvested_assets.push((asset.clone(), balance.total - vested));
+        balance.total = vested;
+        VESTING_BALANCES.save(deps.storage, (&address, &asset.to_string()), &balance)?;
+    }
+    schedule.revoked = true;
+    VESTING_SCHEDULES.save(deps.storage, &address, &schedule)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_vesting")
+        .add_attribute("project_address", address)
+        .add_messages(_transfer_assets_messages(
+            &contract_info.treasury,
+            unvested_assets,
+        )?))
+}
+
+/// Internal function
+/// Adds the assets to the vesting balances of a project address
+pub fn _add_vesting_assets(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    assets: Vec<(AssetInfo, Uint128)>,
+) -> StdResult<()> {
+    for (asset, amount) in assets {
+        VESTING_BALANCES.update::<_, StdError>(storage, (address, &asset.to_string()), |x| {
+            let mut balance = x.unwrap_or_default();
+            balance.total += amount;
+            Ok(balance)
+        })?;
+    }
+    Ok(())
+}
+
+/// Internal function
+/// Loads all the vesting balances of a project address
+pub fn _load_vesting_assets(
+    storage: &dyn Storage,
+    address: &Addr,
+) -> StdResult<Vec<(AssetInfo, VestingBalance)>> {
+    VESTING_BALANCES
+        .prefix(address)
+        .range(storage, None, None, Order::Ascending)
+        .map(|x| x.and_then(|(asset, balance)| Ok((asset.parse()?, balance))))
+        .collect()
+}
+
+/// Query the vesting schedule of a project address and the amounts that can be claimed at the current block
+pub fn query_vesting(deps: Deps, env: Env, address: String) -> StdResult<VestingResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let schedule = VESTING_SCHEDULES.may_load(deps.storage, &address)?;
+
+    let balances = match &schedule {
+        Some(schedule) => _load_vesting_assets(deps.storage, &address)?
+            .into_iter()
+            .map(|(asset, balance)| VestingAssetResponse {
+                asset,
+                total: balance.total,
+                released: balance.released,
+                claimable: schedule.vested_amount(balance.total, env.block.time) - balance.released,
+            })
+            .collect(),
+        None => vec![],
+    };
+
+    Ok(VestingResponse { schedule, balances })
+}
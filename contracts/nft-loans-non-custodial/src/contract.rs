@@ -11,21 +11,33 @@ use nft_loans_export::state::ContractInfo;
 
 use crate::admin::{set_fee_distributor, set_fee_rate, set_owner};
 use crate::admin::claim_ownership;
+use crate::execute::accept_collection_offer;
+use crate::execute::accept_extension;
 use crate::execute::accept_loan;
 use crate::execute::accept_offer;
+use crate::execute::accept_refinance_offer;
 use crate::execute::cancel_offer;
 use crate::execute::deposit_collaterals;
+use crate::execute::make_collection_offer;
 use crate::execute::make_offer;
+use crate::execute::make_refinance_offer;
 use crate::execute::modify_collaterals;
+use crate::execute::native_funds;
+use crate::execute::propose_extension;
+use crate::execute::receive;
+use crate::execute::refund_expired_offer;
 use crate::execute::refuse_offer;
 use crate::execute::repay_borrowed_funds;
+use crate::execute::repay_partial;
 use crate::execute::withdraw_collateral;
 use crate::execute::withdraw_defaulted_loan;
 use crate::execute::withdraw_refused_offer;
 
 use crate::query::{
     query_all_collaterals, query_borrower_info, query_collateral_info, query_collaterals,
-    query_contract_info, query_lender_offers, query_offer_info, query_offers,
+    query_collection_offers,
+    query_contract_info, query_lender_offers, query_loan_extensions, query_offer_info,
+    query_offers,
 };
 use crate::state::CONTRACT_INFO;
 
@@ -76,7 +88,15 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             borrower,
             loan_id,
             comment,
-        } => accept_loan(deps, env, info, borrower, loan_id, comment),
+        } => accept_loan(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            borrower,
+            loan_id,
+            comment,
+        ),
 
         ExecuteMsg::AcceptOffer { global_offer_id } => {
             accept_offer(deps, env, info, global_offer_id)
@@ -86,7 +106,56 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             loan_id,
             terms,
             comment,
-        } => make_offer(deps, env, info, borrower, loan_id, terms, comment),
+            expiration,
+        } => make_offer(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            borrower,
+            loan_id,
+            terms,
+            comment,
+            expiration,
+        ),
+        ExecuteMsg::MakeRefinanceOffer {
+            borrower,
+            loan_id,
+            terms,
+            comment,
+        } => make_refinance_offer(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            borrower,
+            loan_id,
+            terms,
+            comment,
+        ),
+        ExecuteMsg::AcceptRefinanceOffer { global_offer_id } => {
+            accept_refinance_offer(deps, env, info, global_offer_id)
+        }
+        ExecuteMsg::MakeCollectionOffer {
+            collection,
+            terms,
+            expiration,
+            comment,
+        } => make_collection_offer(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            collection,
+            terms,
+            expiration,
+            comment,
+        ),
+        ExecuteMsg::AcceptCollectionOffer {
+            global_offer_id,
+            token,
+            comment,
+        } => accept_collection_offer(deps, env, info, global_offer_id, token, comment),
 
         ExecuteMsg::CancelOffer { global_offer_id } => {
             cancel_offer(deps, env, info, global_offer_id)
@@ -100,9 +169,57 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             withdraw_refused_offer(deps, env, info, global_offer_id)
         }
 
-        ExecuteMsg::RepayBorrowedFunds { loan_id } => {
-            repay_borrowed_funds(deps, env, info, loan_id)
+        ExecuteMsg::RefundExpiredOffer { global_offer_id } => {
+            refund_expired_offer(deps, env, info, global_offer_id)
         }
+
+        ExecuteMsg::RepayBorrowedFunds { loan_id } => repay_borrowed_funds(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            loan_id,
+        ),
+        ExecuteMsg::RepayPartial { loan_id } => repay_partial(
+            deps,
+            env,
+            info.sender.clone(),
+            native_funds(&info)?,
+            loan_id,
+        ),
+        ExecuteMsg::Receive(wrapper) => receive(deps, env, info, wrapper),
+        ExecuteMsg::ProposeExtension {
+            borrower,
+            loan_id,
+            extra_blocks,
+            extra_seconds,
+            extra_interest,
+        } => propose_extension(
+            deps,
+            env,
+            info,
+            borrower,
+            loan_id,
+            extra_blocks,
+            extra_seconds,
+            extra_interest,
+        ),
+        ExecuteMsg::AcceptExtension {
+            borrower,
+            loan_id,
+            extra_blocks,
+            extra_seconds,
+            extra_interest,
+        } => accept_extension(
+            deps,
+            env,
+            info,
+            borrower,
+            loan_id,
+            extra_blocks,
+            extra_seconds,
+            extra_interest,
+        ),
         ExecuteMsg::WithdrawDefaultedLoan { borrower, loan_id } => {
             withdraw_defaulted_loan(deps, env, info, borrower, loan_id)
         }
@@ -130,28 +247,28 @@ fn to_anyhow_binary<T: Serialize>(message: &T) -> Result<Binary> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> Result<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary> {
     match msg {
         QueryMsg::ContractInfo {} => to_anyhow_binary(&query_contract_info(deps)?),
         QueryMsg::BorrowerInfo { borrower } => {
             to_anyhow_binary(&query_borrower_info(deps, borrower)?)
         }
         QueryMsg::CollateralInfo { borrower, loan_id } => {
-            to_anyhow_binary(&query_collateral_info(deps, borrower, loan_id)?)
+            to_anyhow_binary(&query_collateral_info(deps, env, borrower, loan_id)?)
         }
 
         QueryMsg::Collaterals {
             borrower,
             start_after,
             limit,
-        } => to_anyhow_binary(&query_collaterals(deps, borrower, start_after, limit)?),
+        } => to_anyhow_binary(&query_collaterals(deps, env, borrower, start_after, limit)?),
 
         QueryMsg::AllCollaterals { start_after, limit } => {
-            to_anyhow_binary(&query_all_collaterals(deps, start_after, limit)?)
+            to_anyhow_binary(&query_all_collaterals(deps, env, start_after, limit)?)
         }
 
         QueryMsg::OfferInfo { global_offer_id } => {
-            to_anyhow_binary(&query_offer_info(deps, global_offer_id)?)
+            to_anyhow_binary(&query_offer_info(deps, env, global_offer_id)?)
         }
 
         QueryMsg::Offers {
@@ -159,12 +276,47 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> Result<Binary> {
             loan_id,
             start_after,
             limit,
-        } => to_anyhow_binary(&query_offers(deps, borrower, loan_id, start_after, limit)?),
+            include_expired,
+        } => to_anyhow_binary(&query_offers(
+            deps,
+            env,
+            borrower,
+            loan_id,
+            start_after,
+            limit,
+            include_expired,
+        )?),
 
         QueryMsg::LenderOffers {
             lender,
             start_after,
             limit,
-        } => to_anyhow_binary(&query_lender_offers(deps, lender, start_after, limit)?),
+            include_expired,
+        } => to_anyhow_binary(&query_lender_offers(
+            deps,
+            env,
+            lender,
+            start_after,
+            limit,
+            include_expired,
+        )?),
+
+        QueryMsg::CollectionOffers {
+            collection,
+            start_after,
+            limit,
+            include_expired,
+        } => to_anyhow_binary(&query_collection_offers(
+            deps,
+            env,
+            collection,
+            start_after,
+            limit,
+            include_expired,
+        )?),
+
+        QueryMsg::LoanExtensions { borrower, loan_id } => {
+            to_anyhow_binary(&query_loan_extensions(deps, borrower, loan_id)?)
+        }
     }
 }
@@ -73,4 +73,43 @@ pub enum ContractError {
 
     #[error("The loan has already been defaulted, you can't withdraw the funds again")]
     LoanAlreadyDefaulted {},
+
+    #[error("Installments need a positive number and interval, have to be due before the end of the loan and can't be used with a duration in seconds")]
+    WrongInstallmentSchedule {},
+
+    #[error("This offer doesn't refinance an active loan")]
+    NotRefinanceOffer {},
+
+    #[error("Loans can only be made in native or cw20 tokens")]
+    WrongPrincipleAsset {},
+
+    #[error("This offer is not a collection offer")]
+    NotCollectionOffer {},
+
+    #[error("This token doesn't belong to the offer collection")]
+    WrongCollection {},
+
+    #[error("Collection offers can only be accepted with a cw721 token")]
+    WrongCollectionToken {},
+
+    #[error("This offer has expired")]
+    OfferExpired {},
+
+    #[error("An annual rate can only be used with a duration in seconds and can't exceed 1000%")]
+    WrongInterestRate {},
+
+    #[error("An extension has to add time to the loan duration")]
+    WrongExtension {},
+
+    #[error("There is no extension to accept for this loan")]
+    ExtensionNotFound {},
+
+    #[error("The pending extension doesn't match the accepted terms")]
+    ExtensionMismatch {},
+
+    #[error("Loans paid back in installments can't be extended")]
+    ExtensionOnInstallments {},
+
+    #[error("You can't pay back more than what is still owed : {remaining}")]
+    RepaymentTooHigh { remaining: Uint128 },
 }
@@ -3,27 +3,32 @@ use crate::error::ContractError;
 use anyhow::{bail, Result};
 
 use cosmwasm_std::{
-    coins, Addr, BankMsg, CosmosMsg, DepsMut, Env, MessageInfo, Response, Storage, Decimal,
+    from_binary, to_binary, Addr, BankMsg, CosmosMsg, DepsMut, Env, MessageInfo, Response,
+    Storage, Timestamp, Uint128, Decimal,
 };
 
 use cw1155::Cw1155ExecuteMsg;
+use cw20::Cw20ExecuteMsg;
 use cw721::Cw721ExecuteMsg;
 
+use nft_loans_export::msg::{Cw20ReceiveMsg, ExecuteMsg};
+
 use nft_loans_export::state::{
-    BorrowerInfo, CollateralInfo, LoanState, LoanTerms, OfferInfo, OfferState,
+    BorrowerInfo, CollateralInfo, ContractInfo, LoanExtension, LoanState, LoanTerms, OfferInfo,
+    OfferState,
 };
 
 use utils::msg::into_cosmos_msg;
-use utils::state::{AssetInfo, Cw1155Coin, Cw721Coin};
+use utils::state::{AssetInfo, Cw1155Coin, Cw20Coin, Cw721Coin};
 
 use fee_contract_export::state::FeeType;
 use fee_distributor_export::msg::ExecuteMsg as FeeDistributorMsg;
 
 use crate::state::{
-    can_repay_loan, get_active_loan, get_offer, is_active_lender, is_collateral_withdrawable,
+    asset_amount, asset_currency, asset_with_amount, is_same_currency, assert_valid_terms, can_repay_loan, get_active_loan, get_offer, is_active_lender, is_collateral_withdrawable,
     is_lender, is_loan_acceptable, is_loan_counterable, is_loan_defaulted, is_loan_modifiable,
-    is_offer_borrower, is_offer_refusable, lender_offers, save_offer, BORROWER_INFO,
-    COLLATERAL_INFO, CONTRACT_INFO,
+    is_offer_borrower, is_offer_refusable, elapsed_seconds, lender_offers, save_offer, BORROWER_INFO,
+    COLLATERAL_INFO, CONTRACT_INFO, LOAN_EXTENSIONS, PENDING_EXTENSIONS,
 };
 
 /// Signals the deposit of multiple collaterals in the same loan.
@@ -48,16 +53,36 @@ pub fn deposit_collaterals(
     // We can't verify the user has given the contract authorization to transfer their tokens.
     // (because the cw721 standard has changed the messages are always not the same for all cw721 nfts)
     // But we don't actually need it, we will need to secure the frontend to avoid user frustration
+    let loan_id = _deposit_collaterals_raw(deps.storage, env, borrower.clone(), tokens, terms, comment)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit-collateral")
+        .add_attribute("borrower", borrower)
+        .add_attribute("loan_id", loan_id.to_string()))
+}
 
+// Internal function used to save new collaterals for a borrower
+// It returns the loan_id assigned to the collaterals
+fn _deposit_collaterals_raw(
+    storage: &mut dyn Storage,
+    env: Env,
+    borrower: Addr,
+    tokens: Vec<AssetInfo>,
+    terms: Option<LoanTerms>,
+    comment: Option<String>,
+) -> Result<u64> {
     // First we validate at least one asset was provided to the raffle (or else this is useless, we want the raffles to include NFTs)
     if tokens.is_empty(){
         bail!(ContractError::NoAssets {  })
     }
+    if let Some(terms) = &terms {
+        assert_valid_terms(terms)?;
+    }
 
     // We save the collateral info in our internal structure
     // First we update the number of collateral a user has deposited (to make sure the id assigned is unique)
     let loan_id = BORROWER_INFO
-        .update::<_, anyhow::Error>(deps.storage, &borrower, |x| match x {
+        .update::<_, anyhow::Error>(storage, &borrower, |x| match x {
             Some(mut info) => {
                 info.last_collateral_id += 1;
                 Ok(info)
@@ -67,8 +92,8 @@ pub fn deposit_collaterals(
         .last_collateral_id;
     // Then we save an collateral info object
     COLLATERAL_INFO.save(
-        deps.storage,
-        (borrower.clone(), loan_id),
+        storage,
+        (borrower, loan_id),
         &CollateralInfo {
             terms,
             associated_assets: tokens,
@@ -78,10 +103,7 @@ pub fn deposit_collaterals(
         },
     )?;
 
-    Ok(Response::new()
-        .add_attribute("action", "deposit-collateral")
-        .add_attribute("borrower", borrower)
-        .add_attribute("loan_id", loan_id.to_string()))
+    Ok(loan_id)
 }
 
 /// Change the loan terms (and the comment) of a loan.
@@ -96,6 +118,9 @@ pub fn modify_collaterals(
     comment: Option<String>,
 ) -> Result<Response> {
     let borrower = info.sender;
+    if let Some(terms) = &terms {
+        assert_valid_terms(terms)?;
+    }
 
     COLLATERAL_INFO.update(
         deps.storage,
@@ -154,23 +179,34 @@ pub fn withdraw_collateral(
 // It verifies an offer can be made for the current loan
 // It verifies the sent funds match the principle indicated in the terms
 // And then saves the new offer in the internal storage
+#[allow(clippy::too_many_arguments)]
 fn _make_offer_raw(
     storage: &mut dyn Storage,
     env: Env,
-    info: MessageInfo,
+    lender: Addr,
+    sent_funds: AssetInfo,
     borrower: Addr,
     loan_id: u64,
     terms: LoanTerms,
     comment: Option<String>,
+    refinance: bool,
+    expiration: Option<Timestamp>,
 ) -> Result<(String, u64)> {
     let mut collateral: CollateralInfo =
         COLLATERAL_INFO.load(storage, (borrower.clone(), loan_id))?;
-    is_loan_counterable(&collateral)?;
+    // Refinance offers are made on active loans only
+    if refinance {
+        can_repay_loan(storage, env.clone(), &collateral)?;
+    } else {
+        is_loan_counterable(&collateral)?;
+    }
+    assert_valid_terms(&terms)?;
+    if expiration.filter(|expiration| env.block.time >= *expiration).is_some() {
+        bail!(ContractError::OfferExpired {});
+    }
 
     // Make sure the transaction contains funds that match the principle indicated in the terms
-    if info.funds.len() != 1 {
-        bail!(ContractError::MultipleCoins {});
-    } else if terms.principle != info.funds[0].clone() {
+    if terms.principle != sent_funds {
         bail!(ContractError::FundsDontMatchTerms {});
     }
 
@@ -187,7 +223,7 @@ fn _make_offer_raw(
         storage,
         &contract_config.global_offer_index.to_string(),
         &OfferInfo {
-            lender: info.sender,
+            lender,
             borrower,
             loan_id,
             offer_id,
@@ -196,6 +232,9 @@ fn _make_offer_raw(
             list_date: env.block.time,
             deposited_funds: Some(terms.principle),
             comment,
+            refinance,
+            collection: None,
+            expiration,
         },
     )?;
 
@@ -206,14 +245,18 @@ fn _make_offer_raw(
 
 /// Make an offer (offer some terms) to lend some money against someone's collateral
 /// The borrower will then be able to accept those terms if they please them
+/// The offer can't be accepted once it expires, the funds can then be refunded (`refund_expired_offer`)
+#[allow(clippy::too_many_arguments)]
 pub fn make_offer(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo,
+    lender: Addr,
+    sent_funds: AssetInfo,
     borrower: String,
     loan_id: u64,
     terms: LoanTerms,
     comment: Option<String>,
+    expiration: Option<Timestamp>,
 ) -> Result<Response> {
     // We query the loan info
 
@@ -221,21 +264,116 @@ pub fn make_offer(
     let (global_offer_id, _offer_id) = _make_offer_raw(
         deps.storage,
         env,
-        info.clone(),
+        lender.clone(),
+        sent_funds,
         borrower.clone(),
         loan_id,
         terms,
         comment,
+        false,
+        expiration,
     )?;
 
     Ok(Response::new()
         .add_attribute("action", "make-offer")
         .add_attribute("borrower", borrower)
-        .add_attribute("lender", info.sender)
+        .add_attribute("lender", lender)
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("global_offer_id", global_offer_id))
+}
+
+/// Make an offer to refinance a loan that already started
+/// If the borrower accepts it, the principle of the new offer pays back the current lender
+/// and the loan goes on with the new terms
+#[allow(clippy::too_many_arguments)]
+pub fn make_refinance_offer(
+    deps: DepsMut,
+    env: Env,
+    lender: Addr,
+    sent_funds: AssetInfo,
+    borrower: String,
+    loan_id: u64,
+    terms: LoanTerms,
+    comment: Option<String>,
+) -> Result<Response> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+    let (global_offer_id, _offer_id) = _make_offer_raw(
+        deps.storage,
+        env,
+        lender.clone(),
+        sent_funds,
+        borrower.clone(),
+        loan_id,
+        terms,
+        comment,
+        true,
+        None,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "make-refinance-offer")
+        .add_attribute("borrower", borrower)
+        .add_attribute("lender", lender)
         .add_attribute("loan_id", loan_id.to_string())
         .add_attribute("global_offer_id", global_offer_id))
 }
 
+/// Make a standing offer to lend some money against any token of a collection
+/// The funds are escrowed until a token holder accepts the offer (`accept_collection_offer`) or the lender cancels it
+/// The offer isn't linked to any loan yet, the contract stands as borrower until the offer is accepted
+/// Until then, the offer is not indexed by borrower nor by loan
+#[allow(clippy::too_many_arguments)]
+pub fn make_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    lender: Addr,
+    sent_funds: AssetInfo,
+    collection: String,
+    terms: LoanTerms,
+    expiration: Timestamp,
+    comment: Option<String>,
+) -> Result<Response> {
+    let collection = deps.api.addr_validate(&collection)?;
+    assert_valid_terms(&terms)?;
+    if env.block.time >= expiration {
+        bail!(ContractError::OfferExpired {});
+    }
+
+    // Make sure the transaction contains funds that match the principle indicated in the terms
+    if terms.principle != sent_funds {
+        bail!(ContractError::FundsDontMatchTerms {});
+    }
+
+    let mut contract_config = CONTRACT_INFO.load(deps.storage)?;
+    contract_config.global_offer_index += 1;
+    let global_offer_id = contract_config.global_offer_index.to_string();
+    save_offer(
+        deps.storage,
+        &global_offer_id,
+        OfferInfo {
+            lender: lender.clone(),
+            borrower: env.contract.address,
+            loan_id: 0,
+            offer_id: 0,
+            terms: terms.clone(),
+            state: OfferState::Published,
+            list_date: env.block.time,
+            deposited_funds: Some(terms.principle),
+            comment,
+            refinance: false,
+            collection: Some(collection.to_string()),
+            expiration: Some(expiration),
+        },
+    )?;
+    CONTRACT_INFO.save(deps.storage, &contract_config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "make-collection-offer")
+        .add_attribute("collection", collection)
+        .add_attribute("lender", lender)
+        .add_attribute("global_offer_id", global_offer_id))
+}
+
 /// Cancel an offer you made in case the market changes or whatever
 /// The borrower won't be able to accept the loan if you cancel it
 /// You get the assets you offered back when calling this message
@@ -258,9 +396,13 @@ pub fn cancel_offer(
     // We query the loan info
     let borrower = offer_info.borrower.clone();
     let loan_id = offer_info.loan_id;
-    let collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
     // We can cancel an offer only if the Borrower is still searching for a loan (the loan is modifyable)
-    is_loan_modifiable(&collateral)?;
+    // Refinance offers are made on started loans and can be cancelled as long as they are published
+    // Published collection offers are not linked to any loan yet
+    if !offer_info.refinance && offer_info.collection.is_none() {
+        let collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
+        is_loan_modifiable(&collateral)?;
+    }
 
     // The funds deposited for lending are withdrawn
     let withdraw_response = _withdraw_offer_unsafe(lender.clone(), &offer_info)?;
@@ -315,23 +457,74 @@ pub fn withdraw_refused_offer(
         .add_attribute("global_offer_id", global_offer_id))
 }
 
+/// Refund the funds of an expired offer to the lender
+/// Anyone can call this function on behalf of the lender, the funds are always sent back to the lender
+/// This also covers offers refused after the borrower withdrew their collateral, as long as the funds are still there
+pub fn refund_expired_offer(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    global_offer_id: String,
+) -> Result<Response> {
+    let mut offer_info = get_offer(deps.storage, &global_offer_id)?;
+    if matches!(offer_info.state, OfferState::Accepted | OfferState::Cancelled)
+        || offer_info.deposited_funds.is_none()
+        || !offer_info.is_expired(&env.block)
+    {
+        bail!(ContractError::NotWithdrawable {});
+    }
+
+    // The funds deposited for lending are withdrawn
+    let withdraw_message = _withdraw_offer_unsafe(offer_info.lender.clone(), &offer_info)?;
+
+    offer_info.state = OfferState::Expired;
+    offer_info.deposited_funds = None;
+    save_offer(deps.storage, &global_offer_id, offer_info.clone())?;
+
+    Ok(Response::new()
+        .add_message(withdraw_message)
+        .add_attribute("action", "withdraw-funds")
+        .add_attribute("event", "expired-offer")
+        .add_attribute("borrower", offer_info.borrower)
+        .add_attribute("lender", offer_info.lender)
+        .add_attribute("loan_id", offer_info.loan_id.to_string())
+        .add_attribute("global_offer_id", global_offer_id))
+}
+
 /// This creates withdraw messages to withdraw the funds from an offer (to the lender of the borrower depending on the situation
 /// This function does not do any checks on the validity of the procedure
 /// Be careful when using this internal function
 pub fn _withdraw_offer_unsafe(
     recipient: Addr,
     offer_info: &OfferInfo
-) -> Result<BankMsg> {
+) -> Result<CosmosMsg> {
 
     // We get the funds to withdraw
     let funds_to_withdraw = offer_info
         .deposited_funds.clone()
         .ok_or(ContractError::NoFundsToWithdraw {})?;
 
-    Ok(BankMsg::Send {
-        to_address: recipient.to_string(),
-        amount: vec![funds_to_withdraw],
-    })
+    _send_funds(funds_to_withdraw, recipient)
+}
+
+/// Creates the message to send native or cw20 tokens
+pub fn _send_funds(funds: AssetInfo, recipient: Addr) -> Result<CosmosMsg> {
+    match funds {
+        AssetInfo::Coin(coin) => Ok(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![coin],
+        }
+        .into()),
+        AssetInfo::Cw20Coin(Cw20Coin { address, amount }) => into_cosmos_msg(
+            Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            },
+            address,
+            None,
+        ),
+        _ => bail!(ContractError::WrongPrincipleAsset {}),
+    }
 }
 
 /// Refuse an offer to a borrowers collateral
@@ -376,7 +569,8 @@ pub fn refuse_offer(
 pub fn accept_loan(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo,
+    lender: Addr,
+    sent_funds: AssetInfo,
     borrower: String,
     loan_id: u64,
     comment: Option<String>,
@@ -390,11 +584,14 @@ pub fn accept_loan(
     let (global_offer_id, _offer_id) = _make_offer_raw(
         deps.storage,
         env.clone(),
-        info.clone(),
+        lender.clone(),
+        sent_funds,
         borrower_addr,
         loan_id,
         terms.clone(),
         comment,
+        false,
+        None,
     )?;
 
     // Then we make the borrower accept the loan
@@ -402,10 +599,10 @@ pub fn accept_loan(
 
     Ok(res
         .add_attribute("action", "start-loan")
-        .add_attribute("denom-borrowed", terms.principle.denom)
-        .add_attribute("amount_borrowed", terms.principle.amount.to_string())
+        .add_attribute("denom-borrowed", asset_currency(&terms.principle))
+        .add_attribute("amount_borrowed", terms.principle_amount().to_string())
         .add_attribute("borrower", borrower)
-        .add_attribute("lender", info.sender)
+        .add_attribute("lender", lender)
         .add_attribute("loan_id", loan_id.to_string())
         .add_attribute("global_offer_id", global_offer_id))
 }
@@ -424,10 +621,13 @@ fn _accept_offer_raw(
     is_loan_acceptable(&collateral)?;
 
     // We verify the offer is still valid
-    if offer_info.state == OfferState::Published {
+    if offer_info.is_expired(&env.block) {
+        bail!(ContractError::OfferExpired {});
+    } else if offer_info.state == OfferState::Published {
         // We can start the loan now !
         collateral.state = LoanState::Started;
         collateral.start_block = Some(env.block.height);
+        collateral.start_time = Some(env.block.time);
         collateral.active_offer = Some(global_offer_id.clone());
         offer_info.state = OfferState::Accepted;
 
@@ -485,10 +685,10 @@ fn _accept_offer_raw(
         .add_message(fund_messages)
         .add_messages(asset_messages)
         .add_attribute("action", "start-loan")
-        .add_attribute("denom-borrowed", offer_info.terms.principle.denom)
+        .add_attribute("denom-borrowed", asset_currency(&offer_info.terms.principle))
         .add_attribute(
             "amount_borrowed",
-            offer_info.terms.principle.amount.to_string(),
+            offer_info.terms.principle_amount().to_string(),
         )
         .add_attribute("borrower", borrower)
         .add_attribute("lender", offer_info.lender)
@@ -511,49 +711,312 @@ pub fn accept_offer(
     _accept_offer_raw(deps, env, global_offer_id)
 }
 
+/// Accept a collection offer against one of your tokens of the collection
+/// The token is deposited as a new collateral with the offer terms, and the loan starts right away
+pub fn accept_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    global_offer_id: String,
+    token: AssetInfo,
+    comment: Option<String>,
+) -> Result<Response> {
+    let borrower = info.sender;
+    let mut offer_info = get_offer(deps.storage, &global_offer_id)?;
+    let collection = offer_info
+        .collection
+        .clone()
+        .ok_or(ContractError::NotCollectionOffer {})?;
+    if offer_info.state != OfferState::Published {
+        bail!(ContractError::WrongOfferState {
+            state: offer_info.state,
+        });
+    }
+    if offer_info.is_expired(&env.block) {
+        bail!(ContractError::OfferExpired {});
+    }
+
+    // The token has to be part of the collection
+    // Only cw721 tokens are accepted, the lender can't specify how many cw1155 tokens the offer is worth
+    match &token {
+        AssetInfo::Cw721Coin(Cw721Coin { address, .. }) if *address == collection => {}
+        AssetInfo::Cw721Coin(_) => bail!(ContractError::WrongCollection {}),
+        _ => bail!(ContractError::WrongCollectionToken {}),
+    }
+
+    // We create the collateral and link the offer to it
+    let loan_id = _deposit_collaterals_raw(
+        deps.storage,
+        env.clone(),
+        borrower.clone(),
+        vec![token],
+        Some(offer_info.terms.clone()),
+        comment,
+    )?;
+    let mut collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
+    collateral.offer_amount += 1;
+    COLLATERAL_INFO.save(deps.storage, (borrower.clone(), loan_id), &collateral)?;
+
+    offer_info.borrower = borrower;
+    offer_info.loan_id = loan_id;
+    offer_info.offer_id = collateral.offer_amount;
+    save_offer(deps.storage, &global_offer_id, offer_info)?;
+
+    // Then the loan starts with the offer terms
+    let res = _accept_offer_raw(deps, env, global_offer_id)?;
+
+    Ok(res
+        .add_attribute("event", "accept-collection-offer")
+        .add_attribute("collection", collection))
+}
+
+/// Accept a refinance offer made on your active loan
+/// The principle of the new offer pays back what is still owed to the current lender (minus the fee on interests)
+/// What is left goes to the borrower
+/// The collateral stays in the contract and the loan starts again with the new offer terms
+pub fn accept_refinance_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    global_offer_id: String,
+) -> Result<Response> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let borrower = info.sender;
+    let mut new_offer = is_offer_borrower(deps.storage, borrower.clone(), &global_offer_id)?;
+    if !new_offer.refinance {
+        bail!(ContractError::NotRefinanceOffer {});
+    }
+    if new_offer.state != OfferState::Published {
+        bail!(ContractError::WrongOfferState {
+            state: new_offer.state,
+        });
+    }
+
+    let loan_id = new_offer.loan_id;
+    let mut collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
+    can_repay_loan(deps.storage, env.clone(), &collateral)?;
+    let old_offer = get_active_loan(deps.storage, &collateral)?;
+
+    // The new principle has to cover what is still owed to the current lender
+    let total_due = old_offer
+        .terms
+        .total_due_at(elapsed_seconds(&env, &collateral))?;
+    let remaining = total_due - collateral.repaid_amount;
+    let new_principle = new_offer.terms.principle.clone();
+    if !is_same_currency(&new_principle, &old_offer.terms.principle) {
+        bail!(ContractError::FundsDontMatchTerms {});
+    } else if new_offer.terms.principle_amount() < remaining {
+        bail!(ContractError::FundsDontMatchTermsAndPrinciple(
+            remaining,
+            new_offer.terms.principle_amount()
+        ));
+    }
+    let lender_payback = _lender_payback(
+        &contract_info,
+        &old_offer.terms,
+        total_due,
+        collateral.repaid_amount,
+        total_due,
+    );
+    let fee_depositor_payback = remaining - lender_payback;
+    let borrower_payback = new_offer.terms.principle_amount() - remaining;
+
+    // The loan starts again with the new offer
+    collateral.active_offer = Some(global_offer_id.clone());
+    collateral.start_block = Some(env.block.height);
+    collateral.start_time = Some(env.block.time);
+    collateral.repaid_amount = Uint128::zero();
+    COLLATERAL_INFO.save(deps.storage, (borrower.clone(), loan_id), &collateral)?;
+    new_offer.state = OfferState::Accepted;
+    save_offer(deps.storage, &global_offer_id, new_offer.clone())?;
+
+    let mut res = Response::new();
+    if !lender_payback.is_zero() {
+        res = res.add_message(_send_funds(
+            asset_with_amount(&new_principle, lender_payback),
+            old_offer.lender.clone(),
+        )?);
+    }
+    if !borrower_payback.is_zero() {
+        res = res.add_message(_send_funds(
+            asset_with_amount(&new_principle, borrower_payback),
+            borrower.clone(),
+        )?);
+    }
+    if !fee_depositor_payback.is_zero() {
+        res = res.add_message(_fee_deposit_message(
+            &contract_info,
+            &collateral,
+            asset_with_amount(&new_principle, fee_depositor_payback),
+        )?);
+    }
+
+    Ok(res
+        .add_attribute("action", "refinance-loan")
+        .add_attribute("borrower", borrower)
+        .add_attribute("previous_lender", old_offer.lender)
+        .add_attribute("lender", new_offer.lender)
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("global_offer_id", global_offer_id))
+}
+
 /// Repay Borrowed funds and get back your collateral
 /// This function receives principle + interest funds to end the loan and unlock the collateral
+/// What was already paid back using `repay_partial` is deducted
 /// This effectively uts an end to the loan.
 /// Loans can only be repaid before the period ends.
 /// There is not takebacks, no failesafe
 pub fn repay_borrowed_funds(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo,
+    borrower: Addr,
+    sent_funds: AssetInfo,
+    loan_id: u64,
+) -> Result<Response> {
+    _repay(deps, env, borrower, sent_funds, loan_id, false)
+}
+
+/// Pay back part of a loan, for instance an installment
+/// The funds are sent to the lender right away (minus the fee on the interest part)
+/// The collateral is given back when the last part is paid
+pub fn repay_partial(
+    deps: DepsMut,
+    env: Env,
+    borrower: Addr,
+    sent_funds: AssetInfo,
     loan_id: u64,
+) -> Result<Response> {
+    _repay(deps, env, borrower, sent_funds, loan_id, true)
+}
+
+fn _repay(
+    deps: DepsMut,
+    env: Env,
+    borrower: Addr,
+    sent_funds: AssetInfo,
+    loan_id: u64,
+    partial: bool,
 ) -> Result<Response> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
     // We query the loan info
-    let borrower = info.sender;
     let mut collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
     can_repay_loan(deps.storage, env.clone(), &collateral)?;
     let offer_info = get_active_loan(deps.storage, &collateral)?;
 
-    // We verify the sent funds correspond to the principle + interests
-    let interests = offer_info.terms.interest;
-    if info.funds.len() != 1 {
-        bail!(ContractError::MultipleCoins {});
-    } else if offer_info.terms.principle.denom != info.funds[0].denom.clone() {
+    // We verify the sent funds correspond to what is still owed (principle + interests accrued until now)
+    let total_due = offer_info
+        .terms
+        .total_due_at(elapsed_seconds(&env, &collateral))?;
+    let remaining = total_due - collateral.repaid_amount;
+    let sent_amount = asset_amount(&sent_funds);
+    if !is_same_currency(&offer_info.terms.principle, &sent_funds) {
         bail!(ContractError::FundsDontMatchTerms {});
-    } else if offer_info.terms.principle.amount + interests > info.funds[0].amount {
+    } else if !partial && remaining > sent_amount {
         bail!(ContractError::FundsDontMatchTermsAndPrinciple(
-            offer_info.terms.principle.amount + interests,
-            info.funds[0].amount
+            remaining,
+            sent_amount
         ));
+    } else if partial && remaining < sent_amount {
+        bail!(ContractError::RepaymentTooHigh { remaining });
     }
+    let repaid_amount = if partial {
+        collateral.repaid_amount + sent_amount
+    } else {
+        total_due
+    };
+
+    let lender_payback = _lender_payback(
+        &contract_info,
+        &offer_info.terms,
+        total_due,
+        collateral.repaid_amount,
+        repaid_amount,
+    );
+
+    // And the funds to send to the fee_depositor contract
+    let paid_amount = repaid_amount - collateral.repaid_amount;
+    let fee_depositor_payback = paid_amount - lender_payback;
+
+    // The interest accrues until the payment is included in a block, so the borrower may send more than needed
+    // What was sent on top of the amount due is given back
+    let borrower_refund = sent_amount - paid_amount;
 
     // We save the collateral state
-    collateral.state = LoanState::Ended;
+    let loan_ended = repaid_amount == total_due;
+    collateral.repaid_amount = repaid_amount;
+    if loan_ended {
+        collateral.state = LoanState::Ended;
+    }
     COLLATERAL_INFO.save(deps.storage, (borrower.clone(), loan_id), &collateral)?;
 
-    // We prepare the funds to send back to the lender
-    let lender_payback = offer_info.terms.principle.amount
-        + interests * (Decimal::one() - contract_info.fee_rate);
+    let mut res = Response::new();
+    // We get the funds back to the lender
+    if lender_payback.u128() > 0u128{
+        res = res.add_message(_send_funds(
+            asset_with_amount(&sent_funds, lender_payback),
+            offer_info.lender.clone(),
+        )?)
+    }
+
+    // And the collateral back to the borrower once everything is paid back
+    if loan_ended {
+        res = res.add_messages(_withdraw_loan(
+                collateral.clone(),
+                env.contract.address,
+                borrower.clone(),
+            )?);
+    }
 
-    // And the funds to send to the fee_depositor contract
-    let fee_depositor_payback = info.funds[0].amount - lender_payback;
+    // And we pay the fee to the treasury
+    if fee_depositor_payback.u128() > 0u128 {
+        res = res.add_message(_fee_deposit_message(
+            &contract_info,
+            &collateral,
+            asset_with_amount(&sent_funds, fee_depositor_payback),
+        )?);
+    }
+
+    if !borrower_refund.is_zero() {
+        res = res.add_message(_send_funds(
+            asset_with_amount(&sent_funds, borrower_refund),
+            borrower.clone(),
+        )?);
+    }
+
+    Ok(res
+        .add_attribute("action", if loan_ended { "repay-loan" } else { "repay-partial" })
+        .add_attribute("borrower", borrower)
+        .add_attribute("lender", offer_info.lender)
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("repaid_amount", repaid_amount.to_string())
+        .add_attribute("remaining_amount", (total_due - repaid_amount).to_string()))
+}
+
+/// The share of the lender in the funds paid back between `repaid_before` and `repaid_after`
+/// The lender gets the principle and the interests minus the fee, proportionally to what was paid back
+/// `total_due` is the principle and the interests accrued at the time of the payment
+fn _lender_payback(
+    contract_info: &ContractInfo,
+    terms: &LoanTerms,
+    total_due: Uint128,
+    repaid_before: Uint128,
+    repaid_after: Uint128,
+) -> Uint128 {
+    let interest = total_due - terms.principle_amount();
+    let lender_total =
+        terms.principle_amount() + interest * (Decimal::one() - contract_info.fee_rate);
+    lender_total.multiply_ratio(repaid_after, total_due)
+        - lender_total.multiply_ratio(repaid_before, total_due)
+}
 
-    // The fee depositor needs to know which assets where involved in the transaction
+/// Deposits fees to the fee_distributor contract
+/// The fee depositor needs to know which assets where involved in the transaction
+/// cw20 fees are deposited using the fee_distributor Receive hook
+fn _fee_deposit_message(
+    contract_info: &ContractInfo,
+    collateral: &CollateralInfo,
+    fee: AssetInfo,
+) -> Result<CosmosMsg> {
     let collateral_addresses = collateral
         .associated_assets
         .iter()
@@ -564,42 +1027,158 @@ pub fn repay_borrowed_funds(
         })
         .collect::<Result<Vec<String>>>()?;
 
-    let mut res = Response::new();
-    // We get the funds back to the lender
-    if lender_payback.u128() > 0u128{
-        res = res.add_message(BankMsg::Send {
-            to_address: offer_info.lender.to_string(),
-            amount: coins(lender_payback.u128(), info.funds[0].denom.clone()),
-        })
+    let deposit_msg = FeeDistributorMsg::DepositFees {
+        addresses: collateral_addresses,
+        fee_type: FeeType::Funds,
+    };
+    match fee {
+        AssetInfo::Coin(coin) => into_cosmos_msg(
+            deposit_msg,
+            contract_info.fee_distributor.clone(),
+            Some(vec![coin]),
+        ),
+        AssetInfo::Cw20Coin(Cw20Coin { address, amount }) => into_cosmos_msg(
+            Cw20ExecuteMsg::Send {
+                contract: contract_info.fee_distributor.to_string(),
+                amount,
+                msg: to_binary(&deposit_msg)?,
+            },
+            address,
+            None,
+        ),
+        _ => bail!(ContractError::WrongPrincipleAsset {}),
     }
+}
 
-    // And the collateral back to the borrower*
-    res = res.add_messages(_withdraw_loan(
-            collateral.clone(),
-            env.contract.address,
-            borrower.clone(),
-        )?);
+/// Propose to extend a started loan that hasn't defaulted yet
+/// Only the borrower and the active lender can propose an extension
+/// The terms of the loan don't change until the other party accepts it (`accept_extension`)
+#[allow(clippy::too_many_arguments)]
+pub fn propose_extension(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    borrower: String,
+    loan_id: u64,
+    extra_blocks: u64,
+    extra_seconds: Option<u64>,
+    extra_interest: Uint128,
+) -> Result<Response> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+    let collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
+    can_repay_loan(deps.storage, env.clone(), &collateral)?;
+    let offer = get_active_loan(deps.storage, &collateral)?;
+    if info.sender != borrower && info.sender != offer.lender {
+        bail!(ContractError::Unauthorized {});
+    }
+    // The installment deadlines and amounts are fixed when the loan starts
+    if offer.terms.installments.is_some() {
+        bail!(ContractError::ExtensionOnInstallments {});
+    }
+    // The extension has to add time to the duration used by the loan
+    let extra_seconds = extra_seconds.unwrap_or_default();
+    let extra_duration = if offer.terms.duration_in_seconds.is_some() {
+        extra_seconds
+    } else {
+        extra_blocks
+    };
+    if extra_duration == 0 {
+        bail!(ContractError::WrongExtension {});
+    }
 
-    // And we pay the fee to the treasury
-    if fee_depositor_payback.u128() > 0u128 {
-        res = res.add_message(into_cosmos_msg(
-            FeeDistributorMsg::DepositFees {
-                addresses: collateral_addresses,
-                fee_type: FeeType::Funds,
-            },
-            contract_info.fee_distributor,
-            Some(coins(
-                fee_depositor_payback.u128(),
-                info.funds[0].denom.clone(),
-            )),
-        )?);
+    let extension = LoanExtension {
+        global_offer_id: collateral.active_offer.unwrap(),
+        proposer: info.sender.clone(),
+        extra_blocks,
+        extra_seconds,
+        extra_interest,
+        proposal_block: env.block.height,
+        accepted_block: None,
+    };
+    PENDING_EXTENSIONS.save(deps.storage, (borrower.clone(), loan_id), &extension)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose-extension")
+        .add_attribute("borrower", borrower)
+        .add_attribute("proposer", info.sender)
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("extra_blocks", extra_blocks.to_string())
+        .add_attribute("extra_seconds", extra_seconds.to_string())
+        .add_attribute("extra_interest", extra_interest))
+}
+
+/// Accept the extension proposed by the other party of the loan
+/// The duration and interest of the active offer are updated and the extension is saved in the loan history
+/// The terms are repeated by the accepting party, a proposal replaced before acceptance is rejected
+#[allow(clippy::too_many_arguments)]
+pub fn accept_extension(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    borrower: String,
+    loan_id: u64,
+    extra_blocks: u64,
+    extra_seconds: Option<u64>,
+    extra_interest: Uint128,
+) -> Result<Response> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+    let collateral = COLLATERAL_INFO.load(deps.storage, (borrower.clone(), loan_id))?;
+    can_repay_loan(deps.storage, env.clone(), &collateral)?;
+    let mut offer = get_active_loan(deps.storage, &collateral)?;
+
+    // The extension has to be proposed on the current active offer (the loan may have been refinanced since)
+    let mut extension = PENDING_EXTENSIONS
+        .may_load(deps.storage, (borrower.clone(), loan_id))?
+        .filter(|extension| Some(&extension.global_offer_id) == collateral.active_offer.as_ref())
+        .ok_or(ContractError::ExtensionNotFound {})?;
+
+    // Only the other party can accept the extension
+    let counterparty = if extension.proposer == borrower {
+        offer.lender.clone()
+    } else {
+        borrower.clone()
+    };
+    if info.sender != counterparty {
+        bail!(ContractError::Unauthorized {});
+    }
+    if (extension.extra_blocks, extension.extra_seconds, extension.extra_interest)
+        != (extra_blocks, extra_seconds.unwrap_or_default(), extra_interest)
+    {
+        bail!(ContractError::ExtensionMismatch {});
     }
 
-    Ok(res
-        .add_attribute("action", "repay-loan")
+    offer.terms.duration_in_blocks += extension.extra_blocks;
+    offer.terms.duration_in_seconds = offer
+        .terms
+        .duration_in_seconds
+        .map(|duration| duration + extension.extra_seconds);
+    offer.terms.interest = offer.terms.interest.checked_add(extension.extra_interest)?;
+    offer.terms.total_due()?;
+    save_offer(deps.storage, &extension.global_offer_id, offer.clone())?;
+
+    extension.accepted_block = Some(env.block.height);
+    PENDING_EXTENSIONS.remove(deps.storage, (borrower.clone(), loan_id));
+    LOAN_EXTENSIONS.update(deps.storage, (borrower.clone(), loan_id), |history| {
+        let mut history = history.unwrap_or_default();
+        history.push(extension.clone());
+        Ok::<_, ContractError>(history)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept-extension")
         .add_attribute("borrower", borrower)
-        .add_attribute("lender", offer_info.lender)
-        .add_attribute("loan_id", loan_id.to_string()))
+        .add_attribute("lender", offer.lender)
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("global_offer_id", extension.global_offer_id)
+        .add_attribute(
+            "duration_in_blocks",
+            offer.terms.duration_in_blocks.to_string(),
+        )
+        .add_attribute(
+            "duration_in_seconds",
+            offer.terms.duration_in_seconds.unwrap_or_default().to_string(),
+        )
+        .add_attribute("interest", offer.terms.interest))
 }
 
 /// Withdraw the collateral from a defaulted loan
@@ -674,3 +1253,66 @@ pub fn _withdraw_asset(asset: &AssetInfo, sender: Addr, recipient: Addr) -> Resu
         _ => bail!(ContractError::Unreachable {}),
     }
 }
+
+/// The native funds sent along with a message
+/// Only one coin can be sent at once, as loans are made in a single currency
+pub fn native_funds(info: &MessageInfo) -> Result<AssetInfo> {
+    if info.funds.len() != 1 {
+        bail!(ContractError::MultipleCoins {});
+    }
+    Ok(AssetInfo::Coin(info.funds[0].clone()))
+}
+
+/// Make offers, accept loans and repay them with cw20 tokens
+/// This function is used when sending cw20 tokens to the contract directly using a send_msg
+/// The message sent along with the tokens is executed as if the tokens were sent with it
+pub fn receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response> {
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    // The token contract is the sender of this message
+    let sent_funds = AssetInfo::Cw20Coin(Cw20Coin {
+        address: info.sender.to_string(),
+        amount: wrapper.amount,
+    });
+    match from_binary(&wrapper.msg)? {
+        ExecuteMsg::MakeOffer {
+            borrower,
+            loan_id,
+            terms,
+            comment,
+            expiration,
+        } => make_offer(
+            deps, env, sender, sent_funds, borrower, loan_id, terms, comment, expiration,
+        ),
+        ExecuteMsg::MakeRefinanceOffer {
+            borrower,
+            loan_id,
+            terms,
+            comment,
+        } => make_refinance_offer(deps, env, sender, sent_funds, borrower, loan_id, terms, comment),
+        ExecuteMsg::MakeCollectionOffer {
+            collection,
+            terms,
+            expiration,
+            comment,
+        } => make_collection_offer(
+            deps, env, sender, sent_funds, collection, terms, expiration, comment,
+        ),
+        ExecuteMsg::AcceptLoan {
+            borrower,
+            loan_id,
+            comment,
+        } => accept_loan(deps, env, sender, sent_funds, borrower, loan_id, comment),
+        ExecuteMsg::RepayBorrowedFunds { loan_id } => {
+            repay_borrowed_funds(deps, env, sender, sent_funds, loan_id)
+        }
+        ExecuteMsg::RepayPartial { loan_id } => {
+            repay_partial(deps, env, sender, sent_funds, loan_id)
+        }
+        _ => bail!(ContractError::Unauthorized {}),
+    }
+}
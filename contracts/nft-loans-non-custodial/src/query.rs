@@ -2,23 +2,31 @@ use cosmwasm_std::QueryRequest;
 use cosmwasm_std::to_binary;
 use cosmwasm_std::Addr;
 use crate::error::ContractError;
+use crate::state::get_active_loan;
 use crate::state::get_actual_state;
+use crate::state::remaining_amount_due;
 use crate::state::get_offer;
 use crate::state::lender_offers;
 use crate::state::BORROWER_INFO;
 use crate::state::COLLATERAL_INFO;
+use crate::state::LOAN_EXTENSIONS;
+use crate::state::PENDING_EXTENSIONS;
 use cosmwasm_std::StdError;
-use cosmwasm_std::{Deps, Order, StdResult, WasmQuery};
+use cosmwasm_std::{Deps, Env, Order, StdResult, Uint128, WasmQuery};
 use cw721::Cw721QueryMsg;
 use cw721::{OwnerOfResponse};
 use cw_storage_plus::Bound;
 use nft_loans_export::msg::CollateralResponse;
+use nft_loans_export::msg::LoanExtensionsResponse;
 use nft_loans_export::msg::MultipleCollateralsAllResponse;
 use nft_loans_export::msg::MultipleCollateralsResponse;
 use nft_loans_export::msg::MultipleOffersResponse;
 use nft_loans_export::msg::OfferResponse;
 use nft_loans_export::state::BorrowerInfo;
 use nft_loans_export::state::CollateralInfo;
+use nft_loans_export::state::LoanState;
+use nft_loans_export::state::OfferInfo;
+use nft_loans_export::state::OfferState;
 #[cfg(not(feature = "library"))]
 use nft_loans_export::state::ContractInfo;
 
@@ -32,19 +40,31 @@ pub fn query_contract_info(deps: Deps) -> Result<ContractInfo> {
     CONTRACT_INFO.load(deps.storage).map_err(|err| anyhow!(err))
 }
 
-pub fn query_collateral_info(deps: Deps, borrower: String, loan_id: u64) -> Result<CollateralInfo> {
+pub fn query_collateral_info(
+    deps: Deps,
+    env: Env,
+    borrower: String,
+    loan_id: u64,
+) -> Result<CollateralResponse> {
     let borrower = deps.api.addr_validate(&borrower)?;
-    COLLATERAL_INFO
-        .load(deps.storage, (borrower, loan_id))
-        .map_err(|err| anyhow!(err))
+    let collateral = COLLATERAL_INFO
+        .load(deps.storage, (borrower.clone(), loan_id))
+        .map_err(|err| anyhow!(err))?;
+
+    Ok(CollateralResponse {
+        borrower: borrower.to_string(),
+        loan_id,
+        amount_due: query_amount_due(deps, &env, &collateral)?,
+        collateral,
+    })
 }
 
-pub fn query_offer_info(deps: Deps, global_offer_id: String) -> Result<OfferResponse> {
+pub fn query_offer_info(deps: Deps, env: Env, global_offer_id: String) -> Result<OfferResponse> {
     let offer_info = get_offer(deps.storage, &global_offer_id)?;
 
     Ok(OfferResponse {
         global_offer_id,
-        offer_info,
+        offer_info: mark_expired(offer_info, &env),
     })
 }
 
@@ -57,6 +77,7 @@ pub fn query_borrower_info(deps: Deps, borrower: String) -> StdResult<BorrowerIn
 
 pub fn query_collaterals(
     deps: Deps,
+    env: Env,
     borrower: String,
     start_after: Option<u64>,
     limit: Option<u32>,
@@ -70,12 +91,15 @@ pub fn query_collaterals(
         .range(deps.storage, None, start, Order::Descending)
         .map(|result| {
             result
-                .map(|(loan_id, el)| CollateralResponse {
-                    borrower: borrower.to_string(),
-                    loan_id,
-                    collateral: el,
-                })
                 .map_err(|err| anyhow!(err))
+                .and_then(|(loan_id, el)| {
+                    Ok(CollateralResponse {
+                        borrower: borrower.to_string(),
+                        loan_id,
+                        amount_due: query_amount_due(deps, &env, &el)?,
+                        collateral: el,
+                    })
+                })
         })
         .take(limit)
         .collect::<Result<Vec<CollateralResponse>>>()?;
@@ -92,6 +116,7 @@ pub fn query_collaterals(
 
 pub fn query_all_collaterals(
     deps: Deps,
+    env: Env,
     start_after: Option<(String, u64)>,
     limit: Option<u32>,
 ) -> Result<MultipleCollateralsAllResponse> {
@@ -107,12 +132,15 @@ pub fn query_all_collaterals(
         .range(deps.storage, None, start, Order::Descending)
         .map(|result| {
             result
-                .map(|(loan_id, el)| CollateralResponse {
-                    borrower: loan_id.0.to_string(),
-                    loan_id: loan_id.1,
-                    collateral: el,
-                })
                 .map_err(|err| anyhow!(err))
+                .and_then(|(loan_id, el)| {
+                    Ok(CollateralResponse {
+                        borrower: loan_id.0.to_string(),
+                        loan_id: loan_id.1,
+                        amount_due: query_amount_due(deps, &env, &el)?,
+                        collateral: el,
+                    })
+                })
         })
         .take(limit)
         .collect::<Result<Vec<CollateralResponse>>>()?;
@@ -125,12 +153,23 @@ pub fn query_all_collaterals(
     })
 }
 
+/// What is still owed on a started loan, with the interests accrued until the current block
+fn query_amount_due(deps: Deps, env: &Env, collateral: &CollateralInfo) -> Result<Option<Uint128>> {
+    if collateral.state != LoanState::Started {
+        return Ok(None);
+    }
+    let offer = get_active_loan(deps.storage, collateral)?;
+    Ok(Some(remaining_amount_due(env, collateral, &offer.terms)?))
+}
+
 pub fn query_offers(
     deps: Deps,
+    env: Env,
     borrower: String,
     loan_id: u64,
     start_after: Option<String>,
     limit: Option<u32>,
+    include_expired: Option<bool>,
 ) -> Result<MultipleOffersResponse> {
     let borrower = deps.api.addr_validate(&borrower)?;
     let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
@@ -147,7 +186,7 @@ pub fn query_offers(
                     offer_info.state = get_actual_state(&offer_info, deps.storage)?;
                     Ok(
                         OfferResponse {
-                            offer_info,
+                            offer_info: mark_expired(offer_info, &env),
                             global_offer_id: key,
                         }
                     )
@@ -155,6 +194,7 @@ pub fn query_offers(
                 Err(err) => bail!(err)
             }
         })
+        .filter(|offer| is_listed(offer, include_expired))
         .take(limit)
         .collect::<Result<Vec<OfferResponse>>>()?;
 
@@ -166,9 +206,11 @@ pub fn query_offers(
 
 pub fn query_lender_offers(
     deps: Deps,
+    env: Env,
     lender: String,
     start_after: Option<String>,
     limit: Option<u32>,
+    include_expired: Option<bool>,
 ) -> Result<MultipleOffersResponse> {
     let lender = deps.api.addr_validate(&lender)?;
     let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
@@ -181,11 +223,47 @@ pub fn query_lender_offers(
         .range(deps.storage, None, start, Order::Descending)
         .map(|x| {
             x.map(|(key, offer_info)| OfferResponse {
-                offer_info,
+                offer_info: mark_expired(offer_info, &env),
                 global_offer_id: key,
             })
             .map_err(|err| anyhow!(err))
         })
+        .filter(|offer| is_listed(offer, include_expired))
+        .take(limit)
+        .collect::<Result<Vec<OfferResponse>>>()?;
+
+    Ok(MultipleOffersResponse {
+        next_offer: offers.last().map(|last| last.global_offer_id.clone()),
+        offers,
+    })
+}
+
+pub fn query_collection_offers(
+    deps: Deps,
+    env: Env,
+    collection: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    include_expired: Option<bool>,
+) -> Result<MultipleOffersResponse> {
+    let collection = deps.api.addr_validate(&collection)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let offers: Vec<OfferResponse> = lender_offers()
+        .idx
+        .collection
+        .prefix(collection.to_string())
+        .range(deps.storage, None, start, Order::Descending)
+        .map(|x| {
+            let (key, mut offer_info) = x?;
+            offer_info.state = get_actual_state(&offer_info, deps.storage)?;
+            Ok(OfferResponse {
+                offer_info: mark_expired(offer_info, &env),
+                global_offer_id: key,
+            })
+        })
+        .filter(|offer| is_listed(offer, include_expired))
         .take(limit)
         .collect::<Result<Vec<OfferResponse>>>()?;
 
@@ -195,6 +273,38 @@ pub fn query_lender_offers(
     })
 }
 
+/// Published offers that can't be accepted anymore are shown as expired
+fn mark_expired(mut offer_info: OfferInfo, env: &Env) -> OfferInfo {
+    if offer_info.state == OfferState::Published && offer_info.is_expired(&env.block) {
+        offer_info.state = OfferState::Expired;
+    }
+    offer_info
+}
+
+/// Expired offers are only listed when asked for
+fn is_listed(offer: &Result<OfferResponse>, include_expired: Option<bool>) -> bool {
+    match offer {
+        Ok(offer) => {
+            include_expired.unwrap_or(false) || offer.offer_info.state != OfferState::Expired
+        }
+        Err(_) => true,
+    }
+}
+
+pub fn query_loan_extensions(
+    deps: Deps,
+    borrower: String,
+    loan_id: u64,
+) -> Result<LoanExtensionsResponse> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+    Ok(LoanExtensionsResponse {
+        pending: PENDING_EXTENSIONS.may_load(deps.storage, (borrower.clone(), loan_id))?,
+        history: LOAN_EXTENSIONS
+            .may_load(deps.storage, (borrower, loan_id))?
+            .unwrap_or_default(),
+    })
+}
+
 pub fn is_nft_owner(deps: Deps, sender: Addr, nft_address: String, token_id: String) -> Result<()>{
 
     let owner_response: OwnerOfResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
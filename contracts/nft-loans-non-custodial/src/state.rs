@@ -4,13 +4,17 @@ use cw_storage_plus::Index;
 use cw_storage_plus::IndexList;
 use cw_storage_plus::IndexedMap;
 use cw_storage_plus::MultiIndex;
+use cw_storage_plus::PrimaryKey;
 use cw_storage_plus::{Item, Map};
+use std::ops::Deref;
 
-use cosmwasm_std::{Addr, Env, Storage};
+use cosmwasm_std::{Addr, Coin, Env, Storage, Uint128};
+use utils::state::{AssetInfo, Cw20Coin};
 
 use crate::error::ContractError;
 use nft_loans_export::state::{
-    BorrowerInfo, CollateralInfo, ContractInfo, LoanState, OfferInfo, OfferState,
+    BorrowerInfo, CollateralInfo, ContractInfo, LoanExtension, LoanState, LoanTerms, OfferInfo,
+    OfferState, MAX_ANNUAL_RATE,
 };
 
 /// General contract info. Contains also the Contract Config
@@ -24,20 +28,77 @@ pub const COLLATERAL_INFO: Map<(Addr, u64), CollateralInfo> = Map::new("collater
 /// Only used for now to get the last loan index.
 pub const BORROWER_INFO: Map<&Addr, BorrowerInfo> = Map::new("borrower_info");
 
+/// Saves the extension waiting for the approval of the other party, by loan.
+/// A new proposal replaces the pending one
+pub const PENDING_EXTENSIONS: Map<(Addr, u64), LoanExtension> = Map::new("pending_extensions");
+
+/// Saves all the accepted extensions of a loan, in order
+pub const LOAN_EXTENSIONS: Map<(Addr, u64), Vec<LoanExtension>> = Map::new("loan_extensions");
+
+/// A MultiIndex that only indexes the offers matching its filter
+/// It can be queried like the underlying MultiIndex
+pub struct FilteredOfferIndex<'a, IK> {
+    index: MultiIndex<'a, IK, OfferInfo, String>,
+    filter: fn(&OfferInfo) -> bool,
+}
+
+impl<'a, IK> FilteredOfferIndex<'a, IK> {
+    pub fn new(
+        index: MultiIndex<'a, IK, OfferInfo, String>,
+        filter: fn(&OfferInfo) -> bool,
+    ) -> Self {
+        FilteredOfferIndex { index, filter }
+    }
+}
+
+impl<'a, IK> Deref for FilteredOfferIndex<'a, IK> {
+    type Target = MultiIndex<'a, IK, OfferInfo, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.index
+    }
+}
+
+impl<'a, IK: PrimaryKey<'a>> Index<OfferInfo> for FilteredOfferIndex<'a, IK> {
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &OfferInfo) -> StdResult<()> {
+        if (self.filter)(data) {
+            self.index.save(store, pk, data)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &OfferInfo) -> StdResult<()> {
+        if (self.filter)(old_data) {
+            self.index.remove(store, pk, old_data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collection offers are only linked to a borrower and a loan once accepted
+/// Until then their offer id is 0, offers made on a loan are numbered from 1
+fn is_linked_to_loan(offer_info: &OfferInfo) -> bool {
+    offer_info.collection.is_none() || offer_info.offer_id != 0
+}
+
 /// Complicated Offer saving structure.
 /// We created this structure to be able to query lender offers by 3 different indices :
 /// By lender
 /// By borrower <-- This one is not used for now be we keep it for later, if needed to get all offers made by borrower
 /// By loan (borrower + loan_id)
+/// By collection (for collection offers only)
+/// Collection offers that were not accepted yet are not indexed by borrower nor by loan
 pub struct LenderOfferIndexes<'a> {
     pub lender: MultiIndex<'a, Addr, OfferInfo, String>,
-    pub borrower: MultiIndex<'a, Addr, OfferInfo, String>,
-    pub loan: MultiIndex<'a, (Addr, u64), OfferInfo, String>,
+    pub borrower: FilteredOfferIndex<'a, Addr>,
+    pub loan: FilteredOfferIndex<'a, (Addr, u64)>,
+    pub collection: FilteredOfferIndex<'a, String>,
 }
 
 impl<'a> IndexList<OfferInfo> for LenderOfferIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<OfferInfo>> + '_> {
-        let v: Vec<&dyn Index<OfferInfo>> = vec![&self.lender, &self.borrower, &self.loan];
+        let v: Vec<&dyn Index<OfferInfo>> =
+            vec![&self.lender, &self.borrower, &self.loan, &self.collection];
         Box::new(v.into_iter())
     }
 }
@@ -49,15 +110,29 @@ pub fn lender_offers<'a>() -> IndexedMap<'a, &'a str, OfferInfo, LenderOfferInde
             "lender_offers",
             "lender_offers__lenderr",
         ),
-        borrower: MultiIndex::new(
-            |d: &OfferInfo| d.borrower.clone(),
-            "lender_offers",
-            "lender_offers__borrower",
+        borrower: FilteredOfferIndex::new(
+            MultiIndex::new(
+                |d: &OfferInfo| d.borrower.clone(),
+                "lender_offers",
+                "lender_offers__borrower",
+            ),
+            is_linked_to_loan,
         ),
-        loan: MultiIndex::new(
-            |d: &OfferInfo| (d.borrower.clone(), d.loan_id),
-            "lender_offers",
-            "lender_offers__collateral",
+        loan: FilteredOfferIndex::new(
+            MultiIndex::new(
+                |d: &OfferInfo| (d.borrower.clone(), d.loan_id),
+                "lender_offers",
+                "lender_offers__collateral",
+            ),
+            is_linked_to_loan,
+        ),
+        collection: FilteredOfferIndex::new(
+            MultiIndex::new(
+                |d: &OfferInfo| d.collection.clone().unwrap_or_default(),
+                "lender_offers",
+                "lender_offers__collection",
+            ),
+            |d: &OfferInfo| d.collection.is_some(),
         ),
     };
     IndexedMap::new("lender_offers", indexes)
@@ -131,7 +206,17 @@ pub fn is_loan_defaulted(
     let offer = get_active_loan(storage, collateral)?;
     match &collateral.state {
         LoanState::Started => {
-            if collateral.start_block.unwrap() + offer.terms.duration_in_blocks < env.block.height {
+            let start_block = collateral.start_block.unwrap();
+            // The loan also defaults when an installment deadline is missed
+            let missed_installment = collateral.repaid_amount
+                < offer
+                    .terms
+                    .amount_due_after(env.block.height.saturating_sub(start_block))?;
+            let deadline_passed = match offer.terms.duration_in_seconds {
+                Some(duration) => elapsed_seconds(&env, collateral) > duration,
+                None => start_block + offer.terms.duration_in_blocks < env.block.height,
+            };
+            if deadline_passed || missed_installment {
                 Ok(())
             } else {
                 bail!(ContractError::WrongLoanState {
@@ -146,6 +231,99 @@ pub fn is_loan_defaulted(
     }
 }
 
+/// The seconds passed since the start of a loan
+pub fn elapsed_seconds(env: &Env, collateral: &CollateralInfo) -> u64 {
+    collateral
+        .start_time
+        .map(|start_time| env.block.time.seconds().saturating_sub(start_time.seconds()))
+        .unwrap_or_default()
+}
+
+/// The principle and interest accrued at the current block, minus what was already paid back
+pub fn remaining_amount_due(
+    env: &Env,
+    collateral: &CollateralInfo,
+    terms: &LoanTerms,
+) -> Result<Uint128> {
+    Ok(terms
+        .total_due_at(elapsed_seconds(env, collateral))?
+        .saturating_sub(collateral.repaid_amount))
+}
+
+/// Loans are made in native or cw20 tokens
+/// Installments have to be due before the end of the loan
+/// Installments are only supported with durations in blocks
+/// An annual rate needs a duration in seconds to accrue
+pub fn assert_valid_terms(terms: &LoanTerms) -> Result<()> {
+    match &terms.principle {
+        AssetInfo::Coin(_) | AssetInfo::Cw20Coin(_) => {}
+        _ => bail!(ContractError::WrongPrincipleAsset {}),
+    }
+    if let Some(annual_rate) = terms.annual_rate {
+        if terms.duration_in_seconds.is_none() || annual_rate > MAX_ANNUAL_RATE {
+            bail!(ContractError::WrongInterestRate {});
+        }
+    }
+    // The amount due over the whole loan has to fit in the token amounts
+    terms.total_due()?;
+    if let Some(schedule) = &terms.installments {
+        if terms.duration_in_seconds.is_some()
+            || schedule.number == 0
+            || schedule.interval_in_blocks == 0
+            || schedule
+                .number
+                .checked_mul(schedule.interval_in_blocks)
+                .filter(|last_deadline| *last_deadline <= terms.duration_in_blocks)
+                .is_none()
+        {
+            bail!(ContractError::WrongInstallmentSchedule {});
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if both assets are fungible tokens of the same currency (same denom or same cw20 address)
+pub fn is_same_currency(asset: &AssetInfo, other: &AssetInfo) -> bool {
+    match (asset, other) {
+        (AssetInfo::Coin(x), AssetInfo::Coin(y)) => x.denom == y.denom,
+        (AssetInfo::Cw20Coin(x), AssetInfo::Cw20Coin(y)) => x.address == y.address,
+        _ => false,
+    }
+}
+
+pub fn asset_amount(asset: &AssetInfo) -> Uint128 {
+    match asset {
+        AssetInfo::Coin(coin) => coin.amount,
+        AssetInfo::Cw20Coin(coin) => coin.amount,
+        _ => Uint128::zero(),
+    }
+}
+
+/// The same fungible currency, with another amount
+pub fn asset_with_amount(asset: &AssetInfo, amount: Uint128) -> AssetInfo {
+    match asset {
+        AssetInfo::Coin(coin) => AssetInfo::Coin(Coin {
+            denom: coin.denom.clone(),
+            amount,
+        }),
+        AssetInfo::Cw20Coin(coin) => AssetInfo::Cw20Coin(Cw20Coin {
+            address: coin.address.clone(),
+            amount,
+        }),
+        _ => asset.clone(),
+    }
+}
+
+/// The denom of native tokens or the address of cw20 tokens
+pub fn asset_currency(asset: &AssetInfo) -> String {
+    match asset {
+        AssetInfo::Coin(coin) => coin.denom.clone(),
+        AssetInfo::Cw20Coin(coin) => coin.address.clone(),
+        AssetInfo::Cw721Coin(nft) => nft.address.clone(),
+        AssetInfo::Cw1155Coin(nft) => nft.address.clone(),
+    }
+}
+
 pub fn get_active_loan(storage: &dyn Storage, collateral: &CollateralInfo) -> Result<OfferInfo> {
     let global_offer_id = collateral
         .active_offer
@@ -205,6 +383,10 @@ pub fn get_offer(storage: &dyn Storage, global_offer_id: &str) -> Result<OfferIn
 }
 
 pub fn get_actual_state(offer_info: &OfferInfo, storage: &dyn Storage) -> Result<OfferState>{
+    // Published collection offers are not linked to any collateral yet
+    if offer_info.collection.is_some() && offer_info.state == OfferState::Published {
+        return Ok(OfferState::Published);
+    }
     let collateral_info =
         COLLATERAL_INFO.load(storage, (offer_info.borrower.clone(), offer_info.loan_id))?;
 
@@ -212,6 +394,14 @@ pub fn get_actual_state(offer_info: &OfferInfo, storage: &dyn Storage) -> Result
     // A refused offer isn't marked as such but depends on the overlying collateral info state
     Ok(
         match &offer_info.state {
+        // Refinance offers are valid as long as the loan is running
+        OfferState::Published if offer_info.refinance => {
+            if collateral_info.state != LoanState::Started {
+                OfferState::Refused
+            } else {
+                OfferState::Published
+            }
+        }
         OfferState::Published => {
             if collateral_info.state != LoanState::Published {
                 OfferState::Refused
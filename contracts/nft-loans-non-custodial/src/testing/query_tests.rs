@@ -51,6 +51,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 6,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -63,6 +64,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 5,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -75,6 +77,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 4,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -87,6 +90,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 3,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -99,6 +103,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 2,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -111,6 +116,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 1,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -123,6 +129,7 @@ fn test_query_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 0,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -176,6 +183,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 6,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -188,6 +196,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 5,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -223,6 +232,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 4,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft1".to_string(),
@@ -235,6 +245,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 3,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -247,6 +258,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 2,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -282,6 +294,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 1,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
@@ -294,6 +307,7 @@ fn test_query_and_limit_collaterals() {
                 CollateralResponse {
                     borrower: borrower.to_string(),
                     loan_id: 0,
+                    amount_due: None,
                     collateral: CollateralInfo {
                         associated_assets: vec![AssetInfo::Cw721Coin(Cw721Coin {
                             address: "nft".to_string(),
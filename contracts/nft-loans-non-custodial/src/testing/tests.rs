@@ -2,31 +2,43 @@ use std::str::FromStr;
 use crate::contract::execute;
 use crate::contract::instantiate;
 use crate::error::ContractError;
+use crate::query::query_collateral_info;
+use crate::query::query_collaterals;
+use crate::query::query_collection_offers;
+use crate::query::query_lender_offers;
+use crate::query::query_loan_extensions;
 use crate::state::lender_offers;
 use crate::state::COLLATERAL_INFO;
 use crate::state::CONTRACT_INFO;
 use anyhow::Result;
 use cosmwasm_std::{
-    coin, coins,
+    coin, coins, from_slice, to_binary,
     testing::{mock_dependencies, mock_env, mock_info},
-    Api, BankMsg, Coin, DepsMut, Env, Response, SubMsg, Uint128, Decimal
+    Addr, Api, BankMsg, Coin, Deps, DepsMut, Env, Order, Response, StdError, StdResult, SubMsg,
+    Timestamp, Uint128, Decimal
 };
 use cw1155::Cw1155ExecuteMsg;
+use cw20::Cw20ExecuteMsg;
+use cw721::Cw721ExecuteMsg;
 
 use utils::state::OwnerStruct;
 
 use fee_contract_export::state::FeeType;
 use fee_distributor_export::msg::ExecuteMsg as FeeDistributorMsg;
+use nft_loans_export::msg::Cw20ReceiveMsg;
 use nft_loans_export::msg::ExecuteMsg;
 use nft_loans_export::msg::InstantiateMsg;
 use nft_loans_export::state::CollateralInfo;
 use nft_loans_export::state::ContractInfo;
+use nft_loans_export::state::InstallmentSchedule;
+use nft_loans_export::state::LoanExtension;
 use nft_loans_export::state::LoanState;
 use nft_loans_export::state::LoanTerms;
 use nft_loans_export::state::OfferState;
 use utils::msg::into_cosmos_msg;
 use utils::state::AssetInfo;
 use utils::state::Cw1155Coin;
+use utils::state::Cw20Coin;
 use utils::state::Cw721Coin;
 use crate::testing::mock_querier::{mock_dependencies as mock_querier_dependencies};
 
@@ -249,6 +261,7 @@ fn make_offer_helper(
             loan_id,
             terms,
             comment: None,
+            expiration: None,
         },
     )
 }
@@ -543,9 +556,12 @@ fn test_accept_loan() {
     .unwrap();
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
     set_terms_helper(deps.as_mut(), "creator", 0, terms.clone()).unwrap();
 
@@ -593,7 +609,9 @@ fn test_accept_loan() {
             start_block: Some(12345),
             offer_amount: 1,
             comment: None,
-            list_date: mock_env().block.time
+            list_date: mock_env().block.time,
+            repaid_amount: Uint128::zero(),
+            start_time: Some(mock_env().block.time),
         }
     );
 }
@@ -604,9 +622,12 @@ fn test_accept_loan_and_modify() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
     add_collateral_helper(
         deps.as_mut(),
@@ -643,9 +664,12 @@ fn test_repay_loan_early() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
     add_collateral_helper(
         deps.as_mut(),
@@ -684,9 +708,12 @@ fn test_make_offer() {
     .unwrap();
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     let err = make_offer_helper(
@@ -739,9 +766,12 @@ fn test_cancel_offer() {
     .unwrap();
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     make_offer_helper(
@@ -786,9 +816,12 @@ fn test_refuse_offer() {
     .unwrap();
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     make_offer_helper(
@@ -815,9 +848,12 @@ fn test_cancel_accepted() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     add_collateral_helper(
@@ -842,9 +878,12 @@ fn test_withdraw_refused() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     add_collateral_helper(
@@ -907,9 +946,12 @@ fn test_accept_cancelled_offer() {
     .unwrap();
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     make_offer_helper(
@@ -939,9 +981,12 @@ fn test_normal_flow() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(50),
         duration_in_blocks: 1,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     add_collateral_helper(
@@ -1044,9 +1089,12 @@ fn test_defaulted_flow() {
     init_helper(deps.as_mut());
 
     let terms = LoanTerms {
-        principle: coin(456, "luna"),
+        principle: AssetInfo::Coin(coin(456, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 0,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
 
     add_collateral_helper(
@@ -1099,9 +1147,12 @@ fn test_steal_funds() {
     init_helper(deps.as_mut());
     // malicious terms, interest set to 0 to prevent fee distribution
     let terms = LoanTerms {
-        principle: coin(1000, "luna"),
+        principle: AssetInfo::Coin(coin(1000, "luna")),
         interest: Uint128::new(0),
         duration_in_blocks: 1,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
     };
     // attacker deposit nft collateral
     add_collateral_helper(
@@ -1134,3 +1185,1119 @@ fn test_steal_funds() {
     // The attacker can't refuse an offer that was already accepted or withdrawn, etc.
     assert_error(err, ContractError::NotRefusable {  });
 }
+
+fn repay_partial_helper(
+    deps: DepsMut,
+    borrower: &str,
+    loan_id: u64,
+    funds: Vec<Coin>,
+    env: Env,
+) -> Result<Response> {
+    let info = mock_info(borrower, &funds);
+
+    execute(deps, env, info, ExecuteMsg::RepayPartial { loan_id })
+}
+
+#[test]
+fn test_installments() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+
+    let mut terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: Some(InstallmentSchedule {
+            number: 3,
+            interval_in_blocks: 50,
+        }),
+    };
+    // All the installments have to be due before the end of the loan
+    let err = add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(terms.clone()),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongInstallmentSchedule {});
+
+    terms.installments = Some(InstallmentSchedule {
+        number: 2,
+        interval_in_blocks: 50,
+    });
+    for token_id in ["58", "59"] {
+        add_collateral_helper(
+            deps.as_mut(),
+            "creator",
+            "nft",
+            token_id,
+            Some(Uint128::new(45u128)),
+            Some(terms.clone()),
+        )
+        .unwrap();
+    }
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 0, coins(1000, "luna")).unwrap();
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 1, coins(1000, "luna")).unwrap();
+
+    // The first installment is paid, the lender gets their share right away
+    let mut env = mock_env();
+    env.block.height += 10;
+    let res =
+        repay_partial_helper(deps.as_mut(), "creator", 0, coins(600, "luna"), env.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(597, "luna"),
+            }),
+            SubMsg::new(
+                into_cosmos_msg(
+                    FeeDistributorMsg::DepositFees {
+                        addresses: vec!["nft".to_string()],
+                        fee_type: FeeType::Funds
+                    },
+                    "fee_distributor",
+                    Some(coins(3, "luna"))
+                )
+                .unwrap()
+            )
+        ]
+    );
+    let collateral = COLLATERAL_INFO
+        .load(&deps.storage, (deps.api.addr_validate("creator").unwrap(), 0))
+        .unwrap();
+    assert_eq!(collateral.repaid_amount, Uint128::new(600));
+    assert_eq!(collateral.state, LoanState::Started);
+
+    let err = repay_partial_helper(deps.as_mut(), "creator", 0, coins(600, "luna"), env.clone())
+        .unwrap_err();
+    assert_error(
+        err,
+        ContractError::RepaymentTooHigh {
+            remaining: Uint128::new(500),
+        },
+    );
+
+    // The installment schedule can't be extended
+    let err = propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 50, 20).unwrap_err();
+    assert_error(err, ContractError::ExtensionOnInstallments {});
+    let err = accept_extension_helper(deps.as_mut(), "anyone", "creator", 0, 50, 20).unwrap_err();
+    assert_error(err, ContractError::ExtensionNotFound {});
+
+    // Once the first deadline is passed, only the loan with a missed installment defaults
+    env.block.height += 41;
+    let err = withdraw_defaulted_loan_helper(deps.as_mut(), "anyone", "creator", 0, env.clone())
+        .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongLoanState {
+            state: LoanState::Started,
+        },
+    );
+    withdraw_defaulted_loan_helper(deps.as_mut(), "anyone", "creator", 1, env.clone()).unwrap();
+
+    // The rest is paid back at once and the collateral is returned
+    let res =
+        repay_borrowed_funds_helper(deps.as_mut(), "creator", 0, coins(500, "luna"), env.clone())
+            .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(498, "luna"),
+            }),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw1155ExecuteMsg::SendFrom {
+                        from: env.contract.address.to_string(),
+                        to: "creator".to_string(),
+                        token_id: "58".to_string(),
+                        value: Uint128::new(45u128),
+                        msg: None,
+                    },
+                    "nft",
+                    None
+                )
+                .unwrap()
+            ),
+            SubMsg::new(
+                into_cosmos_msg(
+                    FeeDistributorMsg::DepositFees {
+                        addresses: vec!["nft".to_string()],
+                        fee_type: FeeType::Funds
+                    },
+                    "fee_distributor",
+                    Some(coins(2, "luna"))
+                )
+                .unwrap()
+            )
+        ]
+    );
+    let collateral = COLLATERAL_INFO
+        .load(&deps.storage, (deps.api.addr_validate("creator").unwrap(), 0))
+        .unwrap();
+    assert_eq!(collateral.repaid_amount, Uint128::new(1100));
+    assert_eq!(collateral.state, LoanState::Ended);
+}
+
+fn make_refinance_offer_helper(
+    deps: DepsMut,
+    lender: &str,
+    borrower: &str,
+    loan_id: u64,
+    terms: LoanTerms,
+    coins: Vec<Coin>,
+) -> Result<Response> {
+    let info = mock_info(lender, &coins);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::MakeRefinanceOffer {
+            borrower: borrower.to_string(),
+            loan_id,
+            terms,
+            comment: None,
+        },
+    )
+}
+
+fn accept_refinance_offer_helper(
+    deps: DepsMut,
+    borrower: &str,
+    global_offer_id: &str,
+) -> Result<Response> {
+    let info = mock_info(borrower, &[]);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::AcceptRefinanceOffer {
+            global_offer_id: global_offer_id.to_string(),
+        },
+    )
+}
+
+#[test]
+fn test_refinance() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+
+    let terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(terms),
+    )
+    .unwrap();
+
+    let refinance_terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1200, "luna")),
+        interest: Uint128::new(80),
+        duration_in_blocks: 200,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    // Refinance offers can only be made on started loans
+    let err = make_refinance_offer_helper(
+        deps.as_mut(),
+        "new_lender",
+        "creator",
+        0,
+        refinance_terms.clone(),
+        coins(1200, "luna"),
+    )
+    .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongLoanState {
+            state: LoanState::Published,
+        },
+    );
+
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 0, coins(1000, "luna")).unwrap();
+    make_refinance_offer_helper(
+        deps.as_mut(),
+        "new_lender",
+        "creator",
+        0,
+        refinance_terms,
+        coins(1200, "luna"),
+    )
+    .unwrap();
+
+    // Regular offers can't be accepted through the refinance path
+    let err = accept_refinance_offer_helper(deps.as_mut(), "creator", "1").unwrap_err();
+    assert_error(err, ContractError::NotRefinanceOffer {});
+    accept_refinance_offer_helper(deps.as_mut(), "bad_person", "2").unwrap_err();
+
+    // The previous lender is paid back, the surplus goes to the borrower
+    let res = accept_refinance_offer_helper(deps.as_mut(), "creator", "2").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(1095, "luna"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(100, "luna"),
+            }),
+            SubMsg::new(
+                into_cosmos_msg(
+                    FeeDistributorMsg::DepositFees {
+                        addresses: vec!["nft".to_string()],
+                        fee_type: FeeType::Funds
+                    },
+                    "fee_distributor",
+                    Some(coins(5, "luna"))
+                )
+                .unwrap()
+            )
+        ]
+    );
+    let collateral = COLLATERAL_INFO
+        .load(&deps.storage, (deps.api.addr_validate("creator").unwrap(), 0))
+        .unwrap();
+    assert_eq!(collateral.active_offer, Some("2".to_string()));
+    assert_eq!(collateral.state, LoanState::Started);
+
+    // The loan is now owed to the new lender
+    let res =
+        repay_borrowed_funds_helper(deps.as_mut(), "creator", 0, coins(1280, "luna"), mock_env())
+            .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "new_lender".to_string(),
+            amount: coins(1276, "luna"),
+        })
+    );
+}
+
+fn propose_extension_helper(
+    deps: DepsMut,
+    sender: &str,
+    borrower: &str,
+    loan_id: u64,
+    extra_blocks: u64,
+    extra_interest: u128,
+) -> Result<Response> {
+    let info = mock_info(sender, &[]);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::ProposeExtension {
+            borrower: borrower.to_string(),
+            loan_id,
+            extra_blocks,
+            extra_seconds: None,
+            extra_interest: Uint128::new(extra_interest),
+        },
+    )
+}
+
+fn accept_extension_helper(
+    deps: DepsMut,
+    sender: &str,
+    borrower: &str,
+    loan_id: u64,
+    extra_blocks: u64,
+    extra_interest: u128,
+) -> Result<Response> {
+    let info = mock_info(sender, &[]);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::AcceptExtension {
+            borrower: borrower.to_string(),
+            loan_id,
+            extra_blocks,
+            extra_seconds: None,
+            extra_interest: Uint128::new(extra_interest),
+        },
+    )
+}
+
+#[test]
+fn test_loan_extension() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+
+    let terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(terms),
+    )
+    .unwrap();
+
+    // Extensions can only be proposed on started loans
+    let err = propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 50, 20).unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongLoanState {
+            state: LoanState::Published,
+        },
+    );
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 0, coins(1000, "luna")).unwrap();
+
+    propose_extension_helper(deps.as_mut(), "bad_person", "creator", 0, 50, 20).unwrap_err();
+    let err = propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 0, 20).unwrap_err();
+    assert_error(err, ContractError::WrongExtension {});
+    let err = accept_extension_helper(deps.as_mut(), "anyone", "creator", 0, 50, 20).unwrap_err();
+    assert_error(err, ContractError::ExtensionNotFound {});
+
+    // Only the other party can accept the extension
+    propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 50, 20).unwrap();
+    let err = accept_extension_helper(deps.as_mut(), "creator", "creator", 0, 50, 20).unwrap_err();
+    assert_error(err, ContractError::Unauthorized {});
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.terms.duration_in_blocks, 100);
+
+    // A proposal replaced before being accepted can't be accepted with the old terms
+    propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 1, 20).unwrap();
+    let err = accept_extension_helper(deps.as_mut(), "anyone", "creator", 0, 50, 20).unwrap_err();
+    assert_error(err, ContractError::ExtensionMismatch {});
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.terms.duration_in_blocks, 100);
+
+    propose_extension_helper(deps.as_mut(), "creator", "creator", 0, 50, 20).unwrap();
+    accept_extension_helper(deps.as_mut(), "anyone", "creator", 0, 50, 20).unwrap();
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.terms.duration_in_blocks, 150);
+    assert_eq!(offer.terms.interest, Uint128::new(120));
+
+    let extensions = query_loan_extensions(deps.as_ref(), "creator".to_string(), 0).unwrap();
+    assert_eq!(extensions.pending, None);
+    assert_eq!(
+        extensions.history,
+        vec![LoanExtension {
+            global_offer_id: "1".to_string(),
+            proposer: Addr::unchecked("creator"),
+            extra_blocks: 50,
+            extra_seconds: 0,
+            extra_interest: Uint128::new(20),
+            proposal_block: 12345,
+            accepted_block: Some(12345),
+        }]
+    );
+
+    // The loan doesn't default at the original deadline anymore
+    let mut env = mock_env();
+    env.block.height += 120;
+    let err = withdraw_defaulted_loan_helper(deps.as_mut(), "anyone", "creator", 0, env.clone())
+        .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongLoanState {
+            state: LoanState::Started,
+        },
+    );
+    let res = repay_borrowed_funds_helper(deps.as_mut(), "creator", 0, coins(1120, "luna"), env)
+        .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "anyone".to_string(),
+            amount: coins(1114, "luna"),
+        })
+    );
+}
+
+fn make_collection_offer_helper(
+    deps: DepsMut,
+    lender: &str,
+    collection: &str,
+    terms: LoanTerms,
+    expiration: Timestamp,
+    coins: Vec<Coin>,
+) -> Result<Response> {
+    let info = mock_info(lender, &coins);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::MakeCollectionOffer {
+            collection: collection.to_string(),
+            terms,
+            expiration,
+            comment: None,
+        },
+    )
+}
+
+fn accept_collection_offer_helper(
+    deps: DepsMut,
+    borrower: &str,
+    global_offer_id: &str,
+    collection: &str,
+    token_id: &str,
+    env: Env,
+) -> Result<Response> {
+    let info = mock_info(borrower, &[]);
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::AcceptCollectionOffer {
+            global_offer_id: global_offer_id.to_string(),
+            token: AssetInfo::Cw721Coin(Cw721Coin {
+                address: collection.to_string(),
+                token_id: token_id.to_string(),
+            }),
+            comment: None,
+        },
+    )
+}
+
+#[test]
+fn test_collection_offer() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier.with_owner_of(&[
+        (&"nft - 58".to_string(), &"creator".to_string()),
+        (&"nft - 59".to_string(), &"creator".to_string()),
+        (&"nft - 60".to_string(), &"other".to_string()),
+    ]);
+    init_helper(deps.as_mut());
+
+    let terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    let expiration = mock_env().block.time.plus_seconds(100);
+    let err = make_collection_offer_helper(
+        deps.as_mut(),
+        "anyone",
+        "nft",
+        terms.clone(),
+        mock_env().block.time,
+        coins(1000, "luna"),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::OfferExpired {});
+    let err = make_collection_offer_helper(
+        deps.as_mut(),
+        "anyone",
+        "nft",
+        terms.clone(),
+        expiration,
+        coins(900, "luna"),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::FundsDontMatchTerms {});
+    for _ in 0..2 {
+        make_collection_offer_helper(
+            deps.as_mut(),
+            "anyone",
+            "nft",
+            terms.clone(),
+            expiration,
+            coins(1000, "luna"),
+        )
+        .unwrap();
+    }
+    let offers =
+        query_collection_offers(deps.as_ref(), mock_env(), "nft".to_string(), None, None, None)
+            .unwrap();
+    assert_eq!(offers.offers.len(), 2);
+    assert_eq!(offers.offers[0].offer_info.state, OfferState::Published);
+
+    // Only collection offers are indexed by collection
+    // They are not indexed by borrower nor by loan until they are accepted
+    add_collateral_helper(deps.as_mut(), "other", "nft", "60", None, None).unwrap();
+    make_offer_helper(deps.as_mut(), "anyone", "other", 0, terms.clone(), coins(1000, "luna"))
+        .unwrap();
+    let contract_address = mock_env().contract.address;
+    let indexed_offers = |deps: Deps, index: &str, borrower: &Addr| -> Vec<String> {
+        let offers = lender_offers();
+        match index {
+            "borrower" => offers.idx.borrower.prefix(borrower.clone()),
+            "loan" => offers.idx.loan.prefix((borrower.clone(), 0)),
+            _ => offers.idx.collection.prefix(String::new()),
+        }
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()
+        .unwrap()
+    };
+    assert_eq!(indexed_offers(deps.as_ref(), "collection", &contract_address), Vec::<String>::new());
+    assert_eq!(indexed_offers(deps.as_ref(), "borrower", &contract_address), Vec::<String>::new());
+    assert_eq!(indexed_offers(deps.as_ref(), "loan", &contract_address), Vec::<String>::new());
+
+    // Only tokens from the collection can be used
+    let err =
+        accept_collection_offer_helper(deps.as_mut(), "creator", "1", "other_nft", "58", mock_env())
+            .unwrap_err();
+    assert_error(err, ContractError::WrongCollection {});
+
+    // A cw1155 token could be deposited with any value, down to a single unit
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::AcceptCollectionOffer {
+            global_offer_id: "1".to_string(),
+            token: AssetInfo::Cw1155Coin(Cw1155Coin {
+                address: "nft".to_string(),
+                token_id: "58".to_string(),
+                value: Uint128::new(1u128),
+            }),
+            comment: None,
+        },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongCollectionToken {});
+
+    // The collateral is created and the loan starts right away
+    let res =
+        accept_collection_offer_helper(deps.as_mut(), "creator", "1", "nft", "58", mock_env())
+            .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(1000, "luna"),
+            }),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw721ExecuteMsg::TransferNft {
+                        recipient: mock_env().contract.address.to_string(),
+                        token_id: "58".to_string(),
+                    },
+                    "nft",
+                    None
+                )
+                .unwrap()
+            ),
+        ]
+    );
+    let collateral = COLLATERAL_INFO
+        .load(&deps.storage, (deps.api.addr_validate("creator").unwrap(), 0))
+        .unwrap();
+    assert_eq!(collateral.state, LoanState::Started);
+    assert_eq!(collateral.active_offer, Some("1".to_string()));
+    assert_eq!(collateral.terms, Some(terms));
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.borrower, Addr::unchecked("creator"));
+    assert_eq!(offer.state, OfferState::Accepted);
+    let creator = Addr::unchecked("creator");
+    assert_eq!(indexed_offers(deps.as_ref(), "borrower", &creator), vec!["1".to_string()]);
+    assert_eq!(indexed_offers(deps.as_ref(), "loan", &creator), vec!["1".to_string()]);
+
+    let err =
+        accept_collection_offer_helper(deps.as_mut(), "creator", "1", "nft", "59", mock_env())
+            .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongOfferState {
+            state: OfferState::Accepted,
+        },
+    );
+
+    // Once expired, the offer can't be accepted anymore but the lender can get their funds back
+    let mut env = mock_env();
+    env.block.time = expiration;
+    let err = accept_collection_offer_helper(deps.as_mut(), "creator", "2", "nft", "59", env)
+        .unwrap_err();
+    assert_error(err, ContractError::OfferExpired {});
+    let res = cancel_offer_helper(deps.as_mut(), "anyone", "2").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "anyone".to_string(),
+            amount: coins(1000, "luna"),
+        })]
+    );
+    assert_eq!(indexed_offers(deps.as_ref(), "borrower", &contract_address), Vec::<String>::new());
+}
+
+fn refund_expired_offer_helper(
+    deps: DepsMut,
+    sender: &str,
+    global_offer_id: &str,
+    env: Env,
+) -> Result<Response> {
+    let info = mock_info(sender, &[]);
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::RefundExpiredOffer {
+            global_offer_id: global_offer_id.to_string(),
+        },
+    )
+}
+
+#[test]
+fn test_offer_expiry() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+    add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        None,
+    )
+    .unwrap();
+
+    let terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    let expiration = mock_env().block.time.plus_seconds(100);
+    let make_offer_msg = |expiration| ExecuteMsg::MakeOffer {
+        borrower: "creator".to_string(),
+        loan_id: 0,
+        terms: terms.clone(),
+        comment: None,
+        expiration: Some(expiration),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &coins(1000, "luna")),
+        make_offer_msg(mock_env().block.time),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::OfferExpired {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &coins(1000, "luna")),
+        make_offer_msg(expiration),
+    )
+    .unwrap();
+
+    // The offer can't be refunded before it expires
+    let err = refund_expired_offer_helper(deps.as_mut(), "bad_person", "1", mock_env()).unwrap_err();
+    assert_error(err, ContractError::NotWithdrawable {});
+
+    // Expired offers can't be accepted anymore
+    let mut env = mock_env();
+    env.block.time = expiration;
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::AcceptOffer {
+            global_offer_id: "1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::OfferExpired {});
+
+    // Expired offers are marked and filtered out of the queries
+    let offers =
+        query_lender_offers(deps.as_ref(), env.clone(), "anyone".to_string(), None, None, None)
+            .unwrap();
+    assert_eq!(offers.offers, vec![]);
+    let offers = query_lender_offers(
+        deps.as_ref(),
+        env.clone(),
+        "anyone".to_string(),
+        None,
+        None,
+        Some(true),
+    )
+    .unwrap();
+    assert_eq!(offers.offers[0].offer_info.state, OfferState::Expired);
+
+    // Anyone can refund the lender
+    let res = refund_expired_offer_helper(deps.as_mut(), "bad_person", "1", env.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "anyone".to_string(),
+            amount: coins(1000, "luna"),
+        })]
+    );
+    let err = refund_expired_offer_helper(deps.as_mut(), "anyone", "1", env).unwrap_err();
+    assert_error(err, ContractError::NotWithdrawable {});
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.state, OfferState::Expired);
+    assert_eq!(offer.deposited_funds, None);
+
+    // Offers refused because the collateral was withdrawn can also be refunded once expired
+    add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "59",
+        Some(Uint128::new(45u128)),
+        None,
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &coins(1000, "luna")),
+        ExecuteMsg::MakeOffer {
+            borrower: "creator".to_string(),
+            loan_id: 1,
+            terms: terms.clone(),
+            comment: None,
+            expiration: Some(expiration),
+        },
+    )
+    .unwrap();
+    withdraw_collateral_helper(deps.as_mut(), "creator", 1).unwrap();
+    let err = refund_expired_offer_helper(deps.as_mut(), "bad_person", "2", mock_env()).unwrap_err();
+    assert_error(err, ContractError::NotWithdrawable {});
+
+    let mut env = mock_env();
+    env.block.time = expiration;
+    let res = refund_expired_offer_helper(deps.as_mut(), "bad_person", "2", env.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "anyone".to_string(),
+            amount: coins(1000, "luna"),
+        })]
+    );
+    let err = refund_expired_offer_helper(deps.as_mut(), "anyone", "2", env).unwrap_err();
+    assert_error(err, ContractError::NotWithdrawable {});
+    let err = withdraw_refused_offer_helper(deps.as_mut(), "anyone", "2").unwrap_err();
+    assert_error(err, ContractError::NotWithdrawable {});
+}
+
+fn cw20_receive_helper(
+    deps: DepsMut,
+    token: &str,
+    sender: &str,
+    amount: u128,
+    msg: ExecuteMsg,
+) -> Result<Response> {
+    let info = mock_info(token, &[]);
+    let env = mock_env();
+
+    execute(
+        deps,
+        env,
+        info,
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&msg).unwrap(),
+        }),
+    )
+}
+
+#[test]
+fn test_cw20_principle() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+
+    let terms = LoanTerms {
+        principle: AssetInfo::Cw20Coin(Cw20Coin {
+            address: "usdc".to_string(),
+            amount: Uint128::new(1000),
+        }),
+        interest: Uint128::new(100),
+        duration_in_blocks: 100,
+        duration_in_seconds: None,
+        annual_rate: None,
+        installments: None,
+    };
+    add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(terms),
+    )
+    .unwrap();
+    let accept_loan_msg = ExecuteMsg::AcceptLoan {
+        borrower: "creator".to_string(),
+        loan_id: 0,
+        comment: None,
+    };
+
+    // The funds have to be sent in the right token
+    let err = cw20_receive_helper(deps.as_mut(), "other_token", "anyone", 1000, accept_loan_msg.clone())
+        .unwrap_err();
+    assert_error(err, ContractError::FundsDontMatchTerms {});
+    let err = accept_loan_helper(deps.as_mut(), "anyone", "creator", 0, coins(1000, "usdc"))
+        .unwrap_err();
+    assert_error(err, ContractError::FundsDontMatchTerms {});
+
+    let res = cw20_receive_helper(deps.as_mut(), "usdc", "anyone", 1000, accept_loan_msg).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(
+            into_cosmos_msg(
+                Cw20ExecuteMsg::Transfer {
+                    recipient: "creator".to_string(),
+                    amount: Uint128::new(1000),
+                },
+                "usdc",
+                None
+            )
+            .unwrap()
+        )
+    );
+    let offer = lender_offers().load(&deps.storage, "1").unwrap();
+    assert_eq!(offer.lender, Addr::unchecked("anyone"));
+
+    // The loan is paid back with a cw20 Send, the fee is deposited through the fee_distributor Receive hook
+    let res = cw20_receive_helper(
+        deps.as_mut(),
+        "usdc",
+        "creator",
+        1100,
+        ExecuteMsg::RepayBorrowedFunds { loan_id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: "anyone".to_string(),
+                        amount: Uint128::new(1095),
+                    },
+                    "usdc",
+                    None
+                )
+                .unwrap()
+            ),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw1155ExecuteMsg::SendFrom {
+                        from: mock_env().contract.address.to_string(),
+                        to: "creator".to_string(),
+                        token_id: "58".to_string(),
+                        value: Uint128::new(45u128),
+                        msg: None,
+                    },
+                    "nft",
+                    None
+                )
+                .unwrap()
+            ),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw20ExecuteMsg::Send {
+                        contract: "fee_distributor".to_string(),
+                        amount: Uint128::new(5),
+                        msg: to_binary(&FeeDistributorMsg::DepositFees {
+                            addresses: vec!["nft".to_string()],
+                            fee_type: FeeType::Funds
+                        })
+                        .unwrap(),
+                    },
+                    "usdc",
+                    None
+                )
+                .unwrap()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_legacy_principle() {
+    // Loans saved before cw20 principles were supported are still readable
+    let terms: LoanTerms = from_slice(
+        br#"{"principle":{"denom":"luna","amount":"456"},"interest":"0","duration_in_blocks":0}"#,
+    )
+    .unwrap();
+    assert_eq!(terms.principle, AssetInfo::Coin(coin(456, "luna")));
+}
+
+#[test]
+fn test_time_based_loan() {
+    let mut deps = mock_dependencies();
+    init_helper(deps.as_mut());
+
+    // 36.5% a year, for 30 days
+    let mut terms = LoanTerms {
+        principle: AssetInfo::Coin(coin(1000, "luna")),
+        interest: Uint128::zero(),
+        duration_in_blocks: 0,
+        installments: None,
+        duration_in_seconds: None,
+        annual_rate: Some(Decimal::from_str("0.365").unwrap()),
+    };
+    let err = add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(terms.clone()),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongInterestRate {});
+
+    terms.duration_in_seconds = Some(30 * 24 * 3600);
+
+    // The rate is bounded and the amount due can't overflow
+    let err = add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(LoanTerms {
+            annual_rate: Some(Decimal::from_str("10.01").unwrap()),
+            ..terms.clone()
+        }),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongInterestRate {});
+    let err = add_collateral_helper(
+        deps.as_mut(),
+        "creator",
+        "nft",
+        "58",
+        Some(Uint128::new(45u128)),
+        Some(LoanTerms {
+            principle: AssetInfo::Coin(coin(u128::MAX - 10, "luna")),
+            ..terms.clone()
+        }),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast::<StdError>().unwrap(),
+        StdError::Overflow { .. }
+    ));
+
+    assert_eq!(terms.total_due().unwrap(), Uint128::new(1030));
+    for token_id in ["58", "59"] {
+        add_collateral_helper(
+            deps.as_mut(),
+            "creator",
+            "nft",
+            token_id,
+            Some(Uint128::new(45u128)),
+            Some(terms.clone()),
+        )
+        .unwrap();
+    }
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 0, coins(1000, "luna")).unwrap();
+    accept_loan_helper(deps.as_mut(), "anyone", "creator", 1, coins(1000, "luna")).unwrap();
+
+    // The interest accrues with time, whatever the number of blocks
+    let mut env = mock_env();
+    env.block.height += 1_000_000;
+    env.block.time = env.block.time.plus_seconds(10 * 24 * 3600);
+    let collaterals =
+        query_collaterals(deps.as_ref(), env.clone(), "creator".to_string(), None, None).unwrap();
+    assert_eq!(collaterals.collaterals[0].amount_due, Some(Uint128::new(1010)));
+    let collateral = query_collateral_info(deps.as_ref(), env.clone(), "creator".to_string(), 0)
+        .unwrap();
+    assert_eq!(collateral.amount_due, Some(Uint128::new(1010)));
+    let err = withdraw_defaulted_loan_helper(deps.as_mut(), "anyone", "creator", 0, env.clone())
+        .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongLoanState {
+            state: LoanState::Started,
+        },
+    );
+
+    let err =
+        repay_borrowed_funds_helper(deps.as_mut(), "creator", 0, coins(1005, "luna"), env.clone())
+            .unwrap_err();
+    assert_error(
+        err,
+        ContractError::FundsDontMatchTermsAndPrinciple(Uint128::new(1010), Uint128::new(1005)),
+    );
+
+    // The borrower can send a margin for the interest accrued until the transaction is executed, the rest is given back
+    let res =
+        repay_borrowed_funds_helper(deps.as_mut(), "creator", 0, coins(1020, "luna"), env.clone())
+            .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "anyone".to_string(),
+            amount: coins(1009, "luna"),
+        })
+    );
+    assert_eq!(
+        res.messages[2..],
+        vec![
+            SubMsg::new(
+                into_cosmos_msg(
+                    FeeDistributorMsg::DepositFees {
+                        addresses: vec!["nft".to_string()],
+                        fee_type: FeeType::Funds
+                    },
+                    "fee_distributor",
+                    Some(coins(1, "luna"))
+                )
+                .unwrap()
+            ),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(10, "luna"),
+            }),
+        ]
+    );
+
+    // The interest is capped at the full duration and the loan defaults after it
+    env.block.time = env.block.time.plus_seconds(30 * 24 * 3600);
+    let collaterals =
+        query_collaterals(deps.as_ref(), env.clone(), "creator".to_string(), None, None).unwrap();
+    assert_eq!(collaterals.collaterals[0].loan_id, 1);
+    assert_eq!(collaterals.collaterals[0].amount_due, Some(Uint128::new(1030)));
+    assert_eq!(collaterals.collaterals[1].amount_due, None);
+    withdraw_defaulted_loan_helper(deps.as_mut(), "anyone", "creator", 1, env).unwrap();
+}
@@ -1,32 +1,42 @@
 use anyhow::{anyhow, bail, Result};
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, Event, MessageInfo, Reply, Response, StdError,
-    SubMsgResult, Decimal
+    from_binary, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError,
+    SubMsgResult, Decimal, Uint128
 };
 #[cfg(not(feature = "library"))]
 use std::convert::TryInto;
-use std::str::FromStr;
 
 use cw2::set_contract_version;
+use cw_utils::parse_execute_response_data;
 
 use utils::state::OwnerStruct;
 
 
-use raffles_export::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, RaffleResponse};
+use raffles_export::msg::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, RaffleResponse, ReceiveMsg, VerifyResponse,
+};
 use raffles_export::state::{
+    default_drand_chain_hash, AssetInfo,
     ContractInfo, Randomness, MINIMUM_RAFFLE_DURATION, MINIMUM_RAFFLE_TIMEOUT, MINIMUM_RAND_FEE,
 };
 
 use crate::error::ContractError;
 use crate::execute::{
-    execute_buy_tickets, execute_cancel_raffle, execute_claim, execute_create_raffle,
-    execute_modify_raffle, execute_receive, execute_update_randomness,
+    execute_buy_tickets, execute_cancel_raffle, execute_cancel_raffle_series, execute_claim,
+    execute_claim_refund, execute_create_raffle, execute_create_raffle_series,
+    execute_start_series_round, execute_receive_cw1155, execute_receive_nft,
+    execute_modify_raffle, execute_receive, execute_transfer_tickets, execute_update_randomness,
 };
 use crate::query::{
-    query_all_raffles, query_all_tickets, query_contract_info, query_ticket_number,
+    query_all_raffles, query_all_tickets, query_contract_info, query_draw_proof,
+    query_prize_slots, query_raffle_series, query_raffle_stats, query_raffles_awaiting_randomness,
+    query_reward_pool, query_ticket_number, query_user_odds,
+};
+use crate::state::{
+    get_raffle_state, is_owner, load_raffle, migrate_legacy_tickets, pay_randomness_bounty,
+    with_amount, CONTRACT_INFO, RAFFLE_INFO,
 };
-use crate::state::{get_raffle_state, is_owner, load_raffle, CONTRACT_INFO, RAFFLE_INFO};
 
 const CONTRACT_NAME: &str = "illiquidlabs.io:raffles";
 const CONTRACT_VERSION: &str = "0.1.0";
@@ -69,10 +79,19 @@ pub fn instantiate(
         drand_url: msg
             .drand_url
             .unwrap_or_else(|| "https://api.drand.sh/".to_string()),
-        random_pubkey: Binary::from_base64(&msg.random_pubkey)?,
+        drand_chain_hash: msg
+            .drand_chain_hash
+            .unwrap_or_else(default_drand_chain_hash),
+        randomness_bounty: msg.randomness_bounty,
+        reward_pool_fee: msg.reward_pool_fee.unwrap_or_default(),
         verify_signature_contract: deps.api.addr_validate(&msg.verify_signature_contract)?,
+        max_participant_number: msg.max_participant_number,
+        last_series_id: None,
     };
 
+    if let Some(bounty) = &data.randomness_bounty {
+        with_amount(bounty, Uint128::zero())?;
+    }
     data.validate_fee()?;
 
     CONTRACT_INFO.save(deps.storage, &data)?;
@@ -89,6 +108,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             owner,
             assets,
             raffle_ticket_price,
+            additional_ticket_prices,
             raffle_options,
         } => execute_create_raffle(
             deps,
@@ -97,12 +117,14 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             owner,
             assets,
             raffle_ticket_price,
+            additional_ticket_prices.unwrap_or_default(),
             raffle_options,
         ),
         ExecuteMsg::CancelRaffle { raffle_id } => execute_cancel_raffle(deps, env, info, raffle_id),
         ExecuteMsg::ModifyRaffle {
             raffle_id,
             raffle_ticket_price,
+            additional_ticket_prices,
             raffle_options,
         } => execute_modify_raffle(
             deps,
@@ -110,6 +132,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             info,
             raffle_id,
             raffle_ticket_price,
+            additional_ticket_prices,
             raffle_options,
         ),
         ExecuteMsg::BuyTicket {
@@ -117,8 +140,42 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
             ticket_number,
             sent_assets,
         } => execute_buy_tickets(deps, env, info, raffle_id, ticket_number, sent_assets),
-        ExecuteMsg::Receive (msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Receive(ReceiveMsg::Cw20(msg)) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Receive(ReceiveMsg::Cw1155(msg)) => {
+            execute_receive_cw1155(deps, env, info, msg)
+        }
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
         ExecuteMsg::ClaimNft { raffle_id } => execute_claim(deps, env, info, raffle_id),
+        ExecuteMsg::ClaimRefund { raffle_id } => execute_claim_refund(deps, env, info, raffle_id),
+        ExecuteMsg::TransferTickets {
+            raffle_id,
+            recipient,
+            count,
+        } => execute_transfer_tickets(deps, env, info, raffle_id, recipient, count),
+        ExecuteMsg::CreateRaffleSeries {
+            owner,
+            round_assets,
+            raffle_ticket_price,
+            additional_ticket_prices,
+            raffle_options,
+            interval,
+        } => execute_create_raffle_series(
+            deps,
+            env,
+            info,
+            owner,
+            round_assets,
+            raffle_ticket_price,
+            additional_ticket_prices.unwrap_or_default(),
+            raffle_options,
+            interval,
+        ),
+        ExecuteMsg::StartSeriesRound { series_id } => {
+            execute_start_series_round(deps, env, info, series_id)
+        }
+        ExecuteMsg::CancelRaffleSeries { series_id } => {
+            execute_cancel_raffle_series(deps, env, info, series_id)
+        }
         ExecuteMsg::UpdateRandomness {
             raffle_id,
             randomness,
@@ -126,16 +183,43 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
 
         // Admin messages
         ExecuteMsg::ToggleLock { lock } => execute_toggle_lock(deps, env, info, lock),
-        ExecuteMsg::ChangeParameter { parameter, value } => {
-            execute_change_parameter(deps, env, info, parameter, value)
-        }
+        ExecuteMsg::UpdateConfig {
+            owner,
+            fee_addr,
+            minimum_raffle_duration,
+            minimum_raffle_timeout,
+            max_participant_number,
+            raffle_fee,
+            rand_fee,
+            drand_url,
+            verify_signature_contract,
+            drand_chain_hash,
+            randomness_bounty,
+            reward_pool_fee,
+        } => execute_update_config(
+            deps,
+            env,
+            info,
+            owner,
+            fee_addr,
+            minimum_raffle_duration,
+            minimum_raffle_timeout,
+            max_participant_number,
+            raffle_fee,
+            rand_fee,
+            drand_url,
+            verify_signature_contract,
+            drand_chain_hash,
+            randomness_bounty,
+            reward_pool_fee,
+        ),
         ExecuteMsg::ClaimOwnership { } => claim_ownership(deps, env, info),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    // No state migrations performed, just returned a Response
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response> {
+    migrate_legacy_tickets(deps.storage)?;
     Ok(Response::default())
 }
 
@@ -148,6 +232,7 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary> {
             to_binary(&RaffleResponse {
                 raffle_id,
                 raffle_state: get_raffle_state(env, raffle_info.clone()),
+                min_randomness_round: Some(raffle_info.min_randomness_round()),
                 raffle_info: Some(raffle_info),
             })
             .map_err(|x| anyhow!(x))
@@ -176,6 +261,28 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary> {
         QueryMsg::TicketNumber { owner, raffle_id } => {
             to_binary(&query_ticket_number(deps, env, raffle_id, owner)?).map_err(|x| anyhow!(x))
         }
+
+        QueryMsg::PrizeSlots { raffle_id } => {
+            to_binary(&query_prize_slots(deps, env, raffle_id)?).map_err(|x| anyhow!(x))
+        }
+
+        QueryMsg::DrawProof { raffle_id } => {
+            to_binary(&query_draw_proof(deps, env, raffle_id)?).map_err(|x| anyhow!(x))
+        }
+        QueryMsg::RaffleSeries { series_id } => {
+            to_binary(&query_raffle_series(deps, env, series_id)?).map_err(|x| anyhow!(x))
+        }
+        QueryMsg::RaffleStats { raffle_id } => {
+            to_binary(&query_raffle_stats(deps, env, raffle_id)?).map_err(|x| anyhow!(x))
+        }
+        QueryMsg::UserOdds { raffle_id, address } => {
+            to_binary(&query_user_odds(deps, env, raffle_id, address)?).map_err(|x| anyhow!(x))
+        }
+        QueryMsg::RafflesAwaitingRandomness { start_after, limit } => to_binary(
+            &query_raffles_awaiting_randomness(deps, env, start_after, limit)?,
+        )
+        .map_err(|x| anyhow!(x)),
+        QueryMsg::RewardPool {} => to_binary(&query_reward_pool(deps)?).map_err(|x| anyhow!(x)),
     }
 }
 
@@ -198,62 +305,100 @@ pub fn execute_toggle_lock(
         .add_attribute("value", lock.to_string()))
 }
 
-/// Change the different contract parameters
+/// Update the contract parameters
+/// All fields are optional, only the specified ones are changed
+/// The resulting configuration is validated as a whole before being saved
 /// Admin only action
-pub fn execute_change_parameter(
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_config(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    parameter: String,
-    value: String,
+    owner: Option<String>,
+    fee_addr: Option<String>,
+    minimum_raffle_duration: Option<u64>,
+    minimum_raffle_timeout: Option<u64>,
+    max_participant_number: Option<u32>,
+    raffle_fee: Option<Decimal>,
+    rand_fee: Option<Decimal>,
+    drand_url: Option<String>,
+    verify_signature_contract: Option<String>,
+    drand_chain_hash: Option<String>,
+    randomness_bounty: Option<AssetInfo>,
+    reward_pool_fee: Option<Decimal>,
 ) -> Result<Response> {
     let mut contract_info = is_owner(deps.storage, info.sender)?;
+    let mut changes: Vec<(&str, String)> = vec![];
 
-    match parameter.as_str() {
-        "owner" => {
-            let owner = deps.api.addr_validate(&value)?;
-            contract_info.owner = contract_info.owner.propose_new_owner(owner);
-        }
-        "fee_addr" => {
-            let addr = deps.api.addr_validate(&value)?;
-            contract_info.fee_addr = addr;
-        }
-        "minimum_raffle_duration" => {
-            let time = value.parse::<u64>()?;
-            contract_info.minimum_raffle_duration = time.max(MINIMUM_RAFFLE_DURATION);
-        }
-        "minimum_raffle_timeout" => {
-            let time = value.parse::<u64>()?;
-            contract_info.minimum_raffle_timeout = time.max(MINIMUM_RAFFLE_TIMEOUT);
-        }
-        "raffle_fee" => {
-            let fee = Decimal::from_str(&value)?;
-            contract_info.raffle_fee = fee;
-        }
-        "rand_fee" => {
-            let fee = Decimal::from_str(&value)?;
-            contract_info.rand_fee = fee.max(MINIMUM_RAND_FEE);
-        }
-        "drand_url" => {
-            contract_info.drand_url = value.clone();
-        }
-        "verify_signature_contract" => {
-            let addr = deps.api.addr_validate(&value)?;
-            contract_info.verify_signature_contract = addr;
-        }
-        "random_pubkey" => {
-            contract_info.random_pubkey = Binary::from_base64(&value)?;
-        }
-        _ => return Err(anyhow!(ContractError::ParameterNotFound {})),
+    if let Some(owner) = owner {
+        let owner = deps.api.addr_validate(&owner)?;
+        contract_info.owner = contract_info.owner.propose_new_owner(owner.clone());
+        changes.push(("owner", owner.to_string()));
+    }
+    if let Some(fee_addr) = fee_addr {
+        contract_info.fee_addr = deps.api.addr_validate(&fee_addr)?;
+        changes.push(("fee_addr", fee_addr));
+    }
+    if let Some(minimum_raffle_duration) = minimum_raffle_duration {
+        contract_info.minimum_raffle_duration =
+            minimum_raffle_duration.max(MINIMUM_RAFFLE_DURATION);
+        changes.push((
+            "minimum_raffle_duration",
+            contract_info.minimum_raffle_duration.to_string(),
+        ));
+    }
+    if let Some(minimum_raffle_timeout) = minimum_raffle_timeout {
+        contract_info.minimum_raffle_timeout = minimum_raffle_timeout.max(MINIMUM_RAFFLE_TIMEOUT);
+        changes.push((
+            "minimum_raffle_timeout",
+            contract_info.minimum_raffle_timeout.to_string(),
+        ));
+    }
+    if let Some(max_participant_number) = max_participant_number {
+        contract_info.max_participant_number = Some(max_participant_number);
+        changes.push(("max_participant_number", max_participant_number.to_string()));
+    }
+    if let Some(raffle_fee) = raffle_fee {
+        contract_info.raffle_fee = raffle_fee;
+        changes.push(("raffle_fee", raffle_fee.to_string()));
+    }
+    if let Some(rand_fee) = rand_fee {
+        contract_info.rand_fee = rand_fee.max(MINIMUM_RAND_FEE);
+        changes.push(("rand_fee", contract_info.rand_fee.to_string()));
+    }
+    if let Some(drand_url) = drand_url {
+        contract_info.drand_url = drand_url.clone();
+        changes.push(("drand_url", drand_url));
+    }
+    if let Some(verify_signature_contract) = verify_signature_contract {
+        contract_info.verify_signature_contract =
+            deps.api.addr_validate(&verify_signature_contract)?;
+        changes.push(("verify_signature_contract", verify_signature_contract));
+    }
+    if let Some(drand_chain_hash) = drand_chain_hash {
+        contract_info.drand_chain_hash = drand_chain_hash.clone();
+        changes.push(("drand_chain_hash", drand_chain_hash));
+    }
+    if let Some(randomness_bounty) = randomness_bounty {
+        let bounty_value = match &randomness_bounty {
+            AssetInfo::Coin(coin) => coin.to_string(),
+            AssetInfo::Cw20Coin(coin) => format!("{}{}", coin.amount, coin.address),
+            _ => bail!(ContractError::WrongFundsType {}),
+        };
+        contract_info.randomness_bounty = Some(randomness_bounty);
+        changes.push(("randomness_bounty", bounty_value));
+    }
+    if let Some(reward_pool_fee) = reward_pool_fee {
+        contract_info.reward_pool_fee = reward_pool_fee;
+        changes.push(("reward_pool_fee", reward_pool_fee.to_string()));
     }
 
     contract_info.validate_fee()?;
     CONTRACT_INFO.save(deps.storage, &contract_info)?;
 
     Ok(Response::new()
-        .add_attribute("action", "modify_parameter")
-        .add_attribute("parameter", parameter)
-        .add_attribute("value", value))
+        .add_attribute("action", "update_config")
+        .add_attributes(changes))
 }
 
 /// Claim ownership of the contract
@@ -293,72 +438,38 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response> {
 pub fn verify(deps: DepsMut, _env: Env, msg: SubMsgResult) -> Result<Response> {
     match msg {
         SubMsgResult::Ok(subcall) => {
-            let event: Event = subcall
-                .events
-                .into_iter()
-                .find(|e| e.ty == "wasm")
-                .ok_or_else(|| ContractError::NotFoundError("wasm results".to_string()))?;
-
-            let round = event
-                .attributes
-                .clone()
-                .into_iter()
-                .find(|attr| attr.key == "round")
-                .map_or(
-                    Err(ContractError::NotFoundError("randomness round".to_string())),
-                    |round| {
-                        round
-                            .value
-                            .parse::<u64>()
-                            .map_err(|_| ContractError::ParseError("randomness round".to_string()))
-                    },
-                )?;
-
-            let randomness: String = event
-                .attributes
-                .clone()
-                .into_iter()
-                .find(|attr| attr.key == "randomness")
-                .map(|rand| rand.value)
-                .ok_or_else(|| ContractError::NotFoundError("randomness value".to_string()))?;
-
-            let raffle_id: u64 = event
-                .attributes
-                .clone()
-                .into_iter()
-                .find(|attr| attr.key == "raffle_id")
-                .map_or(
-                    Err(ContractError::NotFoundError("raffle_id".to_string())),
-                    |raffle_id| {
-                        raffle_id
-                            .value
-                            .parse::<u64>()
-                            .map_err(|_| ContractError::ParseError("raffle_id".to_string()))
-                    },
-                )?;
-
-            let owner = deps.api.addr_validate(
-                &event
-                    .attributes
-                    .into_iter()
-                    .find(|attr| attr.key == "owner")
-                    .map(|owner| owner.value)
-                    .ok_or_else(|| ContractError::NotFoundError("randomness provider".to_string()))?,
+            let data = subcall
+                .data
+                .ok_or_else(|| ContractError::NotFoundError("verify response".to_string()))?;
+            let verify_response: VerifyResponse = from_binary(
+                &parse_execute_response_data(&data)?
+                    .data
+                    .ok_or_else(|| ContractError::NotFoundError("verify response".to_string()))?,
             )?;
 
+            let raffle_id = verify_response.raffle_id;
+            let owner = deps.api.addr_validate(&verify_response.owner)?;
+
             let mut raffle_info = RAFFLE_INFO.load(deps.storage, raffle_id)?;
+
+            // The first valid randomness of a raffle is rewarded from the reward pool
+            let bounty_messages = pay_randomness_bounty(deps.storage, &raffle_info, &owner)?;
+
             raffle_info.randomness = Some(Randomness {
-                randomness: Binary::from_base64(&randomness)?
+                randomness: verify_response
+                    .randomness
                     .as_slice()
                     .try_into()
                     .map_err(|_| ContractError::ParseError("randomness".to_string()))?,
-                randomness_round: round,
+                randomness_round: verify_response.round,
                 randomness_owner: owner.clone(),
+                randomness_signature: verify_response.signature,
             });
 
             RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
 
             Ok(Response::new()
+                .add_messages(bounty_messages)
                 .add_attribute("action", "update_randomness")
                 .add_attribute("raffle_id", raffle_id.to_string())
                 .add_attribute("sender", owner))
@@ -13,9 +13,6 @@ pub enum ContractError {
     #[error("Unreachable code, something weird happened")]
     Unreachable {},
 
-    #[error("An unplanned bug just happened :/")]
-    ContractBug {},
-
     #[error("Error when parsing a value for {0}")]
     ParseError(String),
 
@@ -37,24 +34,39 @@ pub enum ContractError {
     #[error("You can't buy tickets on this raffle anymore")]
     CantBuyTickets {},
 
+    #[error("You don't fulfill the conditions to enter this raffle")]
+    NotEligible {},
+
     #[error("A raffle can only be done with CW721 or CW1155 assets")]
     WrongAssetType {},
 
     #[error("Tickets to a raffle can only be bought with native assets or CW20 coins")]
     WrongFundsType {},
 
+    #[error("A raffle can only have one ticket price per currency")]
+    DuplicateTicketCurrency {},
+
     #[error("The sent asset doesn't match the asset in the message sent along with it")]
     AssetMismatch {},
 
     #[error("Please include at least one asset when creating a raffle")]
     NoAssets {},
 
+    #[error("Each raffle asset has to be won in exactly one non-empty prize slot")]
+    WrongPrizeSlots {},
+
+    #[error("The minimum number of tickets can't be higher than the maximum number of tickets")]
+    WrongTicketThreshold {},
+
     #[error("The sent assets ({assets_received:?}) don't match the required assets ({assets_wanted:?}) for this raffle")]
     PaiementNotSufficient {
         assets_wanted: AssetInfo,
         assets_received: AssetInfo,
     },
 
+    #[error("At least one ticket has to be bought")]
+    NoTicketsBought {},
+
     #[error("Too much tickets were already purchased for this raffle. Max : {max:?}, Number before purchase : {nb_before:?}, Number after purchase : {nb_after:?}")]
     TooMuchTickets {
         max: u32,
@@ -62,6 +74,24 @@ pub enum ContractError {
         nb_after: u32,
     },
 
+    #[error("The rounds of a raffle series can't overlap, the interval has to be longer than the raffle duration")]
+    WrongSeriesInterval {},
+
+    #[error("All the rounds of a raffle series with prize slots must have the same number of assets")]
+    WrongSeriesRounds {},
+
+    #[error("The next round of this raffle series can't start yet")]
+    SeriesRoundNotScheduled {},
+
+    #[error("There are no assets left for another round of this raffle series")]
+    SeriesInventoryEmpty {},
+
+    #[error("You don't own enough tickets for this raffle. Owned : {owned:?}, Requested : {requested:?}")]
+    NotEnoughTickets { owned: u32, requested: u32 },
+
+    #[error("Tickets can only be transferred to another address, at least one at a time")]
+    WrongTicketTransfer {},
+
     #[error("Too much tickets were already purchased by this user for this raffle. Max : {max:?}, Number before purchase : {nb_before:?}, Number after purchase : {nb_after:?}")]
     TooMuchTicketsForUser {
         max: u32,
@@ -72,27 +102,24 @@ pub enum ContractError {
     #[error("The provided randomness is invalid current round : {current_round:?}")]
     RandomnessNotAccepted { current_round: u64 },
 
+    #[error("The provided randomness was published before the raffle closed, the first accepted round is {min_round:?}")]
+    RandomnessRoundTooEarly { min_round: u64 },
+
     #[error("This raffle is not ready to accept new randomness. Only Closed raffles can be decided upon. Current status : {status:?}")]
     WrongStateForRandmness { status: RaffleState },
 
     #[error("This raffle is not ready to be claimed.  Current status : {status:?}")]
     WrongStateForClaim { status: RaffleState },
 
+    #[error("Tickets can only be refunded when a raffle failed. Current status : {status:?}")]
+    WrongStateForRefund { status: RaffleState },
+
+    #[error("You don't have any ticket to refund in this raffle")]
+    NoTicketsToRefund {},
+
     #[error("This raffle cannot be cancelled anymore,   Current status : {status:?}")]
     WrongStateForCancel { status: RaffleState },
 
     #[error("This raffle has already started.")]
     RaffleAlreadyStarted {},
-
-    #[error("The public key you indicated is invalid")]
-    InvalidPubkey {},
-
-    #[error("The randomness signatur is invalid")]
-    InvalidSignature {},
-
-    #[error("Wrong Format for the verify response")]
-    ParseReplyError {},
-
-    #[error("This parameter name was not found, you can't change it !")]
-    ParameterNotFound {},
 }
@@ -1,24 +1,30 @@
-use crate::query::is_nft_owner;
+use crate::query::{assert_gating_conditions, is_nft_owner};
 use crate::state::{
-    assert_randomness_origin_and_order, can_buy_ticket, get_raffle_owner_finished_messages,
-    get_raffle_owner_messages, get_raffle_state, get_raffle_winner, get_raffle_winner_messages,
-    is_raffle_owner, ticket_cost, CONTRACT_INFO, RAFFLE_INFO, RAFFLE_TICKETS, USER_TICKETS,
+    assert_randomness_origin_and_order, assert_valid_raffle_options, can_buy_ticket,
+    get_raffle_owner_finished_messages, get_raffle_owner_messages, get_raffle_state,
+    get_raffle_winner_messages, get_raffle_winners, get_refund_messages,
+    get_series_owner_messages, is_raffle_owner, add_funds, load_raffle, load_series, split_funds,
+    ticket_cost, transfer_ticket_ranges, CONTRACT_INFO, RAFFLE_INFO, RAFFLE_SERIES,
+    RAFFLE_TICKETS, USER_PAYMENTS, USER_TICKETS,
 };
 use anyhow::{anyhow, bail, Result};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    from_binary, Addr, CosmosMsg, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    from_binary, Addr, Binary, CosmosMsg, Deps, DepsMut, Env, Event, MessageInfo, Response, StdResult,
+    Uint128,
 };
 
 use crate::error::ContractError;
 use raffles_export::state::{
-    AssetInfo, Cw20Coin, RaffleInfo, RaffleOptions, RaffleOptionsMsg, RaffleState,
+    AssetInfo, BeaconNetwork, Cw20Coin, RaffleInfo, RaffleOptions, RaffleOptionsMsg, RaffleSeries, RaffleState,
+    TicketRange,
 };
 
 use cw1155::Cw1155ExecuteMsg;
+use cw1155::Cw1155ReceiveMsg;
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
-use cw721::Cw721ExecuteMsg;
-use raffles_export::msg::{into_cosmos_msg, DrandRandomness, ExecuteMsg};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use raffles_export::msg::{into_cosmos_msg, DrandRandomness, ExecuteMsg, VerifierQueryMsg};
 
 /// Create a new raffle by depositing assets.
 /// The raffle has many options, to make it most accessible.
@@ -41,6 +47,8 @@ use raffles_export::msg::{into_cosmos_msg, DrandRandomness, ExecuteMsg};
 /// `raffle_ticket_price`: The needed tokens (native or CW20) needed to buy a raffle ticket
 /// If you want to have free tickets, specify a 0 amount on a native token (any denom)
 ///
+/// `additional_ticket_prices`: Tickets can also be bought at these prices. Only one price per currency is accepted
+///
 /// `max_participant_number`: maximum number of participants to the raffle. Default : contract_info.max_participant_number
 #[allow(clippy::too_many_arguments)]
 pub fn execute_create_raffle(
@@ -50,6 +58,7 @@ pub fn execute_create_raffle(
     owner: Option<String>,
     all_assets: Vec<AssetInfo>,
     raffle_ticket_price: AssetInfo,
+    additional_ticket_prices: Vec<AssetInfo>,
     raffle_options: RaffleOptionsMsg,
 ) -> Result<Response> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
@@ -65,7 +74,35 @@ pub fn execute_create_raffle(
 
 
     // Then we physcially transfer all the assets
-    let transfer_messages: Vec<CosmosMsg> = all_assets
+    let transfer_messages = _get_deposit_messages(deps.as_ref(), &env, &info.sender, &all_assets)?;
+    // Then we create the internal raffle structure
+    let owner = owner.map(|x| deps.api.addr_validate(&x)).transpose()?;
+    let raffle_id = _create_raffle(
+        deps,
+        env,
+        owner.clone().unwrap_or_else(|| info.sender.clone()),
+        all_assets,
+        raffle_ticket_price,
+        additional_ticket_prices,
+        raffle_options,
+        None,
+    )?;
+
+    Ok(Response::new()
+        .add_messages(transfer_messages)
+        .add_attribute("action", "create_raffle")
+        .add_attribute("raffle_id", raffle_id.to_string())
+        .add_attribute("owner", owner.unwrap_or_else(|| info.sender.clone())))
+}
+
+/// Util to get the messages transferring the raffled assets from their owner to the contract
+fn _get_deposit_messages(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    all_assets: &[AssetInfo],
+) -> Result<Vec<CosmosMsg>> {
+    all_assets
         .iter()
         .map(|asset| match &asset {
             AssetInfo::Cw721Coin(token) => {
@@ -73,7 +110,7 @@ pub fn execute_create_raffle(
                 // (Audit results)
                 // Before transferring the NFT, we make sure the current NFT owner is indeed the borrower of funds
                 // Otherwise, this would cause anyone to be able to create loans in the name of the owner if a bad approval was done
-                is_nft_owner(deps.as_ref(), info.sender.clone(), token.address.to_string(), token.token_id.to_string())?;
+                is_nft_owner(deps, sender.clone(), token.address.to_string(), token.token_id.to_string())?;
 
                 let message = Cw721ExecuteMsg::TransferNft {
                     recipient: env.contract.address.clone().into(),
@@ -84,7 +121,7 @@ pub fn execute_create_raffle(
             }
             AssetInfo::Cw1155Coin(token) => {
                 let message = Cw1155ExecuteMsg::SendFrom {
-                    from: info.sender.to_string(),
+                    from: sender.to_string(),
                     to: env.contract.address.clone().into(),
                     token_id: token.token_id.clone(),
                     value: token.value,
@@ -95,23 +132,89 @@ pub fn execute_create_raffle(
             }
             _ => Err(anyhow!(ContractError::WrongAssetType {})),
         })
-        .collect::<Result<Vec<CosmosMsg>>>()?;
-    // Then we create the internal raffle structure
-    let owner = owner.map(|x| deps.api.addr_validate(&x)).transpose()?;
-    let raffle_id = _create_raffle(
-        deps,
-        env,
-        owner.clone().unwrap_or_else(|| info.sender.clone()),
-        all_assets,
-        raffle_ticket_price,
-        raffle_options,
-    )?;
+        .collect()
+}
 
-    Ok(Response::new()
-        .add_messages(transfer_messages)
-        .add_attribute("action", "create_raffle")
-        .add_attribute("raffle_id", raffle_id.to_string())
-        .add_attribute("owner", owner.unwrap_or_else(|| info.sender.clone())))
+/// Create a new raffle by sending a CW721 token to the contract using a send_nft message
+/// The embedded message has to be a CreateRaffle message, with the received token as the only raffle asset
+/// This allows creating a raffle in a single transaction, without approving the contract first
+pub fn execute_receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response> {
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let received_asset = AssetInfo::cw721(info.sender.as_str(), &wrapper.token_id);
+    _create_raffle_from_received_asset(deps, env, sender, received_asset, wrapper.msg)
+}
+
+/// Create a new raffle by sending a CW1155 token to the contract using a send_from message
+/// The embedded message has to be a CreateRaffle message, with the received tokens as the only raffle asset
+pub fn execute_receive_cw1155(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw1155ReceiveMsg,
+) -> Result<Response> {
+    // The tokens are sent from the `from` address on behalf of the operator
+    let sender = deps
+        .api
+        .addr_validate(&wrapper.from.unwrap_or(wrapper.operator))?;
+    let received_asset =
+        AssetInfo::cw1155_raw(info.sender.as_str(), &wrapper.token_id, wrapper.amount);
+    _create_raffle_from_received_asset(deps, env, sender, received_asset, wrapper.msg)
+}
+
+/// Internal function used when a raffle asset is sent to the contract directly
+/// This function checks the sent message matches the sent asset and creates the raffle internally
+fn _create_raffle_from_received_asset(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    received_asset: AssetInfo,
+    msg: Binary,
+) -> Result<Response> {
+    match from_binary(&msg)? {
+        ExecuteMsg::CreateRaffle {
+            owner,
+            assets,
+            raffle_options,
+            raffle_ticket_price,
+            additional_ticket_prices,
+        } => {
+            let contract_info = CONTRACT_INFO.load(deps.storage)?;
+            if contract_info.lock {
+                bail!(ContractError::ContractIsLocked {});
+            }
+
+            // The asset is already in the contract, we only make sure it's the one specified in the message
+            if assets != vec![received_asset] {
+                bail!(ContractError::AssetMismatch {});
+            }
+
+            let owner = owner
+                .map(|x| deps.api.addr_validate(&x))
+                .transpose()?
+                .unwrap_or(sender);
+            let raffle_id = _create_raffle(
+                deps,
+                env,
+                owner.clone(),
+                assets,
+                raffle_ticket_price,
+                additional_ticket_prices.unwrap_or_default(),
+                raffle_options,
+                None,
+            )?;
+
+            Ok(Response::new()
+                .add_attribute("action", "create_raffle")
+                .add_attribute("raffle_id", raffle_id.to_string())
+                .add_attribute("owner", owner))
+        }
+        _ => Err(anyhow!(ContractError::Unauthorized {})),
+    }
 }
 
 /// Create a new raffle and assign it a unique id
@@ -124,10 +227,45 @@ pub fn _create_raffle(
     owner: Addr,
     all_assets: Vec<AssetInfo>,
     raffle_ticket_price: AssetInfo,
+    additional_ticket_prices: Vec<AssetInfo>,
     raffle_options: RaffleOptionsMsg,
+    series_id: Option<u64>,
 ) -> Result<u64> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
+    // The raffle is bound to the drand network currently used, with the scheme and timing registered in the verifier
+    let beacon: BeaconNetwork = deps.querier.query_wasm_smart(
+        contract_info.verify_signature_contract.clone(),
+        &VerifierQueryMsg::Beacon {
+            chain_hash: contract_info.drand_chain_hash.clone(),
+        },
+    )?;
+
+    let raffle_info = RaffleInfo {
+        owner,
+        assets: all_assets.clone(),
+        raffle_ticket_price,
+        additional_ticket_prices,
+        collected_funds: vec![],
+        number_of_tickets: 0u32,
+        unique_participants: 0,
+        randomness: None,
+        winners: vec![],
+        is_cancelled: false,
+        raffle_options: RaffleOptions::new(
+            env,
+            all_assets.len(),
+            raffle_options,
+            contract_info.clone(),
+        ),
+        series_id,
+        drand_chain_hash: beacon.chain_hash,
+        drand_scheme: beacon.scheme,
+        drand_genesis_time: beacon.genesis_time,
+        drand_period: beacon.period,
+    };
+    assert_valid_raffle_options(deps.api, &raffle_info)?;
+
     // We start by creating a new trade_id (simply incremented from the last id)
     let raffle_id: u64 = CONTRACT_INFO
         .update(deps.storage, |mut c| -> StdResult<_> {
@@ -143,25 +281,219 @@ pub fn _create_raffle(
         // In that case, we emit an error
         // The priority is : We do not want to overwrite existing data
         Some(_) => Err(ContractError::ExistsInRaffleInfo {}),
-        None => Ok(RaffleInfo {
-            owner,
-            assets: all_assets.clone(),
-            raffle_ticket_price: raffle_ticket_price.clone(), // No checks for the assetInfo type, the worst thing that can happen is an error when trying to buy a raffle ticket
-            number_of_tickets: 0u32,
-            randomness: None,
-            winner: None,
-            is_cancelled: false,
-            raffle_options: RaffleOptions::new(
-                env,
-                all_assets.len(),
-                raffle_options,
-                contract_info,
-            ),
-        }),
+        None => Ok(raffle_info),
     })?;
     Ok(raffle_id)
 }
 
+/// Create a series of raffles, run one after the other
+/// All the assets of the series are deposited at once, `round_assets` lists the assets raffled in each round
+/// To raffle a stack of cw1155 tokens, specify a part of the stack in each round
+/// The first round starts at `raffle_options.raffle_start_timestamp`, the next ones at least `interval` seconds after the previous one
+/// A round is started when the previous round is claimed, or by anyone once its scheduled time has come
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_raffle_series(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: Option<String>,
+    round_assets: Vec<Vec<AssetInfo>>,
+    raffle_ticket_price: AssetInfo,
+    additional_ticket_prices: Vec<AssetInfo>,
+    raffle_options: RaffleOptionsMsg,
+    interval: u64,
+) -> Result<Response> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if contract_info.lock {
+        bail!(ContractError::ContractIsLocked {});
+    }
+
+    // Every round has to raffle some assets
+    if round_assets.is_empty() || round_assets.iter().any(|assets| assets.is_empty()) {
+        bail!(ContractError::NoAssets {})
+    }
+    // The prize slots refer to asset indexes, so they only make sense if all rounds look alike
+    if raffle_options.prize_slots.is_some()
+        && round_assets
+            .iter()
+            .any(|assets| assets.len() != round_assets[0].len())
+    {
+        bail!(ContractError::WrongSeriesRounds {})
+    }
+    // A round has to be closed when the next one starts
+    let round_options = RaffleOptions::new(
+        env.clone(),
+        round_assets[0].len(),
+        raffle_options.clone(),
+        contract_info,
+    );
+    if interval < round_options.raffle_duration {
+        bail!(ContractError::WrongSeriesInterval {})
+    }
+
+    // We transfer all the assets at once, merging the cw1155 tokens of all the rounds
+    let mut deposited_assets: Vec<AssetInfo> = vec![];
+    for asset in round_assets.concat() {
+        match (
+            deposited_assets.iter_mut().find(|deposited| match (deposited, &asset) {
+                (AssetInfo::Cw1155Coin(x), AssetInfo::Cw1155Coin(y)) => {
+                    x.address == y.address && x.token_id == y.token_id
+                }
+                _ => false,
+            }),
+            asset,
+        ) {
+            (Some(AssetInfo::Cw1155Coin(deposited)), AssetInfo::Cw1155Coin(token)) => {
+                deposited.value += token.value
+            }
+            (_, asset) => deposited_assets.push(asset),
+        }
+    }
+    let transfer_messages =
+        _get_deposit_messages(deps.as_ref(), &env, &info.sender, &deposited_assets)?;
+
+    // Then we save the series and start its first round
+    let owner = owner
+        .map(|x| deps.api.addr_validate(&x))
+        .transpose()?
+        .unwrap_or_else(|| info.sender.clone());
+    let series_id: u64 = CONTRACT_INFO
+        .update(deps.storage, |mut c| -> StdResult<_> {
+            c.last_series_id = c.last_series_id.map_or(Some(0), |id| Some(id + 1));
+            Ok(c)
+        })?
+        .last_series_id
+        .unwrap();
+    RAFFLE_SERIES.save(
+        deps.storage,
+        series_id,
+        &RaffleSeries {
+            owner: owner.clone(),
+            remaining_rounds: round_assets,
+            raffle_ticket_price,
+            additional_ticket_prices,
+            raffle_options,
+            interval,
+            next_round_start: round_options.raffle_start_timestamp,
+            raffle_ids: vec![],
+        },
+    )?;
+    let raffle_id = _start_series_round(deps.branch(), env, series_id)?;
+
+    Ok(Response::new()
+        .add_messages(transfer_messages)
+        .add_attribute("action", "create_raffle_series")
+        .add_attribute("series_id", series_id.to_string())
+        .add_attribute("raffle_id", raffle_id.to_string())
+        .add_attribute("owner", owner))
+}
+
+/// Start the next round of a raffle series once its scheduled time has come
+/// Anyone can call this function
+pub fn execute_start_series_round(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    series_id: u64,
+) -> Result<Response> {
+    let series = load_series(deps.storage, series_id)?;
+    if env.block.time < series.next_round_start {
+        bail!(ContractError::SeriesRoundNotScheduled {});
+    }
+    let raffle_id = _start_series_round(deps, env, series_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "start_series_round")
+        .add_attribute("series_id", series_id.to_string())
+        .add_attribute("raffle_id", raffle_id.to_string()))
+}
+
+/// Cancels the rounds of a raffle series that didn't start yet
+/// The round currently running is not affected
+pub fn execute_cancel_raffle_series(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    series_id: u64,
+) -> Result<Response> {
+    let mut series = load_series(deps.storage, series_id)?;
+    if series.owner != info.sender {
+        bail!(ContractError::Unauthorized {});
+    }
+
+    // We transfer the remaining assets back to the owner
+    let transfer_messages = get_series_owner_messages(env, &series)?;
+    series.remaining_rounds = vec![];
+    RAFFLE_SERIES.save(deps.storage, series_id, &series)?;
+
+    Ok(Response::new()
+        .add_messages(transfer_messages)
+        .add_attribute("action", "cancel_raffle_series")
+        .add_attribute("series_id", series_id.to_string()))
+}
+
+/// Creates the raffle of the next round of a series
+/// Internal function that doesn't check the schedule
+/// No round can start while the contract is locked
+fn _start_series_round(mut deps: DepsMut, env: Env, series_id: u64) -> Result<u64> {
+    if CONTRACT_INFO.load(deps.storage)?.lock {
+        bail!(ContractError::ContractIsLocked {});
+    }
+    let mut series = load_series(deps.storage, series_id)?;
+    if series.remaining_rounds.is_empty() {
+        bail!(ContractError::SeriesInventoryEmpty {});
+    }
+    let assets = series.remaining_rounds.remove(0);
+    let round_start = series.next_round_start.max(env.block.time);
+    let raffle_id = _create_raffle(
+        deps.branch(),
+        env,
+        series.owner.clone(),
+        assets,
+        series.raffle_ticket_price.clone(),
+        series.additional_ticket_prices.clone(),
+        RaffleOptionsMsg {
+            raffle_start_timestamp: Some(round_start),
+            ..series.raffle_options.clone()
+        },
+        Some(series_id),
+    )?;
+
+    series.next_round_start = round_start.plus_seconds(series.interval);
+    series.raffle_ids.push(raffle_id);
+    RAFFLE_SERIES.save(deps.storage, series_id, &series)?;
+    Ok(raffle_id)
+}
+
+/// Starts the next round of a series when its latest round is claimed
+/// This is best-effort : the claim of the finished round must go through even if the next round can't be created.
+/// In that case an event is emitted and the round can still be started later with `StartSeriesRound`
+/// The round creation checks everything before writing to storage, so a failed attempt leaves no partial state
+fn _start_next_series_round(
+    deps: DepsMut,
+    env: Env,
+    raffle_id: u64,
+    raffle_info: &RaffleInfo,
+    response: Response,
+) -> Result<Response> {
+    let series_id = match raffle_info.series_id {
+        Some(series_id) => series_id,
+        None => return Ok(response),
+    };
+    let series = load_series(deps.storage, series_id)?;
+    if series.raffle_ids.last() != Some(&raffle_id) || series.remaining_rounds.is_empty() {
+        return Ok(response);
+    }
+    Ok(match _start_series_round(deps, env, series_id) {
+        Ok(next_raffle_id) => response.add_attribute("next_raffle_id", next_raffle_id.to_string()),
+        Err(err) => response.add_event(
+            Event::new("series_round_not_started")
+                .add_attribute("series_id", series_id.to_string())
+                .add_attribute("error", err.to_string()),
+        ),
+    })
+}
+
 /// Cancels a raffle
 /// This function is only accessible if no raffle ticket was bought on the raffle
 pub fn execute_cancel_raffle(
@@ -209,6 +541,7 @@ pub fn execute_modify_raffle(
     info: MessageInfo,
     raffle_id: u64,
     raffle_ticket_price: Option<AssetInfo>,
+    additional_ticket_prices: Option<Vec<AssetInfo>>,
     raffle_options: RaffleOptionsMsg,
 ) -> Result<Response> {
     let mut raffle_info = is_raffle_owner(deps.storage, raffle_id, info.sender)?;
@@ -225,10 +558,14 @@ pub fn execute_modify_raffle(
         raffle_options,
         contract_info,
     );
-    // Then modify the ticket price
+    // Then modify the ticket prices
     if let Some(raffle_ticket_price) = raffle_ticket_price {
         raffle_info.raffle_ticket_price = raffle_ticket_price;
     }
+    if let Some(additional_ticket_prices) = additional_ticket_prices {
+        raffle_info.additional_ticket_prices = additional_ticket_prices;
+    }
+    assert_valid_raffle_options(deps.api, &raffle_info)?;
     RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
 
     Ok(Response::new()
@@ -242,7 +579,7 @@ pub fn execute_modify_raffle(
 ///
 /// `assets` : the assets you want to deposit against a raffle ticket.
 /// These assets can either be a native coin or a CW20 token
-/// These must correspond to the raffle_info.raffle_ticket_price exactly
+/// These must correspond exactly to one of the raffle ticket prices (raffle_info.ticket_prices())
 /// This function needs the sender to approve token transfer (for CW20 tokens) priori to the transaction
 /// The next function provides a receiver message implementation if you prefer
 pub fn execute_buy_tickets(
@@ -354,10 +691,20 @@ pub fn _buy_tickets(
 ) -> Result<()> {
     let mut raffle_info = RAFFLE_INFO.load(deps.storage, raffle_id)?;
 
-    // We first check the sent assets match the raffle assets
-    if ticket_cost(raffle_info.clone(), ticket_number)? != assets {
+    // An empty ticket range would share its first id with the next purchase
+    if ticket_number == 0 {
+        bail!(ContractError::NoTicketsBought {});
+    }
+
+    // We first check the sent assets match the raffle ticket price in the same currency
+    let ticket_price = raffle_info
+        .ticket_prices()
+        .into_iter()
+        .find(|price| price.is_same_currency(&assets))
+        .unwrap_or_else(|| raffle_info.raffle_ticket_price.clone());
+    if ticket_cost(ticket_price.clone(), ticket_number)? != assets {
         bail!(ContractError::PaiementNotSufficient {
-            assets_wanted: raffle_info.raffle_ticket_price,
+            assets_wanted: ticket_price,
             assets_received: assets
         });
     }
@@ -365,6 +712,9 @@ pub fn _buy_tickets(
     // We then check the raffle is in the right state
     can_buy_ticket(env, raffle_info.clone())?;
 
+    // Then we check the buyer is allowed to enter the raffle
+    assert_gating_conditions(deps.as_ref(), &raffle_info, &owner)?;
+
     // Then we check the user has the right to buy `ticket_number` more tickets
     if let Some(max_ticket_per_address) = raffle_info.raffle_options.max_ticket_per_address {
         let current_ticket_number = USER_TICKETS
@@ -390,19 +740,33 @@ pub fn _buy_tickets(
         }
     };
 
-    // Then we save the sender to the bought tickets
-    for n in 0..ticket_number {
-        RAFFLE_TICKETS.save(
-            deps.storage,
-            (raffle_id, raffle_info.number_of_tickets + n),
-            &owner,
-        )?;
-    }
+    // Then we save the sender to the bought tickets, as a single range of ticket ids
+    RAFFLE_TICKETS.save(
+        deps.storage,
+        (raffle_id, raffle_info.number_of_tickets),
+        &TicketRange {
+            owner: owner.clone(),
+            count: ticket_number,
+        },
+    )?;
 
-    USER_TICKETS.update::<_, anyhow::Error>(deps.storage, (&owner, raffle_id), |x| match x {
-        Some(current_ticket_number) => Ok(current_ticket_number + ticket_number),
-        None => Ok(ticket_number),
+    let current_ticket_number = USER_TICKETS
+        .may_load(deps.storage, (&owner, raffle_id))?
+        .unwrap_or(0);
+    if current_ticket_number == 0 && ticket_number > 0 {
+        raffle_info.unique_participants += 1;
+    }
+    USER_TICKETS.save(
+        deps.storage,
+        (&owner, raffle_id),
+        &(current_ticket_number + ticket_number),
+    )?;
+    USER_PAYMENTS.update::<_, anyhow::Error>(deps.storage, (&owner, raffle_id), |x| {
+        let mut payments = x.unwrap_or_default();
+        add_funds(&mut payments, assets.clone())?;
+        Ok(payments)
     })?;
+    add_funds(&mut raffle_info.collected_funds, assets)?;
     raffle_info.number_of_tickets += ticket_number;
 
     RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
@@ -436,8 +800,8 @@ pub fn execute_update_randomness(
 /// Claim and end a raffle
 /// This function can be called by anyone
 /// This function has 4 purposes :
-/// 1. Compute the winner of a raffle (using the last provided randomness) and save it in the contract
-/// 2. Send the raffle assets to the winner
+/// 1. Compute the winners of a raffle (one per prize slot, using the last provided randomness) and save them in the contract
+/// 2. Send the assets of each prize slot to its winner
 /// 3. Send the accumulated ticket prices to the raffle owner
 /// 4. Send the fees (a cut of the accumulated ticket prices) to the treasury and the randomness provider
 pub fn execute_claim(
@@ -449,8 +813,23 @@ pub fn execute_claim(
     // Loading the raffle object
     let mut raffle_info = RAFFLE_INFO.load(deps.storage, raffle_id)?;
 
-    // We make sure the raffle is ended
+    // If the raffle didn't sell enough tickets, the assets go back to the owner (only once)
+    // The ticket buyers can then claim a refund
     let raffle_state = get_raffle_state(env.clone(), raffle_info.clone());
+    if raffle_state == RaffleState::Failed && raffle_info.winners.is_empty() {
+        raffle_info.winners = vec![raffle_info.owner.clone(); raffle_info.prizes().len()];
+        RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
+
+        let transfer_messages = get_raffle_owner_messages(env.clone(), raffle_info.clone())?;
+        let response = Response::new()
+            .add_messages(transfer_messages)
+            .add_attribute("action", "claim")
+            .add_attribute("raffle_id", raffle_id.to_string())
+            .add_attribute("raffle_state", raffle_state.to_string());
+        return _start_next_series_round(deps, env, raffle_id, &raffle_info, response);
+    }
+
+    // We make sure the raffle is ended
     if raffle_state != RaffleState::Finished {
         bail!(ContractError::WrongStateForClaim {
             status: raffle_state
@@ -459,23 +838,166 @@ pub fn execute_claim(
 
     // If there was no participant, the winner is the raffle owner and we pay no fees whatsoever
     if raffle_info.number_of_tickets == 0u32 {
-        raffle_info.winner = Some(raffle_info.owner.clone());
+        raffle_info.winners = vec![raffle_info.owner.clone(); raffle_info.prizes().len()];
     } else {
-        // We get the winner of the raffle and save it to the contract. The raffle is now claimed !
-        let winner = get_raffle_winner(deps.as_ref(), env.clone(), raffle_id, raffle_info.clone())?;
-        raffle_info.winner = Some(winner);
+        // We get the winners of the raffle and save them to the contract. The raffle is now claimed !
+        let winners =
+            get_raffle_winners(deps.as_ref(), env.clone(), raffle_id, raffle_info.clone())?;
+        raffle_info.winners = winners;
     }
     RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
 
-    // We send the assets to the winner
+    // We send the assets to the winners
     let winner_transfer_messages = get_raffle_winner_messages(env.clone(), raffle_info.clone())?;
     let funds_transfer_messages =
-        get_raffle_owner_finished_messages(deps.storage, env, raffle_info.clone())?;
+        get_raffle_owner_finished_messages(deps.storage, env.clone(), raffle_info.clone())?;
     // We distribute the ticket prices to the owner and in part to the treasury
-    Ok(Response::new()
+    let response = Response::new()
         .add_messages(winner_transfer_messages)
         .add_messages(funds_transfer_messages)
         .add_attribute("action", "claim")
         .add_attribute("raffle_id", raffle_id.to_string())
-        .add_attribute("winner", raffle_info.winner.unwrap()))
+        .add_attributes(
+            raffle_info
+                .winners
+                .iter()
+                .map(|winner| ("winner", winner.to_string())),
+        );
+
+    // The next round of a raffle series starts once the previous one is claimed
+    _start_next_series_round(deps, env, raffle_id, &raffle_info, response)
+}
+
+/// Transfer some raffle tickets to another address
+/// Tickets can only be transferred while the raffle is open
+/// The recipient has to fulfill the raffle conditions, as if they had bought the tickets
+/// The share of the payments corresponding to the tickets follows them, in case a refund is needed
+pub fn execute_transfer_tickets(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    raffle_id: u64,
+    recipient: String,
+    count: u32,
+) -> Result<Response> {
+    let mut raffle_info = load_raffle(deps.storage, raffle_id)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if count == 0 || recipient == info.sender {
+        bail!(ContractError::WrongTicketTransfer {});
+    }
+
+    // We check the raffle is in the right state
+    can_buy_ticket(env, raffle_info.clone())?;
+
+    // We check the sender has enough tickets
+    let sender_tickets = USER_TICKETS
+        .may_load(deps.storage, (&info.sender, raffle_id))?
+        .unwrap_or(0);
+    if sender_tickets < count {
+        bail!(ContractError::NotEnoughTickets {
+            owned: sender_tickets,
+            requested: count
+        });
+    }
+
+    // We check the recipient is allowed to hold those tickets
+    assert_gating_conditions(deps.as_ref(), &raffle_info, &recipient)?;
+    let recipient_tickets = USER_TICKETS
+        .may_load(deps.storage, (&recipient, raffle_id))?
+        .unwrap_or(0);
+    if let Some(max_ticket_per_address) = raffle_info.raffle_options.max_ticket_per_address {
+        if recipient_tickets + count > max_ticket_per_address {
+            bail!(ContractError::TooMuchTicketsForUser {
+                max: max_ticket_per_address,
+                nb_before: recipient_tickets,
+                nb_after: recipient_tickets + count
+            });
+        }
+    }
+
+    // Then we move the tickets, the ticket counts and the payments
+    transfer_ticket_ranges(deps.storage, raffle_id, &info.sender, &recipient, count)?;
+    USER_TICKETS.save(
+        deps.storage,
+        (&info.sender, raffle_id),
+        &(sender_tickets - count),
+    )?;
+    USER_TICKETS.save(
+        deps.storage,
+        (&recipient, raffle_id),
+        &(recipient_tickets + count),
+    )?;
+    if sender_tickets == count {
+        raffle_info.unique_participants -= 1;
+    }
+    if recipient_tickets == 0 {
+        raffle_info.unique_participants += 1;
+    }
+    RAFFLE_INFO.save(deps.storage, raffle_id, &raffle_info)?;
+
+    let mut sender_payments = USER_PAYMENTS
+        .may_load(deps.storage, (&info.sender, raffle_id))?
+        .unwrap_or_default();
+    let transferred_payments = split_funds(&mut sender_payments, count, sender_tickets)?;
+    USER_PAYMENTS.save(deps.storage, (&info.sender, raffle_id), &sender_payments)?;
+    USER_PAYMENTS.update::<_, anyhow::Error>(deps.storage, (&recipient, raffle_id), |x| {
+        let mut payments = x.unwrap_or_default();
+        for payment in transferred_payments {
+            add_funds(&mut payments, payment)?;
+        }
+        Ok(payments)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer_tickets")
+        .add_attribute("raffle_id", raffle_id.to_string())
+        .add_attribute("owner", info.sender)
+        .add_attribute("recipient", recipient)
+        .add_attribute("ticket_number", count.to_string()))
+}
+
+/// Claim a refund for the tickets bought on a failed raffle
+/// A raffle fails when less than `min_ticket_number` tickets were bought before it closed
+/// The full ticket price is refunded to the buyer, no fees are taken
+pub fn execute_claim_refund(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    raffle_id: u64,
+) -> Result<Response> {
+    let raffle_info = load_raffle(deps.storage, raffle_id)?;
+
+    let raffle_state = get_raffle_state(env, raffle_info);
+    if raffle_state != RaffleState::Failed {
+        bail!(ContractError::WrongStateForRefund {
+            status: raffle_state
+        });
+    }
+
+    // We refund all the tickets of the sender at once
+    let ticket_number = USER_TICKETS
+        .may_load(deps.storage, (&info.sender, raffle_id))?
+        .unwrap_or(0);
+    if ticket_number == 0 {
+        bail!(ContractError::NoTicketsToRefund {});
+    }
+    USER_TICKETS.save(deps.storage, (&info.sender, raffle_id), &0)?;
+
+    // The tickets are refunded in the currencies they were paid with
+    let payments = USER_PAYMENTS
+        .may_load(deps.storage, (&info.sender, raffle_id))?
+        .unwrap_or_default();
+    USER_PAYMENTS.remove(deps.storage, (&info.sender, raffle_id));
+    let refund_messages = payments
+        .into_iter()
+        .map(|payment| get_refund_messages(payment, info.sender.to_string()))
+        .collect::<Result<Vec<Vec<CosmosMsg>>>>()?
+        .concat();
+
+    Ok(Response::new()
+        .add_messages(refund_messages)
+        .add_attribute("action", "claim_refund")
+        .add_attribute("raffle_id", raffle_id.to_string())
+        .add_attribute("owner", info.sender)
+        .add_attribute("ticket_number", ticket_number.to_string()))
 }
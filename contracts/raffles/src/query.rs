@@ -5,18 +5,29 @@ use anyhow::{anyhow, Result, bail};
 use cosmwasm_std::{Api, Deps, Env, Order, StdResult, WasmQuery};
 use cosmwasm_std::QueryRequest;
 use cosmwasm_std::to_binary;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Binary, Decimal};
 
 use raffles_export::msg::AllRafflesResponse;
-use raffles_export::msg::RaffleResponse;
+use raffles_export::msg::{
+    DrawProofResponse, PrizeSlotResponse, RaffleResponse, RaffleSeriesResponse,
+    RaffleStatsResponse, RafflesAwaitingRandomnessResponse, UserOddsResponse,
+};
 
 use cw_storage_plus::Bound;
 
-use cw721::{Cw721QueryMsg, OwnerOfResponse};
+use cw20::{BalanceResponse, Cw20QueryMsg};
+use cw721::{Cw721QueryMsg, OwnerOfResponse, TokensResponse};
 use crate::error::ContractError;
-use crate::state::{get_raffle_state, load_raffle, CONTRACT_INFO, RAFFLE_INFO, USER_TICKETS, RAFFLE_TICKETS};
+use crate::rand::Prng;
+use crate::state::{
+    available_bounty, draw_tickets, earns_randomness_bounty, get_collected_funds,
+    get_raffle_state, load_raffle, load_series, split_payouts, with_amount, CONTRACT_INFO,
+    RAFFLE_INFO, RAFFLE_TICKETS, REWARD_POOL, USER_TICKETS,
+};
 use raffles_export::msg::QueryFilters;
-use raffles_export::state::{AssetInfo, ContractInfo, RaffleInfo, RaffleState};
+use raffles_export::state::{
+    AssetInfo, ContractInfo, GatingCondition, RaffleInfo, RaffleState, TicketRange,
+};
 
 // settings for pagination
 const MAX_LIMIT: u32 = 100;
@@ -36,6 +47,7 @@ fn parse_raffles(
     item.map(|(raffle_id, raffle)| RaffleResponse {
         raffle_id,
         raffle_state: get_raffle_state(env, raffle.clone()),
+        min_randomness_round: Some(raffle.min_randomness_round()),
         raffle_info: Some(raffle),
     })
 }
@@ -94,6 +106,74 @@ pub fn query_ticket_number(
     )?)
 }
 
+/// Query the ticket sales of a raffle along with the payouts they would lead to
+/// Nothing is paid out when the raffle failed or was cancelled
+pub fn query_raffle_stats(deps: Deps, env: Env, raffle_id: u64) -> Result<RaffleStatsResponse> {
+    let raffle_info = load_raffle(deps.storage, raffle_id)?;
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let collected_funds = get_collected_funds(&raffle_info)?;
+
+    let mut owner_payout = vec![];
+    let mut treasury_payout = vec![];
+    let mut randomness_payout = vec![];
+    let raffle_state = get_raffle_state(env, raffle_info.clone());
+    if raffle_state != RaffleState::Failed && raffle_state != RaffleState::Cancelled {
+        for funds in &collected_funds {
+            let (rand_amount, treasury_amount, owner_amount, _) = split_payouts(&contract_info, funds)?;
+            randomness_payout.push(with_amount(funds, rand_amount)?);
+            treasury_payout.push(with_amount(funds, treasury_amount)?);
+            owner_payout.push(with_amount(funds, owner_amount)?);
+        }
+    }
+
+    Ok(RaffleStatsResponse {
+        raffle_id,
+        unique_participants: raffle_info.unique_participants,
+        tickets_sold: raffle_info.number_of_tickets,
+        collected_funds,
+        owner_payout,
+        treasury_payout,
+        randomness_payout,
+    })
+}
+
+/// Query the tickets of a user and their probability to win at least one prize slot
+/// Each prize slot is won by a different ticket, so with `k` slots, `n` tickets and `t` tickets owned,
+/// the probability to win nothing is the product of (n - t - i) / (n - i) for i in 0..k
+pub fn query_user_odds(
+    deps: Deps,
+    _env: Env,
+    raffle_id: u64,
+    address: String,
+) -> Result<UserOddsResponse> {
+    let raffle_info = load_raffle(deps.storage, raffle_id)?;
+    let address = deps.api.addr_validate(&address)?;
+    let ticket_count = USER_TICKETS
+        .may_load(deps.storage, (&address, raffle_id))?
+        .unwrap_or(0);
+
+    let total_tickets = raffle_info.number_of_tickets;
+    let draws = (raffle_info.prizes().len() as u32).min(total_tickets);
+    let mut lose_probability = Decimal::one();
+    for i in 0..draws {
+        lose_probability *= Decimal::from_ratio(
+            total_tickets.saturating_sub(ticket_count + i),
+            total_tickets - i,
+        );
+    }
+
+    Ok(UserOddsResponse {
+        raffle_id,
+        address,
+        ticket_count,
+        win_probability: if ticket_count == 0 {
+            Decimal::zero()
+        } else {
+            Decimal::one() - lose_probability
+        },
+    })
+}
+
 /// Query all raffles using ALL filters
 pub fn query_all_raffles(
     deps: Deps,
@@ -154,6 +234,7 @@ pub fn query_all_raffles_by_depositor(
                     raffle_id,
                     raffle_state: RaffleState::Claimed,
                     raffle_info: None,
+                    min_randomness_round: None,
                 }]
             }
         }
@@ -194,6 +275,7 @@ pub fn query_all_raffles_raw(
                     raffle_id,
                     raffle_state: RaffleState::Claimed,
                     raffle_info: None,
+                    min_randomness_round: None,
                 }]
             }
         }
@@ -201,8 +283,44 @@ pub fn query_all_raffles_raw(
     Ok(AllRafflesResponse { raffles })
 }
 
+/// Query the closed raffles that don't have any randomness yet, for randomness providers to find work
+/// Raffles without tickets are left out, their randomness is not rewarded
+/// Only the BASE_LIMIT raffles after start_after are looked at
+pub fn query_raffles_awaiting_randomness(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<RafflesAwaitingRandomnessResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let raffle_ids = RAFFLE_INFO
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(BASE_LIMIT)
+        .filter(|kv_item| match kv_item {
+            Ok((_, raffle_info)) => {
+                earns_randomness_bounty(raffle_info)
+                    && get_raffle_state(env.clone(), raffle_info.clone()) == RaffleState::Closed
+            }
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|kv_item| kv_item.map(|(raffle_id, _)| raffle_id))
+        .collect::<StdResult<Vec<u64>>>()?;
+
+    Ok(RafflesAwaitingRandomnessResponse {
+        raffle_ids,
+        bounty: available_bounty(deps.storage)?,
+    })
+}
+
+pub fn query_reward_pool(deps: Deps) -> StdResult<Vec<AssetInfo>> {
+    Ok(REWARD_POOL.may_load(deps.storage)?.unwrap_or_default())
+}
+
 /// Query all ticket onwers within a raffle
-///
+/// The tickets are stored as ranges, so we start from the range containing the first queried ticket
 pub fn query_all_tickets(
     deps: Deps,
     _env: Env,
@@ -211,14 +329,86 @@ pub fn query_all_tickets(
     limit: Option<u32>,
 ) -> Result<Vec<String>> {
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(Bound::exclusive);
+    let first_ticket_id = start_after.map(|ticket_id| ticket_id + 1).unwrap_or(0);
+
+    let first_range_id = RAFFLE_TICKETS
+        .prefix(raffle_id)
+        .keys(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(first_ticket_id)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?
+        .unwrap_or(0);
 
-    RAFFLE_TICKETS
+    // Each range contains at least one ticket, so we never need more than `limit` ranges
+    let ticket_ranges = RAFFLE_TICKETS
         .prefix(raffle_id)
-        .range(deps.storage, start.clone(), None, Order::Ascending)
-        .map(|kv_item| Ok(kv_item?.1.to_string()))
+        .range(
+            deps.storage,
+            Some(Bound::inclusive(first_range_id)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<(u32, TicketRange)>>>()?;
+
+    Ok(ticket_ranges
+        .into_iter()
+        .flat_map(|(range_id, ticket_range)| {
+            (range_id..range_id + ticket_range.count)
+                .map(move |ticket_id| (ticket_id, ticket_range.owner.clone()))
+        })
+        .filter(|(ticket_id, _)| *ticket_id >= first_ticket_id)
+        .map(|(_, owner)| owner.to_string())
         .take(limit)
-        .collect()
+        .collect())
+}
+
+/// Query a raffle series, with the assets of the rounds that didn't start yet
+pub fn query_raffle_series(deps: Deps, _env: Env, series_id: u64) -> Result<RaffleSeriesResponse> {
+    Ok(RaffleSeriesResponse {
+        series_id,
+        series_info: load_series(deps.storage, series_id)?,
+    })
+}
+
+/// Query the assets of each prize slot of a raffle, along with their winner once the raffle is claimed
+pub fn query_prize_slots(deps: Deps, _env: Env, raffle_id: u64) -> Result<Vec<PrizeSlotResponse>> {
+    let raffle_info = load_raffle(deps.storage, raffle_id)?;
+
+    Ok(raffle_info
+        .prizes()
+        .into_iter()
+        .enumerate()
+        .map(|(slot, assets)| PrizeSlotResponse {
+            slot: slot as u32,
+            assets,
+            winner: raffle_info.winners.get(slot).cloned(),
+        })
+        .collect())
+}
+
+/// Query all the values needed to recompute the draw of a raffle off-chain
+/// The randomness is derived from the drand signature (sha256), the ChaCha seed is derived from the randomness (sha256)
+/// The draw uses the last randomness provided to the raffle, it's final once the raffle is Finished
+pub fn query_draw_proof(deps: Deps, env: Env, raffle_id: u64) -> Result<DrawProofResponse> {
+    let raffle_info = load_raffle(deps.storage, raffle_id)?;
+    let randomness = raffle_info
+        .randomness
+        .clone()
+        .ok_or_else(|| ContractError::NotFoundError("randomness".to_string()))?;
+
+    Ok(DrawProofResponse {
+        raffle_id,
+        randomness_round: randomness.randomness_round,
+        randomness_signature: randomness.randomness_signature,
+        randomness: Binary::from(randomness.randomness),
+        seed: Binary::from(Prng::new(&randomness.randomness).chacha_seed()),
+        draws: draw_tickets(deps.storage, env, raffle_id, raffle_info)?,
+    })
 }
 
 pub fn is_nft_owner(deps: Deps, sender: Addr, nft_address: String, token_id: String) -> Result<()>{
@@ -233,3 +423,46 @@ pub fn is_nft_owner(deps: Deps, sender: Addr, nft_address: String, token_id: Str
     }
     Ok(())
 }
+
+/// Checks a ticket buyer fulfills all the gating conditions of a raffle
+pub fn assert_gating_conditions(deps: Deps, raffle_info: &RaffleInfo, buyer: &Addr) -> Result<()> {
+    for condition in &raffle_info.raffle_options.gating {
+        let eligible = match condition {
+            GatingCondition::Cw721 { address } => {
+                let tokens_response: TokensResponse =
+                    deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                        contract_addr: address.clone(),
+                        msg: to_binary(&Cw721QueryMsg::Tokens {
+                            owner: buyer.to_string(),
+                            start_after: None,
+                            limit: Some(1),
+                        })?,
+                    }))?;
+                !tokens_response.tokens.is_empty()
+            }
+            GatingCondition::Cw20 {
+                address,
+                min_balance,
+            } => {
+                let balance_response: BalanceResponse =
+                    deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                        contract_addr: address.clone(),
+                        msg: to_binary(&Cw20QueryMsg::Balance {
+                            address: buyer.to_string(),
+                        })?,
+                    }))?;
+                balance_response.balance >= *min_balance
+            }
+            GatingCondition::Coin { denom, min_balance } => {
+                deps.querier.query_balance(buyer, denom)?.amount >= *min_balance
+            }
+            GatingCondition::Allowlist { addresses } => {
+                addresses.iter().any(|address| address == buyer.as_str())
+            }
+        };
+        if !eligible {
+            bail!(ContractError::NotEligible {});
+        }
+    }
+    Ok(())
+}
@@ -17,34 +17,32 @@ impl Prng {
         }
     }
 
-    /// Return a random number (inclusive) between `from` and `to`
-    /// This is not the best algorithm but ut's good enough
-    pub fn random_between(&mut self, from: u32, to: u32) -> u32 {
+    /// Return a random number (inclusive) between `from` and `to`, along with the raw random numbers drawn to get it
+    /// We use rejection sampling to avoid the modulo bias
+    /// (comes from https://stackoverflow.com/questions/10984974/why-do-people-say-there-is-modulo-bias-when-using-a-random-number-generator) :
+    /// the raw numbers above the last full multiple of the interval length are rejected and drawn again
+    pub fn random_between_with_draws(&mut self, from: u32, to: u32) -> (u32, Vec<u32>) {
         if from > to {
-            return 0;
+            return (0, vec![]);
         }
-        let x = self.rand_u32();
-        let interval_length = to - from + 1;
+        let interval_length = u64::from(to - from) + 1;
+        let range = u64::from(u32::MAX) + 1;
+        let limit = range - range % interval_length;
 
-        // The algorithm as it is now is not perfect, it can become biased for a large number of bought tickets
-        // We don't think a large number of tickets will be purchased (compared to u32::max_value)
-        // To make the algorithm better, we could do somehting along the lines of :
-        // (comes from https://stackoverflow.com/questions/10984974/why-do-people-say-there-is-modulo-bias-when-using-a-random-number-generator)
-        /*
-            let u32_max = u32::max_value();
-            let limit = u32_max - u32_max % interval_length;
-            let x = loop {
-                let x = self.rand_u32();
-                if x < limit{
-                    break x;
-                }
-            };
-        */
+        let mut draws = vec![];
+        let x = loop {
+            let x = self.rand_u32();
+            draws.push(x);
+            if u64::from(x) < limit {
+                break x;
+            }
+        };
 
-        from + (x % interval_length)
+        (from + (u64::from(x) % interval_length) as u32, draws)
     }
 
-    fn rand_u32(&mut self) -> u32 {
+    /// The ChaCha seed, derived from the randomness used to initialize the generator
+    pub fn chacha_seed(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
 
         // write input message
@@ -53,8 +51,11 @@ impl Prng {
 
         let mut result = [0u8; 32];
         result.copy_from_slice(hash.as_slice());
+        result
+    }
 
-        let mut rng: ChaChaRng = ChaChaRng::from_seed(result);
+    fn rand_u32(&mut self) -> u32 {
+        let mut rng: ChaChaRng = ChaChaRng::from_seed(self.chacha_seed());
 
         rng.set_word_pos(self.pos);
         self.pos += 8;
@@ -1,15 +1,22 @@
 use anyhow::{anyhow, bail, Result};
 use cosmwasm_std::Coin;
-use cw_storage_plus::{Item, Map};
+use std::collections::HashMap;
+use cw_storage_plus::{Bound, Item, Map};
 
 use cosmwasm_std::{
-    coins, Addr, BankMsg, CosmosMsg, Deps, Env, Response, Storage, SubMsg, Uint128,
+    coins, Addr, Api, BankMsg, CosmosMsg, Deps, Env, Order, Response, StdResult, Storage, SubMsg,
+    Uint128,
 };
 
 use crate::error::ContractError;
 use crate::rand::Prng;
-use raffles_export::msg::{into_cosmos_msg, DrandRandomness, VerifierExecuteMsg};
-use raffles_export::state::{AssetInfo, ContractInfo, Cw20Coin, RaffleInfo, RaffleState};
+use raffles_export::msg::{
+    into_cosmos_msg, DrandRandomness, TicketDraw, VerifierExecuteMsg, VerifierQueryMsg,
+};
+use raffles_export::state::{
+    first_round_after, AssetInfo, BeaconNetwork, ContractInfo, Cw20Coin, GatingCondition,
+    RaffleInfo, RaffleSeries, RaffleState, TicketRange,
+};
 
 use cw1155::Cw1155ExecuteMsg;
 use cw20::Cw20ExecuteMsg;
@@ -17,8 +24,15 @@ use cw721::Cw721ExecuteMsg;
 
 pub const CONTRACT_INFO: Item<ContractInfo> = Item::new("contract_info");
 pub const RAFFLE_INFO: Map<u64, RaffleInfo> = Map::new("raffle_info");
-pub const RAFFLE_TICKETS: Map<(u64, u32), Addr> = Map::new("raffle_tickets");
+/// Ticket ranges of a raffle, indexed by the first ticket id of the range
+pub const RAFFLE_TICKETS: Map<(u64, u32), TicketRange> = Map::new("raffle_ticket_ranges");
+/// Tickets used to be saved one entry per ticket, they are moved to `RAFFLE_TICKETS` on migration
+pub const LEGACY_RAFFLE_TICKETS: Map<(u64, u32), Addr> = Map::new("raffle_tickets");
 pub const USER_TICKETS: Map<(&Addr, u64), u32> = Map::new("user_tickets");
+pub const RAFFLE_SERIES: Map<u64, RaffleSeries> = Map::new("raffle_series");
+/// Funds paid by a user for the tickets of a raffle, one entry per currency
+pub const USER_PAYMENTS: Map<(&Addr, u64), Vec<AssetInfo>> = Map::new("user_payments");
+pub const REWARD_POOL: Item<Vec<AssetInfo>> = Item::new("reward_pool");
 
 /// This function is largely inspired (and even directly copied) from https://github.com/LoTerra/terrand-contract-step1/
 /// This function actually simply calls an external contract that checks the randomness origin
@@ -35,7 +49,19 @@ pub fn assert_randomness_origin_and_order(
     let raffle_info = load_raffle(deps.storage, raffle_id)?;
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
-    if let Some(local_randomness) = raffle_info.randomness {
+    // The timing of the network is read from the verifier registry, through the chain hash the raffle is bound to
+    let beacon: BeaconNetwork = deps.querier.query_wasm_smart(
+        contract_info.verify_signature_contract.clone(),
+        &VerifierQueryMsg::Beacon {
+            chain_hash: raffle_info.drand_chain_hash.clone(),
+        },
+    )?;
+    let min_round = first_round_after(beacon.genesis_time, beacon.period, raffle_info.close_timestamp());
+    if randomness.round < min_round {
+        bail!(ContractError::RandomnessRoundTooEarly { min_round });
+    }
+
+    if let Some(local_randomness) = raffle_info.randomness.clone() {
         if randomness.round <= local_randomness.randomness_round {
             bail!(ContractError::RandomnessNotAccepted {
                 current_round: local_randomness.randomness_round
@@ -45,7 +71,7 @@ pub fn assert_randomness_origin_and_order(
 
     let msg = VerifierExecuteMsg::Verify {
         randomness,
-        pubkey: contract_info.random_pubkey,
+        chain_hash: raffle_info.drand_chain_hash,
         raffle_id,
         owner: owner.to_string(),
     };
@@ -77,30 +103,218 @@ pub fn is_raffle_owner(
     }
 }
 
-/// Picking the winner of the raffle
+/// Picking the winners of the raffle
 /// This function was inspired by https://github.com/scrtlabs/secret-raffle/
-/// We know the odds are not exactly perfect with this architecture
-/// --> that's not how you select a true random number from an interval, but n/4_294_967_295 will stay quite small anyway
-/// (with n the number of bought tickets)
-pub fn get_raffle_winner(
+/// If there are less tickets than prize slots, the remaining prizes go back to the raffle owner
+pub fn get_raffle_winners(
     deps: Deps,
     env: Env,
     raffle_id: u64,
     raffle_info: RaffleInfo,
-) -> Result<Addr> {
+) -> Result<Vec<Addr>> {
+    let slot_number = raffle_info.prizes().len();
+    let mut winners: Vec<Addr> = draw_tickets(deps.storage, env, raffle_id, raffle_info.clone())?
+        .into_iter()
+        .map(|draw| draw.winner)
+        .collect();
+    winners.resize(slot_number, raffle_info.owner);
+
+    Ok(winners)
+}
+
+/// Draws one ticket for each prize slot, without replacement (a ticket can only win once).
+/// We use a partial Fisher-Yates shuffle over the ticket ids, only storing the swapped ids :
+/// For slot n, an index is drawn between 0 and `number_of_tickets - 1 - n`.
+/// The ticket at this index wins and is replaced by the last ticket that can still be drawn.
+/// All the intermediate values are returned, so that anyone can recompute the draw
+pub fn draw_tickets(
+    storage: &dyn Storage,
+    env: Env,
+    raffle_id: u64,
+    raffle_info: RaffleInfo,
+) -> Result<Vec<TicketDraw>> {
     // We initiate the random number generator
     if raffle_info.randomness.is_none() {
         bail!(ContractError::WrongStateForClaim {
             status: get_raffle_state(env, raffle_info)
         });
     }
-    let mut rng: Prng = Prng::new(&raffle_info.randomness.unwrap().randomness);
+    let mut rng: Prng = Prng::new(&raffle_info.randomness.clone().unwrap().randomness);
+
+    let draw_number = (raffle_info.prizes().len() as u32).min(raffle_info.number_of_tickets);
+    let mut swapped_ids: HashMap<u32, u32> = HashMap::new();
+    (0..draw_number)
+        .map(|slot| {
+            // We pick a winner id among the tickets that didn't win yet
+            let last_id = raffle_info.number_of_tickets - 1 - slot;
+            let (drawn_index, random_values) = rng.random_between_with_draws(0u32, last_id);
+            let ticket_id = *swapped_ids.get(&drawn_index).unwrap_or(&drawn_index);
+            swapped_ids.insert(drawn_index, *swapped_ids.get(&last_id).unwrap_or(&last_id));
+
+            Ok(TicketDraw {
+                slot,
+                random_values,
+                drawn_index,
+                ticket_id,
+                winner: get_ticket_owner(storage, raffle_id, ticket_id)?,
+            })
+        })
+        .collect()
+}
+
+/// Finds the owner of a ticket
+/// The range containing the ticket is the last one starting at or before the ticket id.
+/// The ranges are sorted by first ticket id in storage, so this is a binary search and not a scan over all purchases
+pub fn get_ticket_owner(storage: &dyn Storage, raffle_id: u64, ticket_id: u32) -> Result<Addr> {
+    let ticket_range = RAFFLE_TICKETS
+        .prefix(raffle_id)
+        .range(storage, None, Some(Bound::inclusive(ticket_id)), Order::Descending)
+        .next()
+        .transpose()?;
+
+    match ticket_range {
+        Some((first_id, ticket_range)) if ticket_id < first_id + ticket_range.count => {
+            Ok(ticket_range.owner)
+        }
+        _ => bail!(ContractError::NotFoundError(format!("ticket {}", ticket_id))),
+    }
+}
+
+/// Moves the tickets saved one entry per ticket into ticket ranges
+/// Consecutive tickets of the same owner are merged into a single range
+pub fn migrate_legacy_tickets(storage: &mut dyn Storage) -> Result<()> {
+    let legacy_tickets = LEGACY_RAFFLE_TICKETS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<((u64, u32), Addr)>>>()?;
+
+    let mut ranges: Vec<((u64, u32), TicketRange)> = vec![];
+    for ((raffle_id, ticket_id), owner) in legacy_tickets {
+        LEGACY_RAFFLE_TICKETS.remove(storage, (raffle_id, ticket_id));
+        match ranges.last_mut() {
+            Some(((last_raffle_id, first_id), range))
+                if *last_raffle_id == raffle_id
+                    && *first_id + range.count == ticket_id
+                    && range.owner == owner =>
+            {
+                range.count += 1
+            }
+            _ => ranges.push(((raffle_id, ticket_id), TicketRange { owner, count: 1 })),
+        }
+    }
+    for (key, range) in ranges {
+        RAFFLE_TICKETS.save(storage, key, &range)?;
+    }
+    Ok(())
+}
+
+/// Gives the last `count` tickets of `from` in a raffle to `to`
+/// Ticket ranges are split when only part of a range changes owner
+/// The caller has to make sure `from` owns at least `count` tickets
+pub fn transfer_ticket_ranges(
+    storage: &mut dyn Storage,
+    raffle_id: u64,
+    from: &Addr,
+    to: &Addr,
+    count: u32,
+) -> Result<()> {
+    let mut transferred_ranges: Vec<(u32, TicketRange)> = vec![];
+    let mut remaining = count;
+    for range in RAFFLE_TICKETS
+        .prefix(raffle_id)
+        .range(storage, None, None, Order::Descending)
+    {
+        let (first_id, ticket_range) = range?;
+        if ticket_range.owner != *from {
+            continue;
+        }
+        transferred_ranges.push((first_id, ticket_range.clone()));
+        if ticket_range.count >= remaining {
+            break;
+        }
+        remaining -= ticket_range.count;
+    }
+
+    let mut remaining = count;
+    for (first_id, ticket_range) in transferred_ranges {
+        let transferred = ticket_range.count.min(remaining);
+        let kept = ticket_range.count - transferred;
+        if kept > 0 {
+            RAFFLE_TICKETS.save(
+                storage,
+                (raffle_id, first_id),
+                &TicketRange {
+                    owner: from.clone(),
+                    count: kept,
+                },
+            )?;
+        }
+        RAFFLE_TICKETS.save(
+            storage,
+            (raffle_id, first_id + kept),
+            &TicketRange {
+                owner: to.clone(),
+                count: transferred,
+            },
+        )?;
+        remaining -= transferred;
+    }
+    Ok(())
+}
+
+/// Checks the options of a raffle are consistent
+/// Each asset has to be won in exactly one prize slot and no slot can be empty
+/// The minimum ticket number can't be higher than the maximum ticket number
+/// The addresses used in the gating conditions have to be valid
+/// Ticket prices have to be fungible tokens, with at most one price per currency
+pub fn assert_valid_raffle_options(api: &dyn Api, raffle_info: &RaffleInfo) -> Result<()> {
+    let ticket_prices = raffle_info.ticket_prices();
+    for (i, price) in ticket_prices.iter().enumerate() {
+        match price {
+            AssetInfo::Coin(_) | AssetInfo::Cw20Coin(_) => {}
+            _ => bail!(ContractError::WrongFundsType {}),
+        }
+        if ticket_prices[..i]
+            .iter()
+            .any(|other| other.is_same_currency(price))
+        {
+            bail!(ContractError::DuplicateTicketCurrency {});
+        }
+    }
+
+    let raffle_options = &raffle_info.raffle_options;
+    for condition in &raffle_options.gating {
+        match condition {
+            GatingCondition::Cw721 { address } | GatingCondition::Cw20 { address, .. } => {
+                api.addr_validate(address)?;
+            }
+            GatingCondition::Allowlist { addresses } => {
+                for address in addresses {
+                    api.addr_validate(address)?;
+                }
+            }
+            GatingCondition::Coin { .. } => {}
+        }
+    }
+    if let (Some(min_ticket_number), Some(max_participant_number)) = (
+        raffle_options.min_ticket_number,
+        raffle_options.max_participant_number,
+    ) {
+        if min_ticket_number > max_participant_number {
+            bail!(ContractError::WrongTicketThreshold {});
+        }
+    }
 
-    // We pick a winner id
-    let winner_id = rng.random_between(0u32, raffle_info.number_of_tickets - 1);
-    let winner = RAFFLE_TICKETS.load(deps.storage, (raffle_id, winner_id))?;
+    let prize_slots = &raffle_info.raffle_options.prize_slots;
+    let mut asset_indexes: Vec<u32> = prize_slots.iter().flatten().cloned().collect();
+    asset_indexes.sort_unstable();
 
-    Ok(winner)
+    if prize_slots.is_empty()
+        || prize_slots.iter().any(|slot| slot.is_empty())
+        || asset_indexes != (0..raffle_info.assets.len() as u32).collect::<Vec<u32>>()
+    {
+        bail!(ContractError::WrongPrizeSlots {});
+    }
+    Ok(())
 }
 
 /// Queries the raffle state
@@ -119,6 +333,10 @@ pub fn get_raffle_state(env: Env, raffle_info: RaffleInfo) -> RaffleState {
             .plus_seconds(raffle_info.raffle_options.raffle_duration)
     {
         RaffleState::Started
+    } else if raffle_info.number_of_tickets
+        < raffle_info.raffle_options.min_ticket_number.unwrap_or(0)
+    {
+        RaffleState::Failed
     } else if env.block.time
         < raffle_info
             .raffle_options
@@ -128,7 +346,7 @@ pub fn get_raffle_state(env: Env, raffle_info: RaffleInfo) -> RaffleState {
         || raffle_info.randomness.is_none()
     {
         RaffleState::Closed
-    } else if raffle_info.winner.is_none() {
+    } else if raffle_info.winners.is_empty() {
         RaffleState::Finished
     } else {
         RaffleState::Claimed
@@ -141,6 +359,12 @@ pub fn load_raffle(storage: &dyn Storage, raffle_id: u64) -> Result<RaffleInfo>
         .map_err(|_| anyhow!(ContractError::NotFoundInRaffleInfo {}))
 }
 
+pub fn load_series(storage: &dyn Storage, series_id: u64) -> Result<RaffleSeries> {
+    RAFFLE_SERIES
+        .load(storage, series_id)
+        .map_err(|_| anyhow!(ContractError::NotFoundError(format!("raffle series {}", series_id))))
+}
+
 /// Can only buy a ticket when the raffle has started and is not closed
 pub fn can_buy_ticket(env: Env, raffle_info: RaffleInfo) -> Result<()> {
     if get_raffle_state(env, raffle_info) == RaffleState::Started {
@@ -150,9 +374,9 @@ pub fn can_buy_ticket(env: Env, raffle_info: RaffleInfo) -> Result<()> {
     }
 }
 
-/// Computes the ticket cost for multiple tickets bought together
-pub fn ticket_cost(raffle_info: RaffleInfo, ticket_number: u32) -> Result<AssetInfo> {
-    Ok(match raffle_info.raffle_ticket_price {
+/// Computes the ticket cost for multiple tickets bought together at the given ticket price
+pub fn ticket_cost(ticket_price: AssetInfo, ticket_number: u32) -> Result<AssetInfo> {
+    Ok(match ticket_price {
         AssetInfo::Coin(x) => AssetInfo::Coin(Coin {
             denom: x.denom,
             amount: Uint128::from(ticket_number) * x.amount,
@@ -165,26 +389,36 @@ pub fn ticket_cost(raffle_info: RaffleInfo, ticket_number: u32) -> Result<AssetI
     })
 }
 
-/// Util to get the winner messages to return when claiming a Raffle (returns the raffled asset)
+/// Util to get the winner messages to return when claiming a Raffle (returns the raffled assets of each prize slot)
 pub fn get_raffle_winner_messages(env: Env, raffle_info: RaffleInfo) -> Result<Vec<CosmosMsg>> {
-    let winner: Addr = raffle_info.winner.clone().unwrap();
-    _get_raffle_end_asset_messages(env, raffle_info, winner.to_string())
+    Ok(raffle_info
+        .prizes()
+        .iter()
+        .zip(raffle_info.winners.iter())
+        .map(|(assets, winner)| _get_raffle_end_asset_messages(env.clone(), assets, winner.to_string()))
+        .collect::<Result<Vec<Vec<CosmosMsg>>>>()?
+        .concat())
 }
 
 /// Util to get the raffle creator messages to return when the Raffle is cancelled (returns the raffled asset)
 pub fn get_raffle_owner_messages(env: Env, raffle_info: RaffleInfo) -> Result<Vec<CosmosMsg>> {
     let owner: Addr = raffle_info.owner.clone();
-    _get_raffle_end_asset_messages(env, raffle_info, owner.to_string())
+    _get_raffle_end_asset_messages(env, &raffle_info.assets, owner.to_string())
+}
+
+/// Util to get the assets of the rounds that didn't start back to the series owner
+pub fn get_series_owner_messages(env: Env, series: &RaffleSeries) -> Result<Vec<CosmosMsg>> {
+    let assets: Vec<AssetInfo> = series.remaining_rounds.concat();
+    _get_raffle_end_asset_messages(env, &assets, series.owner.to_string())
 }
 
 /// Util to get the assets back from a raffle
 fn _get_raffle_end_asset_messages(
     env: Env,
-    raffle_info: RaffleInfo,
+    assets: &[AssetInfo],
     receiver: String,
 ) -> Result<Vec<CosmosMsg>> {
-    raffle_info
-        .assets
+    assets
         .iter()
         .map(|asset| match asset {
             AssetInfo::Cw721Coin(nft) => {
@@ -209,32 +443,238 @@ fn _get_raffle_end_asset_messages(
         .collect()
 }
 
+/// Returns the same funds with a different amount
+pub fn with_amount(funds: &AssetInfo, amount: Uint128) -> Result<AssetInfo> {
+    match funds {
+        AssetInfo::Coin(coin) => Ok(AssetInfo::coin_raw(amount, &coin.denom)),
+        AssetInfo::Cw20Coin(coin) => Ok(AssetInfo::cw20_raw(amount, &coin.address)),
+        _ => bail!(ContractError::WrongFundsType {}),
+    }
+}
+
+/// Adds funds to a list of funds, merging them with the funds of the same currency
+pub fn add_funds(funds: &mut Vec<AssetInfo>, new_funds: AssetInfo) -> Result<()> {
+    match (
+        funds.iter_mut().find(|x| x.is_same_currency(&new_funds)),
+        new_funds,
+    ) {
+        (Some(AssetInfo::Coin(coin)), AssetInfo::Coin(new_coin)) => coin.amount += new_coin.amount,
+        (Some(AssetInfo::Cw20Coin(coin)), AssetInfo::Cw20Coin(new_coin)) => {
+            coin.amount += new_coin.amount
+        }
+        (None, new_funds @ (AssetInfo::Coin(_) | AssetInfo::Cw20Coin(_))) => funds.push(new_funds),
+        _ => bail!(ContractError::WrongFundsType {}),
+    }
+    Ok(())
+}
+
+/// Removes the share `part / total` of each of the funds and returns it
+pub fn split_funds(funds: &mut [AssetInfo], part: u32, total: u32) -> Result<Vec<AssetInfo>> {
+    funds
+        .iter_mut()
+        .map(|fund| {
+            Ok(match fund {
+                AssetInfo::Coin(coin) => {
+                    let share = coin.amount.multiply_ratio(part, total);
+                    coin.amount -= share;
+                    AssetInfo::coin_raw(share, &coin.denom)
+                }
+                AssetInfo::Cw20Coin(coin) => {
+                    let share = coin.amount.multiply_ratio(part, total);
+                    coin.amount -= share;
+                    AssetInfo::cw20_raw(share, &coin.address)
+                }
+                _ => bail!(ContractError::WrongFundsType {}),
+            })
+        })
+        .collect()
+}
+
+/// Util to get the refund messages of a ticket buyer when a raffle failed (returns the ticket prices)
+pub fn get_refund_messages(refund: AssetInfo, receiver: String) -> Result<Vec<CosmosMsg>> {
+    match refund {
+        AssetInfo::Cw20Coin(coin) if coin.amount != Uint128::zero() => Ok(vec![into_cosmos_msg(
+            Cw20ExecuteMsg::Transfer {
+                recipient: receiver,
+                amount: coin.amount,
+            },
+            coin.address,
+        )?]),
+        AssetInfo::Coin(coin) if coin.amount != Uint128::zero() => Ok(vec![BankMsg::Send {
+            to_address: receiver,
+            amount: vec![coin],
+        }
+        .into()]),
+        AssetInfo::Cw20Coin(_) | AssetInfo::Coin(_) => Ok(vec![]),
+        _ => Err(anyhow!(ContractError::WrongFundsType {})),
+    }
+}
+
 /// Util to get the organizers and helpers messages to return when claiming a Raffle (returns the funds)
+/// The funds collected in each ticket currency are split separately
 pub fn get_raffle_owner_finished_messages(
-    storage: &dyn Storage,
+    storage: &mut dyn Storage,
     _env: Env,
     raffle_info: RaffleInfo,
 ) -> Result<Vec<CosmosMsg>> {
     let contract_info = CONTRACT_INFO.load(storage)?;
 
-    // We start by splitting the fees between owner, treasury and radomness provider
-    let total_paid = match raffle_info.raffle_ticket_price.clone() {
+    Ok(get_collected_funds(&raffle_info)?
+        .into_iter()
+        .map(|funds| {
+            let (_, _, _, pool_amount) = split_payouts(&contract_info, &funds)?;
+            top_up_reward_pool(storage, &funds, pool_amount)?;
+            _get_fee_split_messages(&contract_info, &raffle_info, funds)
+        })
+        .collect::<Result<Vec<Vec<CosmosMsg>>>>()?
+        .concat())
+}
+
+/// Adds the part of the treasury fee kept for randomness bounties to the reward pool
+fn top_up_reward_pool(storage: &mut dyn Storage, funds: &AssetInfo, pool_amount: Uint128) -> Result<()> {
+    if !pool_amount.is_zero() {
+        let mut reward_pool = REWARD_POOL.may_load(storage)?.unwrap_or_default();
+        add_funds(&mut reward_pool, with_amount(funds, pool_amount)?)?;
+        REWARD_POOL.save(storage, &reward_pool)?;
+    }
+    Ok(())
+}
+
+/// Returns the randomness bounty if the reward pool holds enough to pay it
+pub fn available_bounty(storage: &dyn Storage) -> Result<Option<AssetInfo>> {
+    let contract_info = CONTRACT_INFO.load(storage)?;
+    let reward_pool = REWARD_POOL.may_load(storage)?.unwrap_or_default();
+
+    Ok(contract_info.randomness_bounty.filter(|bounty| {
+        reward_pool
+            .iter()
+            .any(|funds| funds.is_same_currency(bounty) && asset_amount(funds) >= asset_amount(bounty))
+    }))
+}
+
+/// Only the first randomness of a raffle that sold tickets is rewarded
+/// Raffles without tickets could otherwise be created in a loop to drain the reward pool
+pub fn earns_randomness_bounty(raffle_info: &RaffleInfo) -> bool {
+    raffle_info.randomness.is_none() && raffle_info.number_of_tickets > 0
+}
+
+/// The treasury fee a raffle collected in the currency of the bounty
+fn treasury_fee_in(
+    contract_info: &ContractInfo,
+    raffle_info: &RaffleInfo,
+    bounty: &AssetInfo,
+) -> Result<Uint128> {
+    get_collected_funds(raffle_info)?
+        .iter()
+        .filter(|funds| funds.is_same_currency(bounty))
+        .try_fold(Uint128::zero(), |total, funds| {
+            let (_, treasury_amount, _) = split_fees(contract_info, funds)?;
+            Ok(total + treasury_amount)
+        })
+}
+
+/// Takes the randomness bounty out of the reward pool and sends it to the randomness provider
+/// Nothing is paid if the raffle doesn't earn a bounty or if the pool can't cover it
+/// The bounty is capped at the treasury fee the raffle collected, so that buying a ticket at a dust price doesn't pay off
+pub fn pay_randomness_bounty(
+    storage: &mut dyn Storage,
+    raffle_info: &RaffleInfo,
+    provider: &Addr,
+) -> Result<Vec<CosmosMsg>> {
+    if !earns_randomness_bounty(raffle_info) {
+        return Ok(vec![]);
+    }
+    let bounty = match available_bounty(storage)? {
+        Some(bounty) => bounty,
+        None => return Ok(vec![]),
+    };
+    let contract_info = CONTRACT_INFO.load(storage)?;
+    let treasury_fee = treasury_fee_in(&contract_info, raffle_info, &bounty)?;
+    if treasury_fee.is_zero() {
+        return Ok(vec![]);
+    }
+    let bounty = with_amount(&bounty, asset_amount(&bounty).min(treasury_fee))?;
+    let mut reward_pool = REWARD_POOL.load(storage)?;
+    for funds in reward_pool.iter_mut() {
+        if funds.is_same_currency(&bounty) {
+            *funds = with_amount(funds, asset_amount(funds) - asset_amount(&bounty))?;
+        }
+    }
+    REWARD_POOL.save(storage, &reward_pool)?;
+
+    get_refund_messages(bounty, provider.to_string())
+}
+
+fn asset_amount(funds: &AssetInfo) -> Uint128 {
+    match funds {
+        AssetInfo::Coin(coin) => coin.amount,
+        AssetInfo::Cw20Coin(coin) => coin.amount,
+        _ => Uint128::zero(),
+    }
+}
+
+pub fn get_collected_funds(raffle_info: &RaffleInfo) -> Result<Vec<AssetInfo>> {
+    // Raffles created before multiple currencies were accepted only track their ticket number
+    if raffle_info.collected_funds.is_empty() {
+        Ok(vec![ticket_cost(
+            raffle_info.raffle_ticket_price.clone(),
+            raffle_info.number_of_tickets,
+        )?])
+    } else {
+        Ok(raffle_info.collected_funds.clone())
+    }
+}
+
+/// Splits the funds paid for tickets between the randomness provider, the treasury and the raffle owner (in that order)
+pub fn split_fees(
+    contract_info: &ContractInfo,
+    total_paid: &AssetInfo,
+) -> Result<(Uint128, Uint128, Uint128)> {
+    let total_paid_amount = match total_paid {
         AssetInfo::Cw20Coin(coin) => coin.amount,
         AssetInfo::Coin(coin) => coin.amount,
         _ => return Err(anyhow!(ContractError::WrongFundsType {})),
-    } * Uint128::from(raffle_info.number_of_tickets);
-    let rand_amount = total_paid * contract_info.rand_fee;
-    let treasury_amount = total_paid * contract_info.raffle_fee;
-    let owner_amount = total_paid - rand_amount - treasury_amount;
+    };
+    let rand_amount = total_paid_amount * contract_info.rand_fee;
+    let treasury_amount = total_paid_amount * contract_info.raffle_fee;
+    let owner_amount = total_paid_amount - rand_amount - treasury_amount;
+    Ok((rand_amount, treasury_amount, owner_amount))
+}
+
+/// Splits the funds paid for tickets like `split_fees`, with part of the treasury fee kept in the reward pool
+/// That part is only kept when the funds are paid in the bounty currency
+/// Returns the randomness provider, treasury, raffle owner and reward pool amounts (in that order)
+pub fn split_payouts(
+    contract_info: &ContractInfo,
+    total_paid: &AssetInfo,
+) -> Result<(Uint128, Uint128, Uint128, Uint128)> {
+    let (rand_amount, treasury_amount, owner_amount) = split_fees(contract_info, total_paid)?;
+    let pool_amount = match &contract_info.randomness_bounty {
+        Some(bounty) if bounty.is_same_currency(total_paid) => {
+            treasury_amount * contract_info.reward_pool_fee
+        }
+        _ => Uint128::zero(),
+    };
+    Ok((rand_amount, treasury_amount - pool_amount, owner_amount, pool_amount))
+}
+
+fn _get_fee_split_messages(
+    contract_info: &ContractInfo,
+    raffle_info: &RaffleInfo,
+    total_paid: AssetInfo,
+) -> Result<Vec<CosmosMsg>> {
+    // We start by splitting the fees between owner, treasury and radomness provider
+    // The part of the treasury fee kept in the reward pool doesn't leave the contract
+    let (rand_amount, treasury_amount, owner_amount, _) = split_payouts(contract_info, &total_paid)?;
 
     // Then we craft the messages needed for asset transfers
-    match raffle_info.raffle_ticket_price {
+    match total_paid {
         AssetInfo::Cw20Coin(coin) => {
             let mut messages: Vec<CosmosMsg> = vec![];
             if rand_amount != Uint128::zero() {
                 messages.push(into_cosmos_msg(
                     Cw20ExecuteMsg::Transfer {
-                        recipient: raffle_info.randomness.unwrap().randomness_owner.to_string(),
+                        recipient: raffle_info.randomness.clone().unwrap().randomness_owner.to_string(),
                         amount: rand_amount,
                     },
                     coin.address.clone(),
@@ -265,7 +705,7 @@ pub fn get_raffle_owner_finished_messages(
             if rand_amount != Uint128::zero() {
                 messages.push(
                     BankMsg::Send {
-                        to_address: raffle_info.randomness.unwrap().randomness_owner.to_string(),
+                        to_address: raffle_info.randomness.clone().unwrap().randomness_owner.to_string(),
                         amount: coins(rand_amount.u128(), coin.denom.clone()),
                     }
                     .into(),
@@ -1,12 +1,17 @@
 use cosmwasm_std::Empty;
-use cw721::{Cw721QueryMsg, OwnerOfResponse};
+use cw20::{BalanceResponse, Cw20QueryMsg};
+use cw721::{Cw721QueryMsg, OwnerOfResponse, TokensResponse};
+use raffles_export::msg::VerifierQueryMsg;
+use raffles_export::state::{
+    BeaconNetwork, DrandScheme, DRAND_MAINNET_GENESIS_TIME, DRAND_MAINNET_PERIOD,
+};
 
 
 use std::marker::PhantomData;
 
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_binary, from_slice, to_binary, Coin, ContractResult,
+    from_binary, from_slice, to_binary, Binary, Coin, ContractResult, Timestamp, Uint128,
     OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError, SystemResult, WasmQuery,
 };
 use std::collections::HashMap;
@@ -29,7 +34,11 @@ pub fn mock_querier_dependencies(
 
 pub struct WasmMockQuerier {
     base: MockQuerier<Empty>,
-    owner_of_querier: OwnerOfQuerier
+    owner_of_querier: OwnerOfQuerier,
+    // We link a "cw20_address - owner" key to a balance
+    cw20_balances: HashMap<String, Uint128>,
+    // The genesis time and period of every beacon network
+    beacon_timing: (Timestamp, u64),
 }
 
 #[derive(Clone, Default)]
@@ -77,6 +86,25 @@ impl WasmMockQuerier {
     pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
         match &request {
             QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                if let Ok(VerifierQueryMsg::Beacon { chain_hash }) = from_binary(msg) {
+                    return SystemResult::Ok(ContractResult::from(to_binary(&BeaconNetwork {
+                        chain_hash,
+                        scheme: DrandScheme::PedersenBlsChained,
+                        public_key: Binary::default(),
+                        genesis_time: self.beacon_timing.0,
+                        period: self.beacon_timing.1,
+                    })));
+                }
+                if let Ok(Cw20QueryMsg::Balance { address }) = from_binary(msg) {
+                    let balance = self
+                        .cw20_balances
+                        .get(&format!("{} - {}", contract_addr, address))
+                        .cloned()
+                        .unwrap_or_default();
+                    return SystemResult::Ok(ContractResult::from(to_binary(&BalanceResponse {
+                        balance,
+                    })));
+                }
                 match from_binary(msg).unwrap() {
                     Cw721QueryMsg::OwnerOf {
                         token_id,
@@ -92,6 +120,21 @@ impl WasmMockQuerier {
                             }),
                         }
                     },
+                    Cw721QueryMsg::Tokens { owner, .. } => {
+                        let tokens = self
+                            .owner_of_querier
+                            .owner_of
+                            .iter()
+                            .filter(|(key, token_owner)| {
+                                key.starts_with(&format!("{} - ", contract_addr))
+                                    && **token_owner == owner
+                            })
+                            .map(|(key, _)| key.clone())
+                            .collect();
+                        SystemResult::Ok(ContractResult::from(to_binary(&TokensResponse {
+                            tokens,
+                        })))
+                    }
                     _ => SystemResult::Err(SystemError::InvalidRequest {
                         error: "UnImplemented in tests".to_string(),
                         request: msg.as_slice().into(),
@@ -108,6 +151,11 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             owner_of_querier: OwnerOfQuerier::default(),
+            cw20_balances: HashMap::new(),
+            beacon_timing: (
+                Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                DRAND_MAINNET_PERIOD,
+            ),
         }
     }
 
@@ -115,4 +163,19 @@ impl WasmMockQuerier {
         self.owner_of_querier = OwnerOfQuerier::new(owner_of);
     }
 
+    pub fn with_cw20_balance(&mut self, cw20_address: &str, owner: &str, balance: u128) {
+        self.cw20_balances.insert(
+            format!("{} - {}", cw20_address, owner),
+            Uint128::from(balance),
+        );
+    }
+
+    pub fn with_beacon_timing(&mut self, genesis_time: Timestamp, period: u64) {
+        self.beacon_timing = (genesis_time, period);
+    }
+
+    pub fn with_native_balance(&mut self, owner: &str, balance: Vec<Coin>) {
+        self.base.update_balance(owner, balance);
+    }
+
 }
@@ -1,37 +1,39 @@
 use std::str::FromStr;
 use anyhow::Result;
-extern crate rustc_serialize as serialize;
-use serialize::base64::{self, ToBase64};
-use serialize::hex::FromHex;
 
 use cosmwasm_std::{
-    coin, coins, from_binary,
+    attr, coin, coins, from_binary, from_slice, to_binary, to_vec,
     testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR},
-    Api, BankMsg, Binary, Coin, DepsMut, Event, Response, SubMsg, SubMsgResponse, SubMsgResult,
-    Uint128, Decimal
+    Addr, Api, BankMsg, Binary, Coin, Deps, DepsMut, Env, Event, Response, StdError, SubMsg, SubMsgResponse, SubMsgResult,
+    Timestamp, Uint128, Decimal, Order, StdResult
 };
 use utils::state::OwnerStruct;
 
 use raffles_export::msg::{
-    into_cosmos_msg, AllRafflesResponse, DrandRandomness, ExecuteMsg, InstantiateMsg, QueryFilters,
-    QueryMsg, RaffleResponse, VerifierExecuteMsg,
+    into_cosmos_msg, AllRafflesResponse, DrandRandomness, DrawProofResponse, ExecuteMsg,
+    InstantiateMsg, MigrateMsg, PrizeSlotResponse, QueryFilters, QueryMsg, RaffleResponse,
+    RaffleSeriesResponse, RaffleStatsResponse, RafflesAwaitingRandomnessResponse, ReceiveMsg,
+    UserOddsResponse, VerifierExecuteMsg, VerifyResponse,
 };
 use raffles_export::state::{
-    AssetInfo, ContractInfo, Cw721Coin, RaffleInfo, RaffleOptions, RaffleOptionsMsg, RaffleState,
-    Randomness,
+    AssetInfo, ContractInfo, Cw721Coin, DrandScheme, GatingCondition, RaffleInfo, RaffleOptions,
+    RaffleOptionsMsg, RaffleState, Randomness, TicketRange, DRAND_MAINNET_CHAIN_HASH,
+    DRAND_MAINNET_GENESIS_TIME, DRAND_MAINNET_PERIOD,
 };
 
-use crate::contract::{execute, instantiate, query, verify};
+use crate::contract::{execute, instantiate, migrate, query, verify};
 use crate::error::ContractError;
-use crate::state::{CONTRACT_INFO, RAFFLE_INFO};
+use crate::state::{
+    get_ticket_owner, CONTRACT_INFO, LEGACY_RAFFLE_TICKETS, RAFFLE_INFO, RAFFLE_TICKETS,
+    USER_TICKETS,
+};
 
-use cw1155::Cw1155ExecuteMsg;
-use cw20::Cw20ExecuteMsg;
-use cw721::Cw721ExecuteMsg;
+use cw1155::{Cw1155ExecuteMsg, Cw1155ReceiveMsg};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
 
 use crate::testing::mock_querier::mock_querier_dependencies;
-
-const HEX_PUBKEY: &str = "868f005eb8e6e4ca0a47c8a77ceaa5309a47978a7c71bc5cce96366b5d7a569937c529eeda66c7293784a9402801af31";
+use sha2::{Digest, Sha256};
 
 pub fn assert_error(err: anyhow::Error, contract_error: ContractError) {
     assert_eq!(err.downcast::<ContractError>().unwrap(), contract_error)
@@ -41,7 +43,9 @@ fn init_helper(deps: DepsMut) {
     let instantiate_msg = InstantiateMsg {
         name: "nft-raffle".to_string(),
         owner: None,
-        random_pubkey: HEX_PUBKEY.from_hex().unwrap().to_base64(base64::STANDARD),
+        drand_chain_hash: None,
+        randomness_bounty: None,
+        reward_pool_fee: None,
         drand_url: None,
         verify_signature_contract: "verifier".to_string(),
         fee_addr: None,
@@ -73,6 +77,7 @@ fn create_raffle(deps: DepsMut) -> Result<Response> {
             })],
             raffle_options: RaffleOptionsMsg::default(),
             raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
         },
     )
 }
@@ -109,6 +114,7 @@ fn create_raffle_comment(deps: DepsMut, comment: &str) -> Result<Response> {
             },
 
             raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
         },
     )
 }
@@ -126,6 +132,7 @@ fn create_raffle_cw20(deps: DepsMut) -> Result<Response> {
             assets: vec![AssetInfo::cw721("nft", "token_id")],
             raffle_options: RaffleOptionsMsg::default(),
             raffle_ticket_price: AssetInfo::cw20(10000u128, "address"),
+            additional_ticket_prices: None,
         },
     )
 }
@@ -143,6 +150,7 @@ fn create_raffle_cw1155(deps: DepsMut) -> Result<Response> {
             assets: vec![AssetInfo::cw1155("nft", "token_id", 675u128)],
             raffle_options: RaffleOptionsMsg::default(),
             raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
         },
     )
 }
@@ -312,6 +320,7 @@ fn test_claim_raffle() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -363,6 +372,7 @@ fn test_ticket_and_claim_raffle() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -419,6 +429,51 @@ fn test_ticket_and_claim_raffle() {
     .unwrap_err();
 }
 
+#[test]
+fn test_legacy_claimed_raffle() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+    create_raffle(deps.as_mut()).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    raffle_info.randomness = Some(Randomness {
+        randomness: [0; 32],
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+
+    // Raffles claimed before prize slots were introduced saved a single winner
+    let legacy_raffle = String::from_utf8(to_vec(&raffle_info).unwrap())
+        .unwrap()
+        .replace(r#""winners":[]"#, r#""winner":"first""#);
+    let raffle_info: RaffleInfo = from_slice(legacy_raffle.as_bytes()).unwrap();
+    assert_eq!(raffle_info.winners, vec![deps.api.addr_validate("first").unwrap()]);
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    let err = claim_nft(deps.as_mut(), 0, 1000u64).unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongStateForClaim {
+            status: RaffleState::Claimed,
+        },
+    );
+
+    // Unclaimed legacy raffles have no winner yet
+    let raffle_info: RaffleInfo = from_slice(
+        legacy_raffle
+            .replace(r#""winner":"first""#, r#""winner":null"#)
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(raffle_info.winners, Vec::<Addr>::new());
+}
+
 #[test]
 fn test_multiple_tickets_and_claim_raffle() {
     let mut deps = mock_querier_dependencies(&[]);
@@ -490,6 +545,7 @@ fn test_multiple_tickets_and_claim_raffle() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -575,6 +631,7 @@ fn test_ticket_and_claim_raffle_cw20() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -650,7 +707,7 @@ fn test_ticket_and_claim_raffle_cw20() {
 }
 #[test]
 fn test_ticket_and_claim_raffle_cw1155() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_querier_dependencies(&[]);
     init_helper(deps.as_mut());
     let response = create_raffle_cw1155(deps.as_mut()).unwrap();
 
@@ -701,6 +758,7 @@ fn test_ticket_and_claim_raffle_cw1155() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -760,9 +818,22 @@ fn test_ticket_and_claim_raffle_cw1155() {
     .unwrap_err();
 }
 
+/// Wraps the contract data the way wasmd returns it to reply handlers (a MsgExecuteContractResponse)
+fn execute_response_data(data: Binary) -> Binary {
+    let mut encoded = vec![0x0a];
+    let mut len = data.len();
+    while len >= 0x80 {
+        encoded.push((len as u8 & 0x7f) | 0x80);
+        len >>= 7;
+    }
+    encoded.push(len as u8);
+    encoded.extend_from_slice(data.as_slice());
+    Binary::from(encoded)
+}
+
 #[test]
 fn test_randomness_provider() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_querier_dependencies(&[]);
     init_helper(deps.as_mut());
     create_raffle_cw1155(deps.as_mut()).unwrap();
     let mut env = mock_env();
@@ -785,8 +856,7 @@ fn test_randomness_provider() {
     .unwrap();
     let msg = VerifierExecuteMsg::Verify {
         randomness: randomness.clone(),
-        pubkey: Binary::from_base64(&HEX_PUBKEY.from_hex().unwrap().to_base64(base64::STANDARD))
-            .unwrap(),
+        chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
         raffle_id: 0,
         owner: "anyone".to_string(),
     };
@@ -804,15 +874,37 @@ fn test_randomness_provider() {
         deps.as_mut(),
         env.clone(),
         SubMsgResult::Ok(SubMsgResponse {
-            events: vec![Event::new("wasm")
-                .add_attribute("round", 90u128.to_string())
-                .add_attribute("owner", "anyone")
-                .add_attribute("randomness", random)
-                .add_attribute("raffle_id", 0u128.to_string())],
-            data: None,
+            events: vec![],
+            data: Some(execute_response_data(
+                to_binary(&VerifyResponse {
+                    raffle_id: 0,
+                    owner: "anyone".to_string(),
+                    round: 90,
+                    randomness: Binary::from_base64(random).unwrap(),
+                    signature: randomness.signature.clone(),
+                })
+                .unwrap(),
+            )),
         }),
     )
     .unwrap();
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    assert_eq!(
+        raffle_info.randomness.unwrap().randomness_owner,
+        deps.api.addr_validate("anyone").unwrap()
+    );
+
+    // The verifier data is required
+    let err = verify(
+        deps.as_mut(),
+        env.clone(),
+        SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::NotFoundError("verify response".to_string()));
 
     randomness.round = 76;
     let another_randomness = execute(
@@ -861,31 +953,284 @@ fn test_randomness_provider() {
     .unwrap();
 }
 
+#[test]
+fn test_randomness_round_after_close() {
+    let mut deps = mock_querier_dependencies(&[]);
+    init_helper(deps.as_mut());
+    let env = mock_env();
+    let genesis_time = Timestamp::from_seconds(env.block.time.seconds() - 3000);
+
+    // The timing of the drand network is registered in the verifier
+    deps.querier.with_beacon_timing(genesis_time, 30);
+    create_raffle_cw1155(deps.as_mut()).unwrap();
+
+    // The raffle closes 1 second after the last round published before it (round 101)
+    let response: RaffleResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::RaffleInfo { raffle_id: 0 }).unwrap())
+            .unwrap();
+    assert_eq!(response.min_randomness_round, Some(102));
+    let mut raffle_info = response.raffle_info.unwrap();
+    assert_eq!(raffle_info.drand_genesis_time, genesis_time);
+    assert_eq!(raffle_info.drand_period, 30);
+
+    // Raffles saved with a stale timing are still checked against the timing of the network they are bound to
+    raffle_info.drand_genesis_time = Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME);
+    raffle_info.drand_period = DRAND_MAINNET_PERIOD;
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(2u64);
+    let mut randomness = DrandRandomness {
+        round: 101,
+        signature: Binary::from_base64("quid").unwrap(),
+        previous_signature: Binary::from_base64("quid").unwrap(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::UpdateRandomness {
+            raffle_id: 0,
+            randomness: randomness.clone(),
+        },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::RandomnessRoundTooEarly { min_round: 102 });
+
+    randomness.round = 102;
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::UpdateRandomness {
+            raffle_id: 0,
+            randomness,
+        },
+    )
+    .unwrap();
+}
+
+fn verify_response_result(raffle_id: u64, provider: &str) -> SubMsgResult {
+    SubMsgResult::Ok(SubMsgResponse {
+        events: vec![],
+        data: Some(execute_response_data(
+            to_binary(&VerifyResponse {
+                raffle_id,
+                owner: provider.to_string(),
+                round: 90,
+                randomness: Binary::from_base64("iVgPamOa3WyQ3PPSIuNUFfidnuLNbvb8TyMTTN/6XR4=")
+                    .unwrap(),
+                signature: Binary::default(),
+            })
+            .unwrap(),
+        )),
+    })
+}
+
+#[test]
+fn test_randomness_reward_pool() {
+    let mut deps = mock_querier_dependencies(&[]);
+    init_helper(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            fee_addr: None,
+            minimum_raffle_duration: None,
+            minimum_raffle_timeout: None,
+            max_participant_number: None,
+            raffle_fee: Some(Decimal::from_str("0.1").unwrap()),
+            rand_fee: None,
+            drand_url: None,
+            verify_signature_contract: None,
+            drand_chain_hash: None,
+            randomness_bounty: Some(AssetInfo::coin(1000, "uluna")),
+            reward_pool_fee: Some(Decimal::from_str("0.5").unwrap()),
+        },
+    )
+    .unwrap();
+
+    // Half of the treasury fee of a claimed raffle goes to the reward pool
+    create_raffle_cw1155(deps.as_mut()).unwrap();
+    for buyer in ["first", "first", "second", "third", "fourth"] {
+        buy_ticket_coin(deps.as_mut(), 0, buyer, coin(10000, "uluna"), 0u64, None).unwrap();
+    }
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    let mut randomness: [u8; 32] = [0; 32];
+    hex::decode_to_slice(
+        "89580f6a639add6c90dcf3d222e35415f89d9ee2cd6ef6fc4f23134cdffa5d1e",
+        randomness.as_mut_slice(),
+    )
+    .unwrap();
+    raffle_info.randomness = Some(Randomness {
+        randomness,
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    // The stats show the payouts of the claim
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::RaffleStats { raffle_id: 0 }).unwrap();
+    let stats = from_binary::<RaffleStatsResponse>(&response).unwrap();
+    assert_eq!(stats.randomness_payout, vec![AssetInfo::coin(5u128, "uluna")]);
+    assert_eq!(stats.treasury_payout, vec![AssetInfo::coin(2500u128, "uluna")]);
+    assert_eq!(stats.owner_payout, vec![AssetInfo::coin(44995u128, "uluna")]);
+
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    assert_eq!(
+        response.messages[1..],
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "rand_provider".to_string(),
+                amount: coins(5, "uluna")
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(2500, "uluna")
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(44995u128, "uluna")
+            }),
+        ]
+    );
+    let reward_pool: Vec<AssetInfo> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RewardPool {}).unwrap()).unwrap();
+    assert_eq!(reward_pool, vec![AssetInfo::coin(2500, "uluna")]);
+
+    // A raffle without any ticket sold doesn't earn the bounty
+    create_raffle_cw1155(deps.as_mut()).unwrap();
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(2u64);
+    let awaiting_randomness = |deps: Deps, env: Env| -> RafflesAwaitingRandomnessResponse {
+        from_binary(
+            &query(
+                deps,
+                env,
+                QueryMsg::RafflesAwaitingRandomness {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        awaiting_randomness(deps.as_ref(), env.clone()).raffle_ids,
+        Vec::<u64>::new()
+    );
+    let response = verify(deps.as_mut(), env.clone(), verify_response_result(1, "provider")).unwrap();
+    assert_eq!(response.messages, vec![]);
+
+    // A raffle with tickets sold is listed once closed, with the bounty
+    create_raffle_cw1155(deps.as_mut()).unwrap();
+    buy_ticket_coin(deps.as_mut(), 2, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+    assert_eq!(
+        awaiting_randomness(deps.as_ref(), env.clone()),
+        RafflesAwaitingRandomnessResponse {
+            raffle_ids: vec![2],
+            bounty: Some(AssetInfo::coin(1000, "uluna")),
+        }
+    );
+
+    // Only the first valid randomness is rewarded
+    let response = verify(deps.as_mut(), env.clone(), verify_response_result(2, "provider")).unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "provider".to_string(),
+            amount: coins(1000, "uluna")
+        })]
+    );
+    let response = verify(deps.as_mut(), env.clone(), verify_response_result(2, "other")).unwrap();
+    assert_eq!(response.messages, vec![]);
+
+    let reward_pool: Vec<AssetInfo> =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::RewardPool {}).unwrap()).unwrap();
+    assert_eq!(reward_pool, vec![AssetInfo::coin(1500, "uluna")]);
+    assert_eq!(
+        awaiting_randomness(deps.as_ref(), env.clone()).raffle_ids,
+        Vec::<u64>::new()
+    );
+
+    // The bounty is capped at the treasury fee collected by the raffle
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CreateRaffle {
+            owner: None,
+            assets: vec![AssetInfo::cw1155("nft", "token_id", 675u128)],
+            raffle_options: RaffleOptionsMsg::default(),
+            raffle_ticket_price: AssetInfo::coin(100u128, "uluna"),
+            additional_ticket_prices: None,
+        },
+    )
+    .unwrap();
+    buy_ticket_coin(deps.as_mut(), 3, "first", coin(100, "uluna"), 0u64, None).unwrap();
+    let response = verify(deps.as_mut(), env.clone(), verify_response_result(3, "provider")).unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "provider".to_string(),
+            amount: coins(10, "uluna")
+        })]
+    );
+    let reward_pool: Vec<AssetInfo> =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::RewardPool {}).unwrap()).unwrap();
+    assert_eq!(reward_pool, vec![AssetInfo::coin(1490, "uluna")]);
+}
+
 // Admin functions
+fn update_owner_msg(owner: String) -> ExecuteMsg {
+    ExecuteMsg::UpdateConfig {
+        owner: Some(owner),
+        fee_addr: None,
+        minimum_raffle_duration: None,
+        minimum_raffle_timeout: None,
+        max_participant_number: None,
+        raffle_fee: None,
+        rand_fee: None,
+        drand_url: None,
+        verify_signature_contract: None,
+        drand_chain_hash: None,
+        randomness_bounty: None,
+        reward_pool_fee: None,
+    }
+}
+
 #[test]
 fn test_renounce() {
     let mut deps = mock_dependencies();
     init_helper(deps.as_mut());
     let info = mock_info("bad_person", &[]);
     let env = mock_env();
-    execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ChangeParameter { parameter: "owner".to_string(), value: env.contract.address.to_string() }).unwrap_err();
+    execute(deps.as_mut(), env.clone(), info, update_owner_msg(env.contract.address.to_string())).unwrap_err();
 
     let info = mock_info("creator", &[]);
     execute(
         deps.as_mut(),
         env.clone(),
         info.clone(),
-        ExecuteMsg::ChangeParameter { parameter: "owner".to_string(), value: env.contract.address.to_string() },
+        update_owner_msg(env.contract.address.to_string()),
     )
     .unwrap();
     // Still admin
-    execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::ChangeParameter { parameter: "owner".to_string(), value: env.contract.address.to_string() }).unwrap();
+    execute(deps.as_mut(), env.clone(), info.clone(), update_owner_msg(env.contract.address.to_string())).unwrap();
 
     // Claim ownership
     execute(deps.as_mut(), env.clone(), mock_info(&env.contract.address.to_string(), &[]), ExecuteMsg::ClaimOwnership {  }).unwrap();
 
     // Not admin anymore
-    execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ChangeParameter { parameter: "owner".to_string(), value: env.contract.address.to_string() }).unwrap_err();
+    execute(deps.as_mut(), env.clone(), info, update_owner_msg(env.contract.address.to_string())).unwrap_err();
 
 }
 
@@ -926,53 +1271,82 @@ fn test_lock() {
 }
 
 #[test]
-fn test_change_parameter() {
-    let mut deps = mock_dependencies();
+fn test_update_config() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
     init_helper(deps.as_mut());
 
+    let update_config_msg = |fee_addr: Option<&str>, raffle_fee: Option<&str>| {
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            fee_addr: fee_addr.map(|x| x.to_string()),
+            minimum_raffle_duration: Some(0),
+            minimum_raffle_timeout: None,
+            max_participant_number: Some(100),
+            raffle_fee: raffle_fee.map(|x| Decimal::from_str(x).unwrap()),
+            rand_fee: None,
+            drand_url: None,
+            verify_signature_contract: None,
+            drand_chain_hash: None,
+            randomness_bounty: None,
+            reward_pool_fee: None,
+        }
+    };
+
     let info = mock_info("bad_person", &[]);
     let env = mock_env();
     execute(
         deps.as_mut(),
         env.clone(),
         info,
-        ExecuteMsg::ChangeParameter {
-            parameter: "any".to_string(),
-            value: "any".to_string(),
-        },
+        update_config_msg(Some("any"), None),
     )
     .unwrap_err();
 
+    // The fees are validated together
     let info = mock_info("creator", &[]);
     execute(
         deps.as_mut(),
         env.clone(),
         info.clone(),
-        ExecuteMsg::ChangeParameter {
-            parameter: "any".to_string(),
-            value: "any".to_string(),
-        },
+        update_config_msg(Some("any"), Some("0.9999")),
     )
     .unwrap_err();
 
-    execute(
+    let response = execute(
         deps.as_mut(),
         env,
         info,
-        ExecuteMsg::ChangeParameter {
-            parameter: "fee_addr".to_string(),
-            value: "any".to_string(),
-        },
+        update_config_msg(Some("any"), Some("0.05")),
     )
     .unwrap();
+    assert_eq!(
+        response.attributes,
+        vec![
+            attr("action", "update_config"),
+            attr("fee_addr", "any"),
+            attr("minimum_raffle_duration", "1"),
+            attr("max_participant_number", "100"),
+            attr("raffle_fee", "0.05"),
+        ]
+    );
+
+    let contract_info = CONTRACT_INFO.load(&deps.storage).unwrap();
+    assert_eq!(contract_info.fee_addr.to_string(), "any");
+    assert_eq!(contract_info.minimum_raffle_duration, 1u64);
+    assert_eq!(contract_info.max_participant_number, Some(100));
+    assert_eq!(contract_info.raffle_fee, Decimal::from_str("0.05").unwrap());
 
+    // The contract default applies to new raffles
+    create_raffle(deps.as_mut()).unwrap();
     assert_eq!(
-        CONTRACT_INFO
-            .load(&deps.storage)
+        RAFFLE_INFO
+            .load(&deps.storage, 0)
             .unwrap()
-            .fee_addr
-            .to_string(),
-        "any"
+            .raffle_options
+            .max_participant_number,
+        Some(100)
     );
 }
 
@@ -998,10 +1372,11 @@ fn test_query_contract_info() {
             lock: false,
             drand_url: "https://api.drand.sh/".to_string(),
             verify_signature_contract: deps.api.addr_validate("verifier").unwrap(),
-            random_pubkey: Binary::from_base64(
-                &HEX_PUBKEY.from_hex().unwrap().to_base64(base64::STANDARD)
-            )
-            .unwrap()
+            drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+            max_participant_number: None,
+            last_series_id: None,
+            randomness_bounty: None,
+            reward_pool_fee: Decimal::zero(),
         }
     );
 }
@@ -1032,6 +1407,7 @@ fn test_query_raffle_info() {
         RaffleResponse {
             raffle_id: 1,
             raffle_state: RaffleState::Started,
+            min_randomness_round: Some(1),
             raffle_info: Some(RaffleInfo {
                 owner: deps.api.addr_validate("creator").unwrap(),
                 assets: vec![AssetInfo::cw721("nft", "token_id")],
@@ -1042,12 +1418,23 @@ fn test_query_raffle_info() {
                     comment: Some("random things my dude".to_string()),
                     max_participant_number: None,
                     max_ticket_per_address: None,
-                    raffle_preview: 0
+                    min_ticket_number: None,
+                    raffle_preview: 0,
+                    prize_slots: vec![vec![0]],
+                    gating: vec![]
                 },
                 raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+                additional_ticket_prices: vec![],
+                collected_funds: vec![],
+                series_id: None,
+                drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                drand_scheme: DrandScheme::PedersenBlsChained,
+                drand_genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                drand_period: DRAND_MAINNET_PERIOD,
                 number_of_tickets: 0u32,
+                unique_participants: 0,
                 randomness: None,
-                winner: None,
+                winners: vec![],
                 is_cancelled: false,
             })
         }
@@ -1088,6 +1475,7 @@ fn test_query_all_raffle_info() {
             RaffleResponse {
                 raffle_id: 1,
                 raffle_state: RaffleState::Started,
+                min_randomness_round: Some(1),
                 raffle_info: Some(RaffleInfo {
                     owner: deps.api.addr_validate("creator").unwrap(),
                     assets: vec![AssetInfo::cw721("nft", "token_id")],
@@ -1098,18 +1486,30 @@ fn test_query_all_raffle_info() {
                         comment: Some("random things my dude".to_string()),
                         max_participant_number: None,
                         max_ticket_per_address: None,
-                        raffle_preview: 0
+                        min_ticket_number: None,
+                        raffle_preview: 0,
+                        prize_slots: vec![vec![0]],
+                        gating: vec![]
                     },
                     raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+                    additional_ticket_prices: vec![],
+                    collected_funds: vec![],
+                    series_id: None,
+                    drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                    drand_scheme: DrandScheme::PedersenBlsChained,
+                    drand_genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                    drand_period: DRAND_MAINNET_PERIOD,
                     number_of_tickets: 0u32,
+                    unique_participants: 0,
                     randomness: None,
-                    winner: None,
+                    winners: vec![],
                     is_cancelled: false,
                 })
             },
             RaffleResponse {
                 raffle_id: 0,
                 raffle_state: RaffleState::Started,
+                min_randomness_round: Some(1),
                 raffle_info: Some(RaffleInfo {
                     owner: deps.api.addr_validate("creator").unwrap(),
                     assets: vec![AssetInfo::cw721("nft", "token_id")],
@@ -1120,12 +1520,23 @@ fn test_query_all_raffle_info() {
                         comment: None,
                         max_participant_number: None,
                         max_ticket_per_address: None,
-                        raffle_preview: 0
+                        min_ticket_number: None,
+                        raffle_preview: 0,
+                        prize_slots: vec![vec![0]],
+                        gating: vec![]
                     },
                     raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+                    additional_ticket_prices: vec![],
+                    collected_funds: vec![],
+                    series_id: None,
+                    drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                    drand_scheme: DrandScheme::PedersenBlsChained,
+                    drand_genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                    drand_period: DRAND_MAINNET_PERIOD,
                     number_of_tickets: 0u32,
+                    unique_participants: 0,
                     randomness: None,
-                    winner: None,
+                    winners: vec![],
                     is_cancelled: false,
                 })
             }
@@ -1151,6 +1562,7 @@ fn test_query_all_raffle_info() {
         vec![RaffleResponse {
             raffle_id: 1,
             raffle_state: RaffleState::Started,
+            min_randomness_round: Some(1),
             raffle_info: Some(RaffleInfo {
                 owner: deps.api.addr_validate("creator").unwrap(),
                 assets: vec![AssetInfo::cw721("nft", "token_id")],
@@ -1161,12 +1573,23 @@ fn test_query_all_raffle_info() {
                     comment: Some("random things my dude".to_string()),
                     max_participant_number: None,
                     max_ticket_per_address: None,
-                    raffle_preview: 0
+                    min_ticket_number: None,
+                    raffle_preview: 0,
+                    prize_slots: vec![vec![0]],
+                    gating: vec![]
                 },
                 raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+                additional_ticket_prices: vec![],
+                collected_funds: vec![],
+                series_id: None,
+                drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                drand_scheme: DrandScheme::PedersenBlsChained,
+                drand_genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                drand_period: DRAND_MAINNET_PERIOD,
                 number_of_tickets: 0u32,
+                unique_participants: 0,
                 randomness: None,
-                winner: None,
+                winners: vec![],
                 is_cancelled: false,
             })
         }]
@@ -1191,6 +1614,7 @@ fn test_query_all_raffle_info() {
         vec![RaffleResponse {
             raffle_id: 0,
             raffle_state: RaffleState::Started,
+            min_randomness_round: Some(1),
             raffle_info: Some(RaffleInfo {
                 owner: deps.api.addr_validate("creator").unwrap(),
                 assets: vec![AssetInfo::cw721("nft", "token_id")],
@@ -1201,12 +1625,23 @@ fn test_query_all_raffle_info() {
                     comment: None,
                     max_participant_number: None,
                     max_ticket_per_address: None,
-                    raffle_preview: 0
+                    min_ticket_number: None,
+                    raffle_preview: 0,
+                    prize_slots: vec![vec![0]],
+                    gating: vec![]
                 },
                 raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+                additional_ticket_prices: vec![],
+                collected_funds: vec![],
+                series_id: None,
+                drand_chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                drand_scheme: DrandScheme::PedersenBlsChained,
+                drand_genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                drand_period: DRAND_MAINNET_PERIOD,
                 number_of_tickets: 0u32,
+                unique_participants: 0,
                 randomness: None,
-                winner: None,
+                winners: vec![],
                 is_cancelled: false,
             })
         }]
@@ -1293,6 +1728,7 @@ fn test_multiple_tickets() {
         randomness,
         randomness_round: 2098475u64,
         randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
     });
     RAFFLE_INFO
         .save(deps.as_mut().storage, 0, &raffle_info)
@@ -1328,3 +1764,1215 @@ fn test_multiple_tickets() {
         ]
     );
 }
+
+#[test]
+fn test_prize_slots() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier.with_owner_of(&[
+        (&"nft - token_0".to_string(), &"creator".to_string()),
+        (&"nft - token_1".to_string(), &"creator".to_string()),
+        (&"nft - token_2".to_string(), &"creator".to_string()),
+    ]);
+    init_helper(deps.as_mut());
+
+    let create_raffle_msg = |prize_slots: Vec<Vec<u32>>| ExecuteMsg::CreateRaffle {
+        owner: None,
+        assets: vec![
+            AssetInfo::cw721("nft", "token_0"),
+            AssetInfo::cw721("nft", "token_1"),
+            AssetInfo::cw721("nft", "token_2"),
+        ],
+        raffle_options: RaffleOptionsMsg {
+            prize_slots: Some(prize_slots),
+            ..Default::default()
+        },
+        raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+        additional_ticket_prices: None,
+    };
+
+    // Each asset has to be in exactly one prize slot
+    for prize_slots in [vec![vec![0, 1]], vec![vec![0, 1], vec![1, 2]], vec![vec![0, 1, 2], vec![]]] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            create_raffle_msg(prize_slots),
+        )
+        .unwrap_err();
+        assert_error(err, ContractError::WrongPrizeSlots {});
+    }
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_raffle_msg(vec![vec![0, 2], vec![1]]),
+    )
+    .unwrap();
+
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "second", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "third", coin(10000, "uluna"), 0u64, None).unwrap();
+
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    let mut randomness: [u8; 32] = [0; 32];
+    hex::decode_to_slice(
+        "89580f6a639add6c90dcf3d222e35415f89d9ee2cd6ef6fc4f23134cdffa5d1e",
+        randomness.as_mut_slice(),
+    )
+    .unwrap();
+    raffle_info.randomness = Some(Randomness {
+        randomness,
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    // Two different tickets win the two prize slots
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    let transfer_message = |recipient: &str, token_id: &str| {
+        SubMsg::new(
+            into_cosmos_msg(
+                Cw721ExecuteMsg::TransferNft {
+                    recipient: recipient.to_string(),
+                    token_id: token_id.to_string(),
+                },
+                "nft".to_string(),
+            )
+            .unwrap(),
+        )
+    };
+    assert_eq!(
+        response.messages[..3],
+        vec![
+            transfer_message("first", "token_0"),
+            transfer_message("first", "token_2"),
+            transfer_message("third", "token_1"),
+        ]
+    );
+
+    let response = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::PrizeSlots { raffle_id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        from_binary::<Vec<PrizeSlotResponse>>(&response).unwrap(),
+        vec![
+            PrizeSlotResponse {
+                slot: 0,
+                assets: vec![
+                    AssetInfo::cw721("nft", "token_0"),
+                    AssetInfo::cw721("nft", "token_2")
+                ],
+                winner: Some(deps.api.addr_validate("first").unwrap()),
+            },
+            PrizeSlotResponse {
+                slot: 1,
+                assets: vec![AssetInfo::cw721("nft", "token_1")],
+                winner: Some(deps.api.addr_validate("third").unwrap()),
+            }
+        ]
+    );
+
+    // The second draw skips the ticket that already won
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::DrawProof { raffle_id: 0 }).unwrap();
+    let proof = from_binary::<DrawProofResponse>(&response).unwrap();
+    assert_eq!(
+        proof
+            .draws
+            .iter()
+            .map(|draw| (draw.slot, draw.ticket_id))
+            .collect::<Vec<_>>(),
+        vec![(0, 0), (1, 2)]
+    );
+}
+
+#[test]
+fn test_rejection_sampling() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+    create_raffle(deps.as_mut()).unwrap();
+
+    // With 2^31 + 1 tickets, almost half of the u32 values are above the last full multiple of the ticket number
+    let number_of_tickets = (1u64 << 31) + 1;
+    let limit = (1u64 << 32) - (1u64 << 32) % number_of_tickets;
+    buy_ticket_coin(
+        deps.as_mut(),
+        0,
+        "first",
+        coin(10000 * 1_500_000_000, "uluna"),
+        0u64,
+        Some(1_500_000_000),
+    )
+    .unwrap();
+    let second_tickets = (number_of_tickets - 1_500_000_000) as u32;
+    buy_ticket_coin(
+        deps.as_mut(),
+        0,
+        "second",
+        coin(10000 * u128::from(second_tickets), "uluna"),
+        0u64,
+        Some(second_tickets),
+    )
+    .unwrap();
+
+    // The first value drawn from this randomness is above the limit
+    let randomness = [4u8; 32];
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    raffle_info.randomness = Some(Randomness {
+        randomness,
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::DrawProof { raffle_id: 0 }).unwrap();
+    let proof = from_binary::<DrawProofResponse>(&response).unwrap();
+    assert_eq!(proof.randomness, Binary::from(randomness.to_vec()));
+    assert_eq!(proof.seed, Binary::from(Sha256::digest(&randomness).to_vec()));
+
+    // The rejected value shows up in the proof, followed by the accepted one
+    let draw = &proof.draws[0];
+    assert_eq!(draw.random_values.len(), 2);
+    assert!(u64::from(draw.random_values[0]) >= limit);
+    assert!(u64::from(draw.random_values[1]) < limit);
+    assert_eq!(
+        u64::from(draw.drawn_index),
+        u64::from(draw.random_values[1]) % number_of_tickets
+    );
+    assert_eq!(draw.ticket_id, draw.drawn_index);
+
+    // Without the redraw, the winning ticket would have been one of the first tickets
+    assert!(u64::from(draw.random_values[0]) % number_of_tickets < 1_500_000_000);
+    assert!(draw.ticket_id >= 1_500_000_000);
+    assert_eq!(draw.winner, deps.api.addr_validate("second").unwrap());
+}
+
+#[test]
+fn test_failed_raffle_refund() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+
+    let create_raffle_msg = |min_ticket_number: u32| ExecuteMsg::CreateRaffle {
+        owner: None,
+        assets: vec![AssetInfo::cw721("nft", "token_id")],
+        raffle_options: RaffleOptionsMsg {
+            min_ticket_number: Some(min_ticket_number),
+            max_participant_number: Some(10),
+            ..Default::default()
+        },
+        raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+        additional_ticket_prices: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_raffle_msg(11),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongTicketThreshold {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_raffle_msg(4),
+    )
+    .unwrap();
+
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(20000, "uluna"), 0u64, Some(2)).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "second", coin(10000, "uluna"), 0u64, None).unwrap();
+
+    // No refunds while the raffle is open
+    let mut env = mock_env();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("first", &[]),
+        ExecuteMsg::ClaimRefund { raffle_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongStateForRefund {
+            status: RaffleState::Started,
+        },
+    );
+
+    // Not enough tickets were sold, the raffle fails without needing randomness
+    env.block.time = env.block.time.plus_seconds(1000u64);
+    let response = query(deps.as_ref(), env.clone(), QueryMsg::RaffleInfo { raffle_id: 0 }).unwrap();
+    assert_eq!(
+        from_binary::<RaffleResponse>(&response).unwrap().raffle_state,
+        RaffleState::Failed
+    );
+
+    // The owner gets the assets back only once
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(
+            into_cosmos_msg(
+                Cw721ExecuteMsg::TransferNft {
+                    recipient: "creator".to_string(),
+                    token_id: "token_id".to_string(),
+                },
+                "nft"
+            )
+            .unwrap()
+        )]
+    );
+    let err = claim_nft(deps.as_mut(), 0, 1000u64).unwrap_err();
+    assert_error(
+        err,
+        ContractError::WrongStateForClaim {
+            status: RaffleState::Failed,
+        },
+    );
+
+    // The ticket buyers get a full refund
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("first", &[]),
+        ExecuteMsg::ClaimRefund { raffle_id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "first".to_string(),
+            amount: coins(20000u128, "uluna"),
+        })]
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("first", &[]),
+        ExecuteMsg::ClaimRefund { raffle_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::NoTicketsToRefund {});
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("third", &[]),
+        ExecuteMsg::ClaimRefund { raffle_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::NoTicketsToRefund {});
+}
+
+#[test]
+fn test_ticket_ranges() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+    create_raffle(deps.as_mut()).unwrap();
+
+    // Buying a lot of tickets at once only saves one range
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(
+        deps.as_mut(),
+        0,
+        "second",
+        coin(5_000_000, "uluna"),
+        0u64,
+        Some(500),
+    )
+    .unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "third", coin(20000, "uluna"), 0u64, Some(2)).unwrap();
+
+    assert_eq!(
+        get_ticket_owner(&deps.storage, 0, 0).unwrap(),
+        deps.api.addr_validate("first").unwrap()
+    );
+    assert_eq!(
+        get_ticket_owner(&deps.storage, 0, 250).unwrap(),
+        deps.api.addr_validate("second").unwrap()
+    );
+    assert_eq!(
+        get_ticket_owner(&deps.storage, 0, 502).unwrap(),
+        deps.api.addr_validate("third").unwrap()
+    );
+    get_ticket_owner(&deps.storage, 0, 503).unwrap_err();
+
+    let query_tickets = |start_after: Option<u32>, limit: Option<u32>| {
+        from_binary::<Vec<String>>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::AllTickets {
+                    raffle_id: 0,
+                    start_after,
+                    limit,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        query_tickets(None, Some(3)),
+        vec!["first", "second", "second"]
+    );
+    assert_eq!(
+        query_tickets(Some(498), None),
+        vec!["second", "second", "third", "third"]
+    );
+    assert_eq!(query_tickets(Some(502), None), Vec::<String>::new());
+
+    let response = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::TicketNumber {
+            owner: "second".to_string(),
+            raffle_id: 0,
+        },
+    )
+    .unwrap();
+    assert_eq!(from_binary::<u32>(&response).unwrap(), 500);
+
+    // Empty purchases are not saved as ranges
+    let err = buy_ticket_coin(deps.as_mut(), 0, "fourth", coin(0, "uluna"), 0u64, Some(0))
+        .unwrap_err();
+    assert_error(err, ContractError::NoTicketsBought {});
+}
+
+#[test]
+fn test_migrate_legacy_tickets() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+    create_raffle(deps.as_mut()).unwrap();
+
+    // Tickets used to be saved one entry per ticket
+    for (ticket_id, owner) in ["first", "second", "second", "first"].iter().enumerate() {
+        let owner = deps.api.addr_validate(owner).unwrap();
+        LEGACY_RAFFLE_TICKETS
+            .save(deps.as_mut().storage, (0, ticket_id as u32), &owner)
+            .unwrap();
+    }
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    raffle_info.number_of_tickets = 4;
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    assert_eq!(
+        RAFFLE_TICKETS
+            .prefix(0)
+            .range(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![
+            (
+                0,
+                TicketRange {
+                    owner: deps.api.addr_validate("first").unwrap(),
+                    count: 1
+                }
+            ),
+            (
+                1,
+                TicketRange {
+                    owner: deps.api.addr_validate("second").unwrap(),
+                    count: 2
+                }
+            ),
+            (
+                3,
+                TicketRange {
+                    owner: deps.api.addr_validate("first").unwrap(),
+                    count: 1
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        get_ticket_owner(&deps.storage, 0, 2).unwrap(),
+        deps.api.addr_validate("second").unwrap()
+    );
+    assert!(LEGACY_RAFFLE_TICKETS
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_none());
+
+    // New tickets are added after the migrated ones
+    buy_ticket_coin(deps.as_mut(), 0, "third", coin(10000, "uluna"), 0u64, None).unwrap();
+    assert_eq!(
+        get_ticket_owner(&deps.storage, 0, 4).unwrap(),
+        deps.api.addr_validate("third").unwrap()
+    );
+}
+
+#[test]
+fn test_gated_raffle() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier.with_owner_of(&[
+        (&"nft - token_id".to_string(), &"creator".to_string()),
+        (&"collection - holder_nft".to_string(), &"holder".to_string()),
+        (&"collection - other_nft".to_string(), &"other".to_string()),
+    ]);
+    deps.querier.with_cw20_balance("cw20", "holder", 100u128);
+    deps.querier.with_cw20_balance("cw20", "other", 99u128);
+    deps.querier
+        .with_native_balance("holder", coins(1000u128, "uluna"));
+    init_helper(deps.as_mut());
+
+    let gating = vec![
+        GatingCondition::Cw721 {
+            address: "collection".to_string(),
+        },
+        GatingCondition::Cw20 {
+            address: "cw20".to_string(),
+            min_balance: Uint128::from(100u128),
+        },
+        GatingCondition::Coin {
+            denom: "uluna".to_string(),
+            min_balance: Uint128::from(1000u128),
+        },
+        GatingCondition::Allowlist {
+            addresses: vec!["holder".to_string(), "other".to_string()],
+        },
+    ];
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CreateRaffle {
+            owner: None,
+            assets: vec![AssetInfo::cw721("nft", "token_id")],
+            raffle_options: RaffleOptionsMsg {
+                gating: Some(gating.clone()),
+                ..Default::default()
+            },
+            raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
+        },
+    )
+    .unwrap();
+
+    // The gating rules are exposed in the raffle info
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::RaffleInfo { raffle_id: 0 }).unwrap();
+    assert_eq!(
+        from_binary::<RaffleResponse>(&response)
+            .unwrap()
+            .raffle_info
+            .unwrap()
+            .raffle_options
+            .gating,
+        gating
+    );
+
+    // All the conditions have to be fulfilled
+    let err = buy_ticket_coin(deps.as_mut(), 0, "other", coin(10000, "uluna"), 0u64, None)
+        .unwrap_err();
+    assert_error(err, ContractError::NotEligible {});
+    let err = buy_ticket_coin(deps.as_mut(), 0, "stranger", coin(10000, "uluna"), 0u64, None)
+        .unwrap_err();
+    assert_error(err, ContractError::NotEligible {});
+    buy_ticket_coin(deps.as_mut(), 0, "holder", coin(10000, "uluna"), 0u64, None).unwrap();
+}
+
+#[test]
+fn test_create_raffle_with_receive() {
+    let mut deps = mock_querier_dependencies(&[]);
+    init_helper(deps.as_mut());
+
+    let create_raffle_msg = |asset: AssetInfo| {
+        to_binary(&ExecuteMsg::CreateRaffle {
+            owner: None,
+            assets: vec![asset],
+            raffle_options: RaffleOptionsMsg::default(),
+            raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
+        })
+        .unwrap()
+    };
+
+    // The received NFT has to be the raffle asset
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("nft", &[]),
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "creator".to_string(),
+            token_id: "token_id".to_string(),
+            msg: create_raffle_msg(AssetInfo::cw721("nft", "other_token_id")),
+        }),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::AssetMismatch {});
+
+    let response = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("nft", &[]),
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "creator".to_string(),
+            token_id: "token_id".to_string(),
+            msg: create_raffle_msg(AssetInfo::cw721("nft", "token_id")),
+        }),
+    )
+    .unwrap();
+    assert_eq!(response.messages, vec![]);
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    assert_eq!(raffle_info.owner, deps.api.addr_validate("creator").unwrap());
+    assert_eq!(raffle_info.assets, vec![AssetInfo::cw721("nft", "token_id")]);
+
+    // cw1155 tokens use the same receive hook name as cw20 tokens
+    let receive_msg = ExecuteMsg::Receive(ReceiveMsg::Cw1155(Cw1155ReceiveMsg {
+        operator: "operator".to_string(),
+        from: Some("creator".to_string()),
+        token_id: "token_id".to_string(),
+        amount: Uint128::from(675u128),
+        msg: create_raffle_msg(AssetInfo::cw1155("cw1155", "token_id", 675u128)),
+    }));
+    let receive_msg: ExecuteMsg = from_binary(&to_binary(&receive_msg).unwrap()).unwrap();
+    execute(deps.as_mut(), mock_env(), mock_info("cw1155", &[]), receive_msg).unwrap();
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 1).unwrap();
+    assert_eq!(raffle_info.owner, deps.api.addr_validate("creator").unwrap());
+    assert_eq!(
+        raffle_info.assets,
+        vec![AssetInfo::cw1155("cw1155", "token_id", 675u128)]
+    );
+
+    // Raffle tickets can still be bought with cw20 tokens
+    let receive_msg = ExecuteMsg::Receive(ReceiveMsg::Cw20(Cw20ReceiveMsg {
+        sender: "buyer".to_string(),
+        amount: Uint128::from(10000u128),
+        msg: to_binary(&ExecuteMsg::BuyTicket {
+            raffle_id: 0,
+            ticket_number: 1,
+            sent_assets: AssetInfo::cw20(10000u128, "cw20"),
+        })
+        .unwrap(),
+    }));
+    let receive_msg: ExecuteMsg = from_binary(&to_binary(&receive_msg).unwrap()).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("cw20", &[]), receive_msg).unwrap_err();
+    assert_error(
+        err,
+        ContractError::PaiementNotSufficient {
+            assets_wanted: AssetInfo::coin(10000u128, "uluna"),
+            assets_received: AssetInfo::cw20(10000u128, "cw20"),
+        },
+    );
+
+    // No raffles can be created when the contract is locked
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::ToggleLock { lock: true },
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("nft", &[]),
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "creator".to_string(),
+            token_id: "token_id".to_string(),
+            msg: create_raffle_msg(AssetInfo::cw721("nft", "token_id")),
+        }),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::ContractIsLocked {});
+}
+
+#[test]
+fn test_multiple_ticket_currencies() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+
+    let create_raffle_msg = |additional_ticket_prices: Vec<AssetInfo>| ExecuteMsg::CreateRaffle {
+        owner: None,
+        assets: vec![AssetInfo::cw721("nft", "token_id")],
+        raffle_options: RaffleOptionsMsg::default(),
+        raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+        additional_ticket_prices: Some(additional_ticket_prices),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_raffle_msg(vec![AssetInfo::coin(5u128, "uluna")]),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::DuplicateTicketCurrency {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_raffle_msg(vec![AssetInfo::cw20(50000u128, "address")]),
+    )
+    .unwrap();
+
+    // Tickets can be bought in any of the accepted currencies, at the price of that currency
+    let err = buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uusd"), 0u64, None).unwrap_err();
+    assert_error(
+        err,
+        ContractError::PaiementNotSufficient {
+            assets_wanted: AssetInfo::coin(10000u128, "uluna"),
+            assets_received: AssetInfo::coin(10000u128, "uusd"),
+        },
+    );
+    let err = buy_ticket_cw20(deps.as_mut(), 0, "first", 10000u128, "address", 0u64).unwrap_err();
+    assert_error(
+        err,
+        ContractError::PaiementNotSufficient {
+            assets_wanted: AssetInfo::cw20(50000u128, "address"),
+            assets_received: AssetInfo::cw20(10000u128, "address"),
+        },
+    );
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(20000, "uluna"), 0u64, Some(2)).unwrap();
+    buy_ticket_cw20(deps.as_mut(), 0, "second", 50000u128, "address", 0u64).unwrap();
+    buy_ticket_cw20(deps.as_mut(), 0, "third", 50000u128, "address", 0u64).unwrap();
+
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    assert_eq!(raffle_info.number_of_tickets, 4);
+    assert_eq!(
+        raffle_info.collected_funds,
+        vec![
+            AssetInfo::coin(20000u128, "uluna"),
+            AssetInfo::cw20(100000u128, "address")
+        ]
+    );
+
+    // The funds of each currency are split separately
+    let mut raffle_info = raffle_info;
+    raffle_info.randomness = Some(Randomness {
+        randomness: [0; 32],
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    assert_eq!(
+        response.messages[1..],
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "rand_provider".to_string(),
+                amount: coins(2u128, "uluna"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(4u128, "uluna"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(19994u128, "uluna"),
+            }),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: "rand_provider".to_string(),
+                        amount: Uint128::from(10u128)
+                    },
+                    "address".to_string()
+                )
+                .unwrap()
+            ),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: "creator".to_string(),
+                        amount: Uint128::from(20u128)
+                    },
+                    "address".to_string()
+                )
+                .unwrap()
+            ),
+            SubMsg::new(
+                into_cosmos_msg(
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: "creator".to_string(),
+                        amount: Uint128::from(99970u128)
+                    },
+                    "address".to_string()
+                )
+                .unwrap()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_transfer_tickets() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier
+        .with_owner_of(&[(&"nft - token_id".to_string(), &"creator".to_string())]);
+    init_helper(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CreateRaffle {
+            owner: None,
+            assets: vec![AssetInfo::cw721("nft", "token_id")],
+            raffle_options: RaffleOptionsMsg {
+                max_ticket_per_address: Some(3),
+                min_ticket_number: Some(10),
+                ..Default::default()
+            },
+            raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
+        },
+    )
+    .unwrap();
+
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(20000, "uluna"), 0u64, Some(2)).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "second", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+
+    let transfer_msg = |recipient: &str, count: u32| ExecuteMsg::TransferTickets {
+        raffle_id: 0,
+        recipient: recipient.to_string(),
+        count,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("first", &[]),
+        transfer_msg("first", 1),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongTicketTransfer {});
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("second", &[]),
+        transfer_msg("third", 2),
+    )
+    .unwrap_err();
+    assert_error(
+        err,
+        ContractError::NotEnoughTickets {
+            owned: 1,
+            requested: 2,
+        },
+    );
+
+    // The last tickets of the sender are transferred, splitting ranges if needed
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("first", &[]),
+        transfer_msg("third", 2),
+    )
+    .unwrap();
+    let owners: Vec<String> = (0..4)
+        .map(|ticket_id| get_ticket_owner(&deps.storage, 0, ticket_id).unwrap().to_string())
+        .collect();
+    assert_eq!(owners, vec!["first", "third", "second", "third"]);
+    let first = deps.api.addr_validate("first").unwrap();
+    let third = deps.api.addr_validate("third").unwrap();
+    assert_eq!(USER_TICKETS.load(&deps.storage, (&first, 0)).unwrap(), 1);
+    assert_eq!(USER_TICKETS.load(&deps.storage, (&third, 0)).unwrap(), 2);
+
+    // The recipient can't hold more than max_ticket_per_address tickets
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("second", &[]),
+        transfer_msg("third", 1),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("first", &[]),
+        transfer_msg("third", 1),
+    )
+    .unwrap_err();
+    assert_error(
+        err,
+        ContractError::TooMuchTicketsForUser {
+            max: 3,
+            nb_before: 3,
+            nb_after: 4,
+        },
+    );
+
+    // Tickets can't be transferred once the raffle is closed
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(1000u64);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("third", &[]),
+        transfer_msg("first", 1),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::CantBuyTickets {});
+
+    // The refunds follow the tickets
+    let response = execute(
+        deps.as_mut(),
+        env,
+        mock_info("third", &[]),
+        ExecuteMsg::ClaimRefund { raffle_id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "third".to_string(),
+            amount: coins(30000u128, "uluna"),
+        })]
+    );
+}
+
+#[test]
+fn test_raffle_series() {
+    let mut deps = mock_querier_dependencies(&[]);
+    init_helper(deps.as_mut());
+
+    let create_series_msg = |interval: u64| ExecuteMsg::CreateRaffleSeries {
+        owner: None,
+        round_assets: vec![vec![AssetInfo::cw1155("nft", "token_id", 10u128)]; 4],
+        raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+        additional_ticket_prices: None,
+        raffle_options: RaffleOptionsMsg {
+            raffle_duration: Some(100),
+            ..Default::default()
+        },
+        interval,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_series_msg(50),
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::WrongSeriesInterval {});
+
+    // The whole cw1155 stack is deposited at once and the first round starts
+    let response = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        create_series_msg(2000),
+    )
+    .unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(
+            into_cosmos_msg(
+                Cw1155ExecuteMsg::SendFrom {
+                    from: "creator".to_string(),
+                    to: MOCK_CONTRACT_ADDR.to_string(),
+                    token_id: "token_id".to_string(),
+                    value: Uint128::from(40u128),
+                    msg: None,
+                },
+                "nft"
+            )
+            .unwrap()
+        )]
+    );
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    assert_eq!(raffle_info.series_id, Some(0));
+    assert_eq!(
+        raffle_info.assets,
+        vec![AssetInfo::cw1155("nft", "token_id", 10u128)]
+    );
+
+    // Claiming a round starts the next one, at its scheduled time
+    let mut raffle_info = raffle_info;
+    raffle_info.randomness = Some(Randomness {
+        randomness: [0; 32],
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    assert!(response
+        .attributes
+        .contains(&attr("next_raffle_id", "1")));
+    let raffle_info = RAFFLE_INFO.load(&deps.storage, 1).unwrap();
+    assert_eq!(
+        raffle_info.raffle_options.raffle_start_timestamp,
+        mock_env().block.time.plus_seconds(2000)
+    );
+
+    // The following round can only be started by anyone once its scheduled time has come
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(1000);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::StartSeriesRound { series_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::SeriesRoundNotScheduled {});
+    env.block.time = env.block.time.plus_seconds(3000);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::StartSeriesRound { series_id: 0 },
+    )
+    .unwrap();
+
+    let response = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::RaffleSeries { series_id: 0 },
+    )
+    .unwrap();
+    let series = from_binary::<RaffleSeriesResponse>(&response)
+        .unwrap()
+        .series_info;
+    assert_eq!(series.raffle_ids, vec![0, 1, 2]);
+    assert_eq!(
+        series.remaining_rounds,
+        vec![vec![AssetInfo::cw1155("nft", "token_id", 10u128)]]
+    );
+
+    // Cancelling the series gives the remaining inventory back
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::CancelRaffleSeries { series_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::Unauthorized {});
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CancelRaffleSeries { series_id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        response.messages,
+        vec![SubMsg::new(
+            into_cosmos_msg(
+                Cw1155ExecuteMsg::SendFrom {
+                    from: MOCK_CONTRACT_ADDR.to_string(),
+                    to: "creator".to_string(),
+                    token_id: "token_id".to_string(),
+                    value: Uint128::from(10u128),
+                    msg: None,
+                },
+                "nft"
+            )
+            .unwrap()
+        )]
+    );
+    env.block.time = env.block.time.plus_seconds(2000);
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::StartSeriesRound { series_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::SeriesInventoryEmpty {});
+}
+
+#[test]
+fn test_raffle_series_next_round_failure() {
+    let mut deps = mock_querier_dependencies(&[]);
+    init_helper(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CreateRaffleSeries {
+            owner: None,
+            round_assets: vec![vec![AssetInfo::cw1155("nft", "token_id", 10u128)]; 2],
+            raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
+            raffle_options: RaffleOptionsMsg {
+                raffle_duration: Some(100),
+                ..Default::default()
+            },
+            interval: 2000,
+        },
+    )
+    .unwrap();
+    let mut raffle_info = RAFFLE_INFO.load(&deps.storage, 0).unwrap();
+    raffle_info.randomness = Some(Randomness {
+        randomness: [0; 32],
+        randomness_round: 2098475u64,
+        randomness_owner: deps.api.addr_validate("rand_provider").unwrap(),
+        randomness_signature: Binary::default(),
+    });
+    RAFFLE_INFO
+        .save(deps.as_mut().storage, 0, &raffle_info)
+        .unwrap();
+
+    // The round is still claimed when the next one can't start
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::ToggleLock { lock: true },
+    )
+    .unwrap();
+    let response = claim_nft(deps.as_mut(), 0, 1000u64).unwrap();
+    assert_eq!(response.messages.len(), 1);
+    assert_eq!(
+        response.events,
+        vec![Event::new("series_round_not_started")
+            .add_attribute("series_id", "0")
+            .add_attribute("error", ContractError::ContractIsLocked {}.to_string())]
+    );
+    assert!(!response
+        .attributes
+        .iter()
+        .any(|attribute| attribute.key == "next_raffle_id"));
+    assert!(!RAFFLE_INFO.load(&deps.storage, 0).unwrap().winners.is_empty());
+
+    // Series rounds can't start while the contract is locked
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(2000);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::StartSeriesRound { series_id: 0 },
+    )
+    .unwrap_err();
+    assert_error(err, ContractError::ContractIsLocked {});
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::ToggleLock { lock: false },
+    )
+    .unwrap();
+    let response = execute(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::StartSeriesRound { series_id: 0 },
+    )
+    .unwrap();
+    assert!(response.attributes.contains(&attr("raffle_id", "1")));
+}
+
+#[test]
+fn test_raffle_stats_and_odds() {
+    let mut deps = mock_querier_dependencies(&[]);
+    deps.querier.with_owner_of(&[
+        (&"nft - token_id".to_string(), &"creator".to_string()),
+        (&"nft - token_id_2".to_string(), &"creator".to_string()),
+    ]);
+    init_helper(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::CreateRaffle {
+            owner: None,
+            assets: vec![
+                AssetInfo::cw721("nft", "token_id"),
+                AssetInfo::cw721("nft", "token_id_2"),
+            ],
+            raffle_options: RaffleOptionsMsg {
+                prize_slots: Some(vec![vec![0], vec![1]]),
+                ..Default::default()
+            },
+            raffle_ticket_price: AssetInfo::coin(10000u128, "uluna"),
+            additional_ticket_prices: None,
+        },
+    )
+    .unwrap();
+
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(20000, "uluna"), 0u64, Some(2)).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "second", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "third", coin(10000, "uluna"), 0u64, None).unwrap();
+    buy_ticket_coin(deps.as_mut(), 0, "first", coin(10000, "uluna"), 0u64, None).unwrap();
+
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::RaffleStats { raffle_id: 0 }).unwrap();
+    assert_eq!(
+        from_binary::<RaffleStatsResponse>(&response).unwrap(),
+        RaffleStatsResponse {
+            raffle_id: 0,
+            unique_participants: 3,
+            tickets_sold: 5,
+            collected_funds: vec![AssetInfo::coin(50000u128, "uluna")],
+            owner_payout: vec![AssetInfo::coin(49985u128, "uluna")],
+            treasury_payout: vec![AssetInfo::coin(10u128, "uluna")],
+            randomness_payout: vec![AssetInfo::coin(5u128, "uluna")],
+        }
+    );
+
+    // Two different tickets win the two prize slots
+    let user_odds = |deps: Deps, address: &str| {
+        from_binary::<UserOddsResponse>(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::UserOdds {
+                    raffle_id: 0,
+                    address: address.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let odds = user_odds(deps.as_ref(), "first");
+    assert_eq!(odds.ticket_count, 3);
+    assert_eq!(odds.win_probability, Decimal::from_str("0.9").unwrap());
+    assert_eq!(
+        user_odds(deps.as_ref(), "second").win_probability,
+        Decimal::from_str("0.4").unwrap()
+    );
+    assert_eq!(
+        user_odds(deps.as_ref(), "fourth").win_probability,
+        Decimal::zero()
+    );
+
+    // Participants are counted by address holding tickets
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("second", &[]),
+        ExecuteMsg::TransferTickets {
+            raffle_id: 0,
+            recipient: "third".to_string(),
+            count: 1,
+        },
+    )
+    .unwrap();
+    let response = query(deps.as_ref(), mock_env(), QueryMsg::RaffleStats { raffle_id: 0 }).unwrap();
+    assert_eq!(
+        from_binary::<RaffleStatsResponse>(&response)
+            .unwrap()
+            .unique_participants,
+        2
+    );
+}
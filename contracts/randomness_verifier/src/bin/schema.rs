@@ -1,11 +1,10 @@
 use cosmwasm_schema::write_api;
 
-use raffles_export::msg::{VerifierExecuteMsg, VerifierQueryMsg};
-use randomness_verifier::contract::EmptyMsg;
+use raffles_export::msg::{VerifierExecuteMsg, VerifierInstantiateMsg, VerifierQueryMsg};
 
 fn main() {
     write_api! {
-        instantiate: EmptyMsg,
+        instantiate: VerifierInstantiateMsg,
         execute: VerifierExecuteMsg,
         query: VerifierQueryMsg,
     }
@@ -1,95 +1,195 @@
-use cosmwasm_schema::cw_serde;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError,
+    StdResult,
 };
 
-use drand_verify::{derive_randomness, g1_from_variable, verify};
-use raffles_export::msg::{DrandRandomness, VerifierExecuteMsg};
+use drand_verify::derive_randomness;
+use raffles_export::msg::{
+    DrandRandomness, VerifierExecuteMsg, VerifierInstantiateMsg, VerifierQueryMsg,
+    VerifyRandomnessResponse, VerifyResponse,
+};
+use raffles_export::state::BeaconNetwork;
 
-#[cw_serde]
-pub struct EmptyMsg {}
+use crate::schemes::verify_beacon;
+use crate::state::{BEACONS, OWNER};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: EmptyMsg,
+    info: MessageInfo,
+    msg: VerifierInstantiateMsg,
 ) -> StdResult<Response> {
-    // store token info
+    let owner = deps
+        .api
+        .addr_validate(&msg.owner.unwrap_or_else(|| info.sender.to_string()))?;
+    OWNER.save(deps.storage, &owner)?;
+    for beacon in msg.beacons {
+        beacon.validate_period()?;
+        BEACONS.save(deps.storage, &beacon.chain_hash.clone(), &beacon)?;
+    }
+
     Ok(Response::default().add_attribute("fee_contract", "randomness_verifier"))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: VerifierExecuteMsg,
 ) -> StdResult<Response> {
     match msg {
         VerifierExecuteMsg::Verify {
             randomness,
-            pubkey,
+            chain_hash,
             raffle_id,
             owner,
-        } => execute_verify(randomness, pubkey, raffle_id, owner),
+        } => execute_verify(deps, randomness, chain_hash, raffle_id, owner),
+        VerifierExecuteMsg::SetBeacon { beacon } => execute_set_beacon(deps, info, beacon),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(_deps: Deps, _env: Env, _msg: EmptyMsg) -> StdResult<Binary> {
-    Err(StdError::generic_err("No queries"))
+pub fn query(deps: Deps, _env: Env, msg: VerifierQueryMsg) -> StdResult<Binary> {
+    match msg {
+        VerifierQueryMsg::Beacon { chain_hash } => to_binary(&load_beacon(deps, &chain_hash)?),
+        VerifierQueryMsg::VerifyRandomness {
+            randomness,
+            chain_hash,
+        } => to_binary(&query_verify_randomness(deps, randomness, chain_hash)?),
+    }
+}
+
+fn load_beacon(deps: Deps, chain_hash: &str) -> StdResult<BeaconNetwork> {
+    BEACONS
+        .load(deps.storage, chain_hash)
+        .map_err(|_| StdError::generic_err(format!("Unknown beacon network {}", chain_hash)))
 }
 
 pub fn execute_verify(
+    deps: DepsMut,
     randomness: DrandRandomness,
-    pubkey: Binary,
+    chain_hash: String,
     raffle_id: u64,
     owner: String,
 ) -> StdResult<Response> {
-    let pk = g1_from_variable(&pubkey).map_err(|_| StdError::generic_err("Invalid Public Key"))?;
-    let valid = verify(
-        &pk,
-        randomness.round,
-        randomness.previous_signature.as_slice(),
-        randomness.signature.as_slice(),
-    )
-    .unwrap_or(false);
-
-    if !valid {
+    let beacon = load_beacon(deps.as_ref(), &chain_hash)?;
+    if !verify_beacon(&beacon.scheme, &beacon.public_key, &randomness)? {
         return Err(StdError::generic_err("Invalid Signature"));
     }
 
-    let randomness_result = derive_randomness(&randomness.signature);
+    let verify_response = VerifyResponse {
+        raffle_id,
+        owner,
+        round: randomness.round,
+        randomness: Binary::from(derive_randomness(&randomness.signature)),
+        signature: randomness.signature,
+    };
 
     Ok(Response::new()
-        .add_attribute("round", randomness.round.to_string())
-        .add_attribute("randomness", Binary::from(randomness_result).to_string())
-        .add_attribute("raffle_id", raffle_id.to_string())
-        .add_attribute("owner", owner))
+        .add_attribute("round", verify_response.round.to_string())
+        .add_attribute("randomness", verify_response.randomness.to_string())
+        .add_attribute("raffle_id", verify_response.raffle_id.to_string())
+        .add_attribute("owner", verify_response.owner.clone())
+        .add_attribute("signature", verify_response.signature.to_string())
+        .set_data(to_binary(&verify_response)?))
+}
+
+/// Checks a beacon signature without saving anything
+/// Malformed signatures are reported as invalid, only unknown networks return an error
+pub fn query_verify_randomness(
+    deps: Deps,
+    randomness: DrandRandomness,
+    chain_hash: String,
+) -> StdResult<VerifyRandomnessResponse> {
+    let beacon = load_beacon(deps, &chain_hash)?;
+    let is_valid = verify_beacon(&beacon.scheme, &beacon.public_key, &randomness).unwrap_or(false);
+
+    Ok(VerifyRandomnessResponse {
+        is_valid,
+        randomness: is_valid.then(|| Binary::from(derive_randomness(&randomness.signature))),
+    })
+}
+
+/// Registers a drand beacon network, or replaces the one with the same chain hash
+/// Owner only action
+pub fn execute_set_beacon(
+    deps: DepsMut,
+    info: MessageInfo,
+    beacon: BeaconNetwork,
+) -> StdResult<Response> {
+    if OWNER.load(deps.storage)? != info.sender {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    beacon.validate_period()?;
+    BEACONS.save(deps.storage, &beacon.chain_hash.clone(), &beacon)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_beacon")
+        .add_attribute("chain_hash", beacon.chain_hash))
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::Timestamp;
     extern crate rustc_serialize as serialize;
 
     use raffles_export::msg::VerifierExecuteMsg;
-    use serialize::base64::{self, ToBase64};
+    use raffles_export::state::{
+        DrandScheme, DRAND_MAINNET_CHAIN_HASH, DRAND_MAINNET_GENESIS_TIME, DRAND_MAINNET_PERIOD,
+    };
     use serialize::hex::FromHex;
     const HEX_PUBKEY: &str = "868f005eb8e6e4ca0a47c8a77ceaa5309a47978a7c71bc5cce96366b5d7a569937c529eeda66c7293784a9402801af31";
+    const QUICKNET_CHAIN_HASH: &str =
+        "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971";
+    const QUICKNET_HEX_PUBKEY: &str = "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a";
+    // A test network using the pedersen-bls-unchained scheme, with a known secret key
+    const UNCHAINED_CHAIN_HASH: &str = "unchained";
+    const UNCHAINED_HEX_PUBKEY: &str = "b6fbfd0008e14285df5ed9fdd810cc91a77fa125f92f1e208a458dc35d9777552c88bbcda4adba1d33b7a5da551de837";
+
+    fn hex_binary(hex: &str) -> Binary {
+        Binary::from(hex.from_hex().unwrap())
+    }
 
     fn init_helper(deps: DepsMut) {
-        let instantiate_msg = EmptyMsg {};
+        let instantiate_msg = VerifierInstantiateMsg {
+            owner: None,
+            beacons: vec![
+                BeaconNetwork {
+                    chain_hash: DRAND_MAINNET_CHAIN_HASH.to_string(),
+                    scheme: DrandScheme::PedersenBlsChained,
+                    public_key: hex_binary(HEX_PUBKEY),
+                    genesis_time: Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME),
+                    period: DRAND_MAINNET_PERIOD,
+                },
+                BeaconNetwork {
+                    chain_hash: QUICKNET_CHAIN_HASH.to_string(),
+                    scheme: DrandScheme::BlsUnchainedG1Rfc9380,
+                    public_key: hex_binary(QUICKNET_HEX_PUBKEY),
+                    genesis_time: Timestamp::from_seconds(1692803367),
+                    period: 3,
+                },
+            ],
+        };
         let info = mock_info("creator", &[]);
         let env = mock_env();
 
         instantiate(deps, env, info, instantiate_msg).unwrap();
     }
 
+    fn verify_msg(chain_hash: &str, randomness: DrandRandomness) -> VerifierExecuteMsg {
+        VerifierExecuteMsg::Verify {
+            randomness,
+            chain_hash: chain_hash.to_string(),
+            raffle_id: 0u64,
+            owner: "anyone".to_string(),
+        }
+    }
+
     #[test]
     fn verify() {
         let mut deps = mock_dependencies();
@@ -99,26 +199,15 @@ pub mod tests {
 
         let randomness = DrandRandomness{
             round:2098475,
-            signature:Binary::from_base64(&"84bcb438f4505ef5282804c3d98a76b536970f9755004ea103fc15183c1b314a1e95582cb4dbaf3330272c72fe5675550ac6718ef53dfe18fca65c37f223a7dfbc1920a9c32dbf01a227290293a67fa4682dd266f717f5078829d83912926649".from_hex().unwrap().to_base64(base64::STANDARD)).unwrap(),
-            previous_signature:Binary::from_base64(&"b49ee4089fc510300b38d75ebba84576097bed61c171574acf2557f636c7144b471b57e18e5b0c3f8774e194344931c011873149d0db51fc70d22448bfc264d230be7ed6fcd3eb3b61fdc877d657dfa0d8ecaea6c1fa35f90bc84e88c1af17d4".from_hex().unwrap().to_base64(base64::STANDARD)).unwrap(),
+            signature: hex_binary("84bcb438f4505ef5282804c3d98a76b536970f9755004ea103fc15183c1b314a1e95582cb4dbaf3330272c72fe5675550ac6718ef53dfe18fca65c37f223a7dfbc1920a9c32dbf01a227290293a67fa4682dd266f717f5078829d83912926649"),
+            previous_signature: hex_binary("b49ee4089fc510300b38d75ebba84576097bed61c171574acf2557f636c7144b471b57e18e5b0c3f8774e194344931c011873149d0db51fc70d22448bfc264d230be7ed6fcd3eb3b61fdc877d657dfa0d8ecaea6c1fa35f90bc84e88c1af17d4"),
         };
 
-        let pubkey =
-            Binary::from_base64(&HEX_PUBKEY.from_hex().unwrap().to_base64(base64::STANDARD))
-                .unwrap();
-
-        let raffle_id = 0u64;
-        let owner = "anyone".to_string();
         let response = execute(
             deps.as_mut(),
             env,
             info,
-            VerifierExecuteMsg::Verify {
-                randomness,
-                pubkey,
-                raffle_id,
-                owner,
-            },
+            verify_msg(DRAND_MAINNET_CHAIN_HASH, randomness),
         )
         .unwrap();
         assert_eq!(
@@ -128,6 +217,186 @@ pub mod tests {
                 .add_attribute("randomness", "iVgPamOa3WyQ3PPSIuNUFfidnuLNbvb8TyMTTN/6XR4=")
                 .add_attribute("raffle_id", 0u128.to_string())
                 .add_attribute("owner", "anyone")
+                .add_attribute("signature", "hLy0OPRQXvUoKATD2Yp2tTaXD5dVAE6hA/wVGDwbMUoelVgstNuvMzAnLHL+VnVVCsZxjvU9/hj8plw38iOn37wZIKnDLb8BoicpApOmf6RoLdJm9xf1B4gp2DkSkmZJ")
+                .set_data(
+                    to_binary(&VerifyResponse {
+                        raffle_id: 0,
+                        owner: "anyone".to_string(),
+                        round: 2098475,
+                        randomness: Binary::from_base64("iVgPamOa3WyQ3PPSIuNUFfidnuLNbvb8TyMTTN/6XR4=")
+                            .unwrap(),
+                        signature: hex_binary("84bcb438f4505ef5282804c3d98a76b536970f9755004ea103fc15183c1b314a1e95582cb4dbaf3330272c72fe5675550ac6718ef53dfe18fca65c37f223a7dfbc1920a9c32dbf01a227290293a67fa4682dd266f717f5078829d83912926649"),
+                    })
+                    .unwrap()
+                )
         );
     }
+
+    #[test]
+    fn query_verify_randomness() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        let randomness = DrandRandomness {
+            round: 1000,
+            previous_signature: Binary::default(),
+            signature: hex_binary("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39"),
+        };
+        let verify_query = |randomness: DrandRandomness, chain_hash: &str| {
+            query(
+                deps.as_ref(),
+                mock_env(),
+                VerifierQueryMsg::VerifyRandomness {
+                    randomness,
+                    chain_hash: chain_hash.to_string(),
+                },
+            )
+            .map(|response| cosmwasm_std::from_binary::<VerifyRandomnessResponse>(&response).unwrap())
+        };
+
+        assert_eq!(
+            verify_query(randomness.clone(), QUICKNET_CHAIN_HASH).unwrap(),
+            VerifyRandomnessResponse {
+                is_valid: true,
+                randomness: Some(Binary::from(derive_randomness(&randomness.signature))),
+            }
+        );
+        assert_eq!(
+            verify_query(
+                DrandRandomness {
+                    round: 1001,
+                    ..randomness.clone()
+                },
+                QUICKNET_CHAIN_HASH
+            )
+            .unwrap(),
+            VerifyRandomnessResponse {
+                is_valid: false,
+                randomness: None,
+            }
+        );
+        // Malformed signatures are invalid too
+        assert!(
+            !verify_query(randomness.clone(), DRAND_MAINNET_CHAIN_HASH)
+                .unwrap()
+                .is_valid
+        );
+        verify_query(randomness, UNCHAINED_CHAIN_HASH).unwrap_err();
+    }
+
+    #[test]
+    fn verify_quicknet() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        // https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/1000
+        let randomness = DrandRandomness {
+            round: 1000,
+            previous_signature: Binary::default(),
+            signature: hex_binary("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39"),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            verify_msg(QUICKNET_CHAIN_HASH, randomness.clone()),
+        )
+        .unwrap();
+
+        // The signature is bound to its round and its network
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            verify_msg(
+                QUICKNET_CHAIN_HASH,
+                DrandRandomness {
+                    round: 1001,
+                    ..randomness.clone()
+                },
+            ),
+        )
+        .unwrap_err();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            verify_msg(DRAND_MAINNET_CHAIN_HASH, randomness),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn verify_unchained() {
+        let mut deps = mock_dependencies();
+        init_helper(deps.as_mut());
+
+        let randomness = DrandRandomness {
+            round: 1000,
+            previous_signature: Binary::default(),
+            signature: hex_binary("85471a0182a865a3f8631ca787d307ff5460a1edacfd8876c366d5962989705506ba7d7dfe9a1145fb42d3ae9e71a99c0033105f9b91a01f3440d7cb7b8ebd84b5c6deeec29bc65550ee19a2e89125489d959bbb4c7da56dc7d1256ed18b0224"),
+        };
+
+        // The network has to be registered first, by the owner only
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            verify_msg(UNCHAINED_CHAIN_HASH, randomness.clone()),
+        )
+        .unwrap_err();
+        let mut beacon = BeaconNetwork {
+            chain_hash: UNCHAINED_CHAIN_HASH.to_string(),
+            scheme: DrandScheme::PedersenBlsUnchained,
+            public_key: hex_binary(UNCHAINED_HEX_PUBKEY),
+            genesis_time: Timestamp::from_seconds(1_600_000_000),
+            period: 0,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            VerifierExecuteMsg::SetBeacon {
+                beacon: beacon.clone(),
+            },
+        )
+        .unwrap_err();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            VerifierExecuteMsg::SetBeacon {
+                beacon: beacon.clone(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("The drand period should be positive"));
+        beacon.period = 30;
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            VerifierExecuteMsg::SetBeacon {
+                beacon: beacon.clone(),
+            },
+        )
+        .unwrap();
+        let response = query(
+            deps.as_ref(),
+            mock_env(),
+            VerifierQueryMsg::Beacon {
+                chain_hash: UNCHAINED_CHAIN_HASH.to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(cosmwasm_std::from_binary::<BeaconNetwork>(&response).unwrap(), beacon);
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("caller", &[]),
+            verify_msg(UNCHAINED_CHAIN_HASH, randomness),
+        )
+        .unwrap();
+    }
 }
@@ -1 +1,3 @@
 pub mod contract;
+pub mod schemes;
+pub mod state;
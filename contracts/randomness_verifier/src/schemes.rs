@@ -0,0 +1,74 @@
+use fff::Field;
+use groupy::{CurveAffine, CurveProjective};
+use paired::bls12_381::{Bls12, Fq12, G1Affine, G2Affine, G1};
+use paired::{Engine, ExpandMsgXmd, HashToCurve, PairingCurveAffine};
+use sha2::{Digest, Sha256};
+
+use cosmwasm_std::{StdError, StdResult};
+use drand_verify::{g1_from_variable, g2_from_variable, verify};
+use raffles_export::msg::DrandRandomness;
+use raffles_export::state::DrandScheme;
+
+const G1_DOMAIN: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// Verifies a drand beacon against the public key of its network, using the signature scheme of the network
+/// The chained schemes sign the round and the previous signature, the unchained schemes only sign the round
+pub fn verify_beacon(
+    scheme: &DrandScheme,
+    public_key: &[u8],
+    randomness: &DrandRandomness,
+) -> StdResult<bool> {
+    match scheme {
+        DrandScheme::PedersenBlsChained | DrandScheme::PedersenBlsUnchained => {
+            let pk = g1_from_variable(public_key)
+                .map_err(|_| StdError::generic_err("Invalid Public Key"))?;
+            let previous_signature = match scheme {
+                DrandScheme::PedersenBlsChained => randomness.previous_signature.as_slice(),
+                _ => &[],
+            };
+            Ok(verify(
+                &pk,
+                randomness.round,
+                previous_signature,
+                randomness.signature.as_slice(),
+            )
+            .unwrap_or(false))
+        }
+        DrandScheme::BlsUnchainedG1Rfc9380 => {
+            let pk = g2_from_variable(public_key)
+                .map_err(|_| StdError::generic_err("Invalid Public Key"))?;
+            let signature = match g1_from_variable(randomness.signature.as_slice()) {
+                Ok(signature) => signature,
+                Err(_) => return Ok(false),
+            };
+            let message = Sha256::digest(&randomness.round.to_be_bytes());
+            let message_on_g1 =
+                <G1 as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(message, G1_DOMAIN)
+                    .into_affine();
+
+            // e(signature, g2) == e(H(message), pk)
+            Ok(fast_pairing_equality(
+                &signature,
+                &G2Affine::one(),
+                &message_on_g1,
+                &pk,
+            ))
+        }
+    }
+}
+
+/// Checks if e(p, q) == e(r, s)
+fn fast_pairing_equality(p: &G1Affine, q: &G2Affine, r: &G1Affine, s: &G2Affine) -> bool {
+    fn e_prime(p: &G1Affine, q: &G2Affine) -> Fq12 {
+        Bls12::miller_loop([(&(p.prepare()), &(q.prepare()))].iter())
+    }
+
+    let mut minus_p = *p;
+    minus_p.negate();
+    let mut tmp = e_prime(&minus_p, q);
+    tmp.mul_assign(&e_prime(r, s));
+    match Bls12::final_exponentiation(&tmp) {
+        Some(value) => value == Fq12::one(),
+        None => false,
+    }
+}
@@ -0,0 +1,8 @@
+use cosmwasm_std::Addr;
+use cw_storage_plus::{Item, Map};
+
+use raffles_export::state::BeaconNetwork;
+
+pub const OWNER: Item<Addr> = Item::new("owner");
+/// The drand beacon networks signatures can be verified for, indexed by chain hash
+pub const BEACONS: Map<&str, BeaconNetwork> = Map::new("beacons");
@@ -1,9 +1,12 @@
-use cosmwasm_std::{StdError, StdResult, Uint128};
+use cosmwasm_std::{StdError, StdResult, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
 use fee_contract_export::state::FeeType;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use utils::msg::is_valid_name;
 
+use crate::state::{AssetInfo, VestingSchedule};
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct MigrateMsg {}
 
@@ -12,6 +15,7 @@ pub struct InstantiateMsg {
     pub name: String,
     pub owner: Option<String>,
     pub treasury: String,
+    pub staking_token: Option<String>,
 }
 
 impl InstantiateMsg {
@@ -34,11 +38,15 @@ pub enum ExecuteMsg {
         treasury: Option<String>,
         projects_allocation_for_assets_fee: Option<Uint128>,
         projects_allocation_for_funds_fee: Option<Uint128>,
+        staking_token: Option<String>,
+        stakers_allocation: Option<Uint128>,
+        unbonding_period: Option<u64>,
     },
     DepositFees {
         addresses: Vec<String>,
         fee_type: FeeType,
     },
+    Receive(Cw20ReceiveMsg),
     WithdrawFees {
         addresses: Vec<String>,
     },
@@ -46,6 +54,25 @@ pub enum ExecuteMsg {
         address: String,
         fee_address: String,
     },
+    // Only accepted through the Receive hook of the staking token
+    Stake {},
+    Unstake {
+        amount: Uint128,
+    },
+    WithdrawUnbonded {},
+    ClaimRewards {},
+    SetVestingSchedule {
+        address: String,
+        start: Option<Timestamp>,
+        cliff: u64,
+        duration: u64,
+    },
+    Claim {
+        address: String,
+    },
+    Revoke {
+        address: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -59,4 +86,37 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    StakerInfo {
+        address: String,
+    },
+    PendingRewards {
+        address: String,
+    },
+    TotalStaked {},
+    Vesting {
+        address: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AssetBalance {
+    pub asset: AssetInfo,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingAssetResponse {
+    pub asset: AssetInfo,
+    pub total: Uint128,
+    pub released: Uint128,
+    pub claimable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingResponse {
+    pub schedule: Option<VestingSchedule>,
+    pub balances: Vec<VestingAssetResponse>,
 }
@@ -1,6 +1,8 @@
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, StdError, StdResult, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -10,4 +12,87 @@ pub struct ContractInfo {
     pub treasury: Addr,
     pub projects_allocation_for_funds_fee: Uint128, // In 10th of percent
     pub projects_allocation_for_assets_fee: Uint128, // In 10th of percent
+    pub staking_token: Option<Addr>, // The token (ILIQ) stakers deposit to get a share of the fees
+    #[serde(default)]
+    pub stakers_allocation: Uint128, // In 10th of percent of the treasury allocation
+    #[serde(default)]
+    pub unbonding_period: u64, // In seconds
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct StakerInfo {
+    pub staked: Uint128,
+    pub unbonding: Vec<UnbondingInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct UnbondingInfo {
+    pub amount: Uint128,
+    pub release_at: Timestamp,
+}
+
+/// Linear vesting applied to the fees of a project
+/// Nothing is vested before the cliff, everything is vested after the duration (both from the start)
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingSchedule {
+    pub start: Timestamp,
+    pub cliff: u64,    // In seconds
+    pub duration: u64, // In seconds
+    pub revoked: bool, // Once revoked, the unvested funds were sent back to the treasury
+}
+
+impl VestingSchedule {
+    pub fn vested_amount(&self, total: Uint128, time: Timestamp) -> Uint128 {
+        let elapsed = time.seconds().saturating_sub(self.start.seconds());
+        if self.revoked || elapsed >= self.duration {
+            total
+        } else if elapsed < self.cliff {
+            Uint128::zero()
+        } else {
+            total.multiply_ratio(elapsed, self.duration)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingBalance {
+    pub total: Uint128,
+    pub released: Uint128,
+}
+
+/// Fungible asset types the fee distributor can hold
+/// Coin holds a native denom, Cw20 holds a cw20 contract address
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetInfo {
+    Coin(String),
+    Cw20(String),
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetInfo::Coin(x) => write!(f, "coin_{}", x),
+            AssetInfo::Cw20(x) => write!(f, "cw20_{}", x),
+        }
+    }
+}
+
+// Assets are saved in memory using their string representation
+impl FromStr for AssetInfo {
+    type Err = StdError;
+
+    fn from_str(string_rep: &str) -> StdResult<Self> {
+        if let Some(coin_name) = string_rep.strip_prefix("coin_") {
+            Ok(AssetInfo::Coin(coin_name.to_string()))
+        } else if let Some(cw20_name) = string_rep.strip_prefix("cw20_") {
+            Ok(AssetInfo::Cw20(cw20_name.to_string()))
+        } else {
+            Err(StdError::generic_err("Wrong asset info saved in memory"))
+        }
+    }
 }
@@ -1,6 +1,9 @@
-use crate::state::{BorrowerInfo, CollateralInfo, ContractInfo, LoanTerms, OfferInfo};
+use crate::state::{
+    BorrowerInfo, CollateralInfo, ContractInfo, LoanExtension, LoanTerms, OfferInfo,
+};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{StdError, StdResult, Decimal};
+pub use cw20::Cw20ReceiveMsg;
+use cosmwasm_std::{Decimal, StdError, StdResult, Timestamp, Uint128};
 use utils::msg::is_valid_name;
 use utils::state::AssetInfo;
 
@@ -36,6 +39,7 @@ impl InstantiateMsg {
 }
 /// This contract nevers holds any funds
 /// In case it does, it's that an error occured
+/// Messages that need funds can be sent with native tokens or through a cw20 `Send` (`Receive` hook)
 #[cw_serde]
 pub enum ExecuteMsg {
     //// We support both Cw721 and Cw1155
@@ -56,11 +60,29 @@ pub enum ExecuteMsg {
     },
 
     /// Make an offer to deposited collaterals
+    /// The offer can't be accepted after the optional expiration
     MakeOffer {
         borrower: String,
         loan_id: u64,
         terms: LoanTerms,
         comment: Option<String>,
+        expiration: Option<Timestamp>,
+    },
+    /// Offer new terms for a loan that already started
+    /// The principle pays back the current lender when the borrower accepts it
+    MakeRefinanceOffer {
+        borrower: String,
+        loan_id: u64,
+        terms: LoanTerms,
+        comment: Option<String>,
+    },
+    /// Make a standing offer against any token of a cw721 collection
+    /// The offer can be accepted by anyone holding a token of the collection until it expires
+    MakeCollectionOffer {
+        collection: String,
+        terms: LoanTerms,
+        expiration: Timestamp,
+        comment: Option<String>,
     },
     CancelOffer {
         global_offer_id: String,
@@ -71,17 +93,54 @@ pub enum ExecuteMsg {
     WithdrawRefusedOffer {
         global_offer_id: String,
     },
+    /// Sends the funds of an expired offer back to the lender, anyone can call it
+    RefundExpiredOffer {
+        global_offer_id: String,
+    },
     AcceptOffer {
         global_offer_id: String,
     },
+    AcceptRefinanceOffer {
+        global_offer_id: String,
+    },
+    /// Deposits the token as collateral and starts the loan with the collection offer terms
+    AcceptCollectionOffer {
+        global_offer_id: String,
+        token: AssetInfo,
+        comment: Option<String>,
+    },
     AcceptLoan {
         borrower: String,
         loan_id: u64,
         comment: Option<String>,
     },
+    /// Pays back the whole loan, the funds sent on top of what is due are given back to the borrower
     RepayBorrowedFunds {
         loan_id: u64,
     },
+    /// Pays back part of a loan, the collateral is given back once everything is paid
+    RepayPartial {
+        loan_id: u64,
+    },
+
+    /// Propose to extend a started loan, by the borrower or the lender
+    /// The loan duration and interest are updated only when the other party accepts the extension
+    /// Loans with a duration in seconds are extended by `extra_seconds`
+    ProposeExtension {
+        borrower: String,
+        loan_id: u64,
+        extra_blocks: u64,
+        extra_seconds: Option<u64>,
+        extra_interest: Uint128,
+    },
+    /// The accepted terms have to match the pending proposal, so it can't be replaced in the meantime
+    AcceptExtension {
+        borrower: String,
+        loan_id: u64,
+        extra_blocks: u64,
+        extra_seconds: Option<u64>,
+        extra_interest: Uint128,
+    },
 
     /// Used only when the loan can be paid back late
     WithdrawDefaultedLoan {
@@ -89,6 +148,9 @@ pub enum ExecuteMsg {
         loan_id: u64,
     },
 
+    /// Used to make offers, accept loans and repay them with cw20 tokens
+    Receive(Cw20ReceiveMsg),
+
     /// Internal state
     SetOwner {
         owner: String,
@@ -129,19 +191,33 @@ pub enum QueryMsg {
     #[returns(OfferResponse)]
     OfferInfo { global_offer_id: String },
 
+    /// Expired offers are filtered out unless `include_expired` is set
     #[returns(MultipleOffersResponse)]
     Offers {
         borrower: String,
         loan_id: u64,
         start_after: Option<String>,
         limit: Option<u32>,
+        include_expired: Option<bool>,
     },
     #[returns(MultipleOffersResponse)]
     LenderOffers {
         lender: String,
         start_after: Option<String>,
         limit: Option<u32>,
+        include_expired: Option<bool>,
     },
+
+    #[returns(MultipleOffersResponse)]
+    CollectionOffers {
+        collection: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        include_expired: Option<bool>,
+    },
+
+    #[returns(LoanExtensionsResponse)]
+    LoanExtensions { borrower: String, loan_id: u64 },
 }
 
 #[cw_serde]
@@ -149,6 +225,7 @@ pub struct CollateralResponse {
     pub borrower: String,
     pub loan_id: u64,
     pub collateral: CollateralInfo,
+    pub amount_due: Option<Uint128>, // What is still owed at the current block, for started loans
 }
 
 #[cw_serde]
@@ -174,3 +251,9 @@ pub struct MultipleOffersResponse {
     pub offers: Vec<OfferResponse>,
     pub next_offer: Option<String>,
 }
+
+#[cw_serde]
+pub struct LoanExtensionsResponse {
+    pub pending: Option<LoanExtension>,
+    pub history: Vec<LoanExtension>,
+}
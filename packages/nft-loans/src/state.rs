@@ -1,6 +1,10 @@
 use utils::state::OwnerStruct;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Coin, Timestamp, Uint128, Decimal};
+use cosmwasm_std::{
+    Addr, BlockInfo, Coin, Decimal, OverflowError, OverflowOperation, StdError, StdResult,
+    Timestamp, Uint128,
+};
+use serde::{Deserialize, Deserializer};
 
 use utils::state::AssetInfo;
 // We neep a map per user of all loans that are happening right now !
@@ -16,6 +20,10 @@ pub struct CollateralInfo {
     pub active_offer: Option<String>,
     pub start_block: Option<u64>,
     pub comment: Option<String>,
+    #[serde(default)]
+    pub repaid_amount: Uint128, // The principle and interest already paid back to the lender
+    #[serde(default)]
+    pub start_time: Option<Timestamp>, // Used for loans with a duration in seconds
 }
 
 impl Default for CollateralInfo {
@@ -29,6 +37,8 @@ impl Default for CollateralInfo {
             offer_amount: 0u64,
             active_offer: None,
             start_block: None,
+            repaid_amount: Uint128::zero(),
+            start_time: None,
         }
     }
 }
@@ -48,15 +58,153 @@ pub struct OfferInfo {
     pub terms: LoanTerms,
     pub state: OfferState,
     pub list_date: Timestamp,
-    pub deposited_funds: Option<Coin>,
+    #[serde(deserialize_with = "deserialize_optional_principle")]
+    pub deposited_funds: Option<AssetInfo>,
     pub comment: Option<String>,
+    #[serde(default)]
+    pub refinance: bool, // Refinance offers are made on started loans, to pay back the current lender
+    #[serde(default)]
+    pub collection: Option<String>, // Collection offers can be accepted against any token of the collection
+    #[serde(default)]
+    pub expiration: Option<Timestamp>, // The offer can't be accepted after this time
+}
+
+impl OfferInfo {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        self.expiration
+            .map(|expiration| block.time >= expiration)
+            .unwrap_or(false)
+    }
 }
 
 #[cw_serde]
 pub struct LoanTerms {
-    pub principle: Coin,
+    #[serde(deserialize_with = "deserialize_principle")]
+    pub principle: AssetInfo, // Native or cw20 tokens only
     pub interest: Uint128,
     pub duration_in_blocks: u64,
+    #[serde(default)]
+    pub installments: Option<InstallmentSchedule>,
+    #[serde(default)]
+    pub duration_in_seconds: Option<u64>, // Replaces `duration_in_blocks` when specified
+    #[serde(default)]
+    pub annual_rate: Option<Decimal>, // Interest accrued on the principle over time, on top of the fixed `interest`
+}
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 3600;
+pub const MAX_ANNUAL_RATE: Decimal = Decimal::raw(10_000_000_000_000_000_000); // 1000% a year
+
+/// The loan is paid back in equal installments, the n-th one being due `n * interval_in_blocks` blocks after the loan starts
+#[cw_serde]
+pub struct InstallmentSchedule {
+    pub number: u64,
+    pub interval_in_blocks: u64,
+}
+
+/// Loans created before cw20 principles were supported saved their principle as a native `Coin`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Principle {
+    Asset(AssetInfo),
+    Legacy(Coin),
+}
+
+impl From<Principle> for AssetInfo {
+    fn from(principle: Principle) -> Self {
+        match principle {
+            Principle::Asset(asset) => asset,
+            Principle::Legacy(coin) => AssetInfo::Coin(coin),
+        }
+    }
+}
+
+fn deserialize_principle<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AssetInfo, D::Error> {
+    Principle::deserialize(deserializer).map(AssetInfo::from)
+}
+
+fn deserialize_optional_principle<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<AssetInfo>, D::Error> {
+    Option::<Principle>::deserialize(deserializer).map(|principle| principle.map(AssetInfo::from))
+}
+
+impl LoanTerms {
+    /// The amount of tokens lent
+    pub fn principle_amount(&self) -> Uint128 {
+        match &self.principle {
+            AssetInfo::Coin(coin) => coin.amount,
+            AssetInfo::Cw20Coin(coin) => coin.amount,
+            _ => Uint128::zero(),
+        }
+    }
+
+    /// The principle and interest to pay back over the whole loan
+    pub fn total_due(&self) -> StdResult<Uint128> {
+        self.total_due_at(self.duration_in_seconds.unwrap_or_default())
+    }
+
+    /// The interest owed once `elapsed_seconds` seconds passed since the start of the loan
+    /// The annual rate accrues pro-rata and is capped at the full duration of the loan
+    pub fn interest_at(&self, elapsed_seconds: u64) -> StdResult<Uint128> {
+        match (self.annual_rate, self.duration_in_seconds) {
+            (Some(annual_rate), Some(duration)) => {
+                let accrual_time = Uint128::from(elapsed_seconds.min(duration));
+                let accrued_interest = self
+                    .principle_amount()
+                    .checked_multiply_ratio(
+                        annual_rate.atomics().checked_mul(accrual_time)?,
+                        Decimal::one()
+                            .atomics()
+                            .checked_mul(Uint128::from(SECONDS_PER_YEAR))?,
+                    )
+                    .map_err(|_| {
+                        OverflowError::new(
+                            OverflowOperation::Mul,
+                            self.principle_amount(),
+                            annual_rate,
+                        )
+                    })?;
+                Ok(self.interest.checked_add(accrued_interest)?)
+            }
+            _ => Ok(self.interest),
+        }
+    }
+
+    /// The principle and interest owed once `elapsed_seconds` seconds passed since the start of the loan
+    pub fn total_due_at(&self, elapsed_seconds: u64) -> StdResult<Uint128> {
+        self.principle_amount()
+            .checked_add(self.interest_at(elapsed_seconds)?)
+            .map_err(StdError::from)
+    }
+
+    /// The amount that should have been paid back once `elapsed_blocks` blocks passed since the start of the loan
+    /// Loans without installments are only due at the end of the loan
+    pub fn amount_due_after(&self, elapsed_blocks: u64) -> StdResult<Uint128> {
+        match &self.installments {
+            Some(schedule) if elapsed_blocks > 0 => {
+                let passed_deadlines =
+                    ((elapsed_blocks - 1) / schedule.interval_in_blocks.max(1)).min(schedule.number);
+                Ok(self
+                    .total_due()?
+                    .multiply_ratio(passed_deadlines, schedule.number.max(1)))
+            }
+            _ => Ok(Uint128::zero()),
+        }
+    }
+}
+
+/// Amendment of the duration and interest of a started loan
+/// It's proposed by one party of the loan and applied only when the other party accepts it
+#[cw_serde]
+pub struct LoanExtension {
+    pub global_offer_id: String,
+    pub proposer: Addr,
+    pub extra_blocks: u64,
+    #[serde(default)]
+    pub extra_seconds: u64,
+    pub extra_interest: Uint128,
+    pub proposal_block: u64,
+    pub accepted_block: Option<u64>,
 }
 
 #[cw_serde]
@@ -74,6 +222,7 @@ pub enum OfferState {
     Accepted,
     Refused,
     Cancelled,
+    Expired,
 }
 
 #[cw_serde]
@@ -1,15 +1,18 @@
 
+use cw1155::Cw1155ReceiveMsg;
 use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
 use anyhow::Result;
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{to_binary, Binary, CosmosMsg, StdError, StdResult, WasmMsg, Decimal};
+use cosmwasm_std::{to_binary, Addr, Binary, CosmosMsg, StdError, StdResult, WasmMsg, Decimal};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::state::ContractInfo;
 use crate::state::RaffleInfo;
+use crate::state::RaffleSeries;
 use crate::state::RaffleState;
-use crate::state::{AssetInfo, RaffleOptionsMsg};
+use crate::state::{AssetInfo, BeaconNetwork, RaffleOptionsMsg};
 
 fn is_valid_name(name: &str) -> bool {
     let bytes = name.as_bytes();
@@ -47,8 +50,10 @@ pub struct InstantiateMsg {
     pub raffle_fee: Option<Decimal>, 
     pub rand_fee: Option<Decimal>,   
     pub drand_url: Option<String>,
-    pub random_pubkey: String,
+    pub drand_chain_hash: Option<String>,
     pub verify_signature_contract: String,
+    pub randomness_bounty: Option<AssetInfo>,
+    pub reward_pool_fee: Option<Decimal>,
 }
 
 #[cw_serde]
@@ -69,7 +74,8 @@ impl InstantiateMsg {
 #[cw_serde]
 pub struct DrandRandomness {
     pub round: u64,
-    pub previous_signature: Binary,
+    #[serde(default)]
+    pub previous_signature: Binary, // Only used by the chained schemes
     pub signature: Binary,
 }
 
@@ -80,6 +86,7 @@ pub enum ExecuteMsg {
         assets: Vec<AssetInfo>,
         raffle_options: RaffleOptionsMsg,
         raffle_ticket_price: AssetInfo,
+        additional_ticket_prices: Option<Vec<AssetInfo>>,
     },
     CancelRaffle {
         raffle_id: u64,
@@ -87,6 +94,7 @@ pub enum ExecuteMsg {
     ModifyRaffle {
         raffle_id: u64,
         raffle_ticket_price: Option<AssetInfo>,
+        additional_ticket_prices: Option<Vec<AssetInfo>>,
         raffle_options: RaffleOptionsMsg,
     },
     BuyTicket {
@@ -94,10 +102,33 @@ pub enum ExecuteMsg {
         ticket_number: u32,
         sent_assets: AssetInfo,
     },
-    Receive(Cw20ReceiveMsg),
+    Receive(ReceiveMsg),
+    ReceiveNft(Cw721ReceiveMsg),
     ClaimNft {
         raffle_id: u64,
     },
+    ClaimRefund {
+        raffle_id: u64,
+    },
+    TransferTickets {
+        raffle_id: u64,
+        recipient: String,
+        count: u32,
+    },
+    CreateRaffleSeries {
+        owner: Option<String>,
+        round_assets: Vec<Vec<AssetInfo>>,
+        raffle_ticket_price: AssetInfo,
+        additional_ticket_prices: Option<Vec<AssetInfo>>,
+        raffle_options: RaffleOptionsMsg,
+        interval: u64,
+    },
+    StartSeriesRound {
+        series_id: u64,
+    },
+    CancelRaffleSeries {
+        series_id: u64,
+    },
     UpdateRandomness {
         raffle_id: u64,
         randomness: DrandRandomness,
@@ -107,13 +138,32 @@ pub enum ExecuteMsg {
     ToggleLock {
         lock: bool,
     },
-    ChangeParameter {
-        parameter: String,
-        value: String,
+    UpdateConfig {
+        owner: Option<String>,
+        fee_addr: Option<String>,
+        minimum_raffle_duration: Option<u64>,
+        minimum_raffle_timeout: Option<u64>,
+        max_participant_number: Option<u32>,
+        raffle_fee: Option<Decimal>,
+        rand_fee: Option<Decimal>,
+        drand_url: Option<String>,
+        verify_signature_contract: Option<String>,
+        drand_chain_hash: Option<String>,
+        randomness_bounty: Option<AssetInfo>,
+        reward_pool_fee: Option<Decimal>,
     },
     ClaimOwnership { }
 }
 
+/// The cw20 and cw1155 standards both use the `receive` hook name
+/// The token standard is deduced from the message fields
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum ReceiveMsg {
+    Cw20(Cw20ReceiveMsg),
+    Cw1155(Cw1155ReceiveMsg),
+}
+
 #[cw_serde]
 pub struct QueryFilters {
     pub states: Option<Vec<String>>,
@@ -143,27 +193,136 @@ pub enum QueryMsg {
     },
     #[returns(u32)]
     TicketNumber { owner: String, raffle_id: u64 },
+    #[returns(Vec<PrizeSlotResponse>)]
+    PrizeSlots { raffle_id: u64 },
+    #[returns(DrawProofResponse)]
+    DrawProof { raffle_id: u64 },
+    #[returns(RaffleSeriesResponse)]
+    RaffleSeries { series_id: u64 },
+    #[returns(RaffleStatsResponse)]
+    RaffleStats { raffle_id: u64 },
+    #[returns(UserOddsResponse)]
+    UserOdds { raffle_id: u64, address: String },
+    #[returns(RafflesAwaitingRandomnessResponse)]
+    RafflesAwaitingRandomness {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(Vec<AssetInfo>)]
+    RewardPool {},
+}
+
+#[cw_serde]
+pub struct VerifierInstantiateMsg {
+    pub owner: Option<String>,
+    pub beacons: Vec<BeaconNetwork>,
 }
 
 #[cw_serde]
 pub enum VerifierExecuteMsg {
     Verify {
         randomness: DrandRandomness,
-        pubkey: Binary,
+        chain_hash: String,
         raffle_id: u64,
         owner: String,
     },
+    SetBeacon {
+        beacon: BeaconNetwork,
+    },
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
-pub enum VerifierQueryMsg {}
+pub enum VerifierQueryMsg {
+    #[returns(BeaconNetwork)]
+    Beacon { chain_hash: String },
+    #[returns(VerifyRandomnessResponse)]
+    VerifyRandomness {
+        randomness: DrandRandomness,
+        chain_hash: String,
+    },
+}
+
+/// Data returned by the verifier after a successful `Verify` execution
+#[cw_serde]
+pub struct VerifyResponse {
+    pub raffle_id: u64,
+    pub owner: String,
+    pub round: u64,
+    pub randomness: Binary, // The 32 bytes of randomness derived from the signature
+    pub signature: Binary,
+}
+
+#[cw_serde]
+pub struct VerifyRandomnessResponse {
+    pub is_valid: bool,
+    pub randomness: Option<Binary>, // Only returned for valid signatures
+}
 
 #[cw_serde]
 pub struct RaffleResponse {
     pub raffle_id: u64,
     pub raffle_state: RaffleState,
     pub raffle_info: Option<RaffleInfo>,
+    pub min_randomness_round: Option<u64>, // The first drand round accepted as randomness for the raffle
+}
+
+#[cw_serde]
+pub struct RaffleSeriesResponse {
+    pub series_id: u64,
+    pub series_info: RaffleSeries,
+}
+
+#[cw_serde]
+pub struct RaffleStatsResponse {
+    pub raffle_id: u64,
+    pub unique_participants: u32,
+    pub tickets_sold: u32,
+    pub collected_funds: Vec<AssetInfo>,
+    // The payouts if the raffle ends with the current tickets, in each ticket currency
+    pub owner_payout: Vec<AssetInfo>,
+    pub treasury_payout: Vec<AssetInfo>, // Without the part of the fee kept in the randomness reward pool
+    pub randomness_payout: Vec<AssetInfo>,
+}
+
+#[cw_serde]
+pub struct UserOddsResponse {
+    pub raffle_id: u64,
+    pub address: Addr,
+    pub ticket_count: u32,
+    pub win_probability: Decimal, // The probability to win at least one prize slot with the current tickets
+}
+
+#[cw_serde]
+pub struct RafflesAwaitingRandomnessResponse {
+    pub raffle_ids: Vec<u64>, // Closed raffles with tickets sold, no randomness was provided for yet
+    pub bounty: Option<AssetInfo>, // The bounty paid for the first valid randomness, if the reward pool can cover it. Capped at the treasury fee of each raffle
+}
+
+#[cw_serde]
+pub struct PrizeSlotResponse {
+    pub slot: u32,
+    pub assets: Vec<AssetInfo>,
+    pub winner: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct TicketDraw {
+    pub slot: u32,
+    pub random_values: Vec<u32>, // All the values drawn from the generator, the last one is accepted
+    pub drawn_index: u32, // The index drawn among the tickets that didn't win yet
+    pub ticket_id: u32,
+    pub winner: Addr,
+}
+
+#[cw_serde]
+pub struct DrawProofResponse {
+    pub raffle_id: u64,
+    pub randomness_round: u64,
+    pub randomness_signature: Binary,
+    pub randomness: Binary,
+    pub seed: Binary, // The ChaCha seed : sha256(randomness)
+    pub draws: Vec<TicketDraw>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -1,9 +1,9 @@
 use std::convert::TryInto;
 use strum_macros;
 use anyhow::{Result, bail};
-use cosmwasm_std::{coin, Addr, Binary, Coin, Env, Timestamp, Uint128, Decimal, StdError};
+use cosmwasm_std::{coin, Addr, Binary, Coin, Env, Timestamp, Uint128, Decimal, StdError, StdResult};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use utils::state::OwnerStruct;
 
 /*
@@ -17,6 +17,61 @@ pub const MINIMUM_RAFFLE_DURATION: u64 = 1;
 pub const MINIMUM_RAFFLE_TIMEOUT: u64 = 120; // The raffle timeout is a least 2 minutes
 pub const DECIMAL_FRACTIONAL: u128 = 1_000_000_000_000_000_000u128; // 1*10**18
 pub const MINIMUM_RAND_FEE: Decimal = Decimal::raw(DECIMAL_FRACTIONAL/10_000u128); // The randomness provider gets at least 1/10_000 of the total raffle price
+pub const DRAND_MAINNET_CHAIN_HASH: &str =
+    "8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce";
+pub const DRAND_MAINNET_GENESIS_TIME: u64 = 1595431050;
+pub const DRAND_MAINNET_PERIOD: u64 = 30;
+
+/// The signature schemes of the drand beacon networks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DrandScheme {
+    #[default]
+    PedersenBlsChained, // G1 public key, G2 signatures of the round and the previous signature (drand mainnet)
+    PedersenBlsUnchained, // G1 public key, G2 signatures of the round only
+    BlsUnchainedG1Rfc9380, // G2 public key, G1 signatures of the round only (drand quicknet)
+}
+
+/// A drand beacon network the randomness verifier can check signatures of
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BeaconNetwork {
+    pub chain_hash: String,
+    pub scheme: DrandScheme,
+    pub public_key: Binary,
+    pub genesis_time: Timestamp, // The time of the first round of the network
+    pub period: u64, // The number of seconds between two rounds of the network
+}
+
+impl BeaconNetwork {
+    pub fn validate_period(&self) -> StdResult<()> {
+        if self.period == 0 {
+            return Err(StdError::generic_err("The drand period should be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the first round of a drand network published strictly after the given time
+pub fn first_round_after(genesis_time: Timestamp, period: u64, time: Timestamp) -> u64 {
+    if time < genesis_time {
+        return 1;
+    }
+    // Round r is published at genesis_time + (r - 1) * period
+    (time.seconds() - genesis_time.seconds()) / period.max(1) + 2
+}
+
+pub fn default_drand_chain_hash() -> String {
+    DRAND_MAINNET_CHAIN_HASH.to_string()
+}
+
+pub fn default_drand_genesis_time() -> Timestamp {
+    Timestamp::from_seconds(DRAND_MAINNET_GENESIS_TIME)
+}
+
+pub fn default_drand_period() -> u64 {
+    DRAND_MAINNET_PERIOD
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -90,6 +145,25 @@ impl AssetInfo {
             value,
         })
     }
+
+    /// Returns true if both assets are fungible tokens of the same currency (same denom or same cw20 address)
+    pub fn is_same_currency(&self, other: &AssetInfo) -> bool {
+        match (self, other) {
+            (AssetInfo::Coin(x), AssetInfo::Coin(y)) => x.denom == y.denom,
+            (AssetInfo::Cw20Coin(x), AssetInfo::Cw20Coin(y)) => x.address == y.address,
+            _ => false,
+        }
+    }
+}
+
+/// Conditions a ticket buyer has to fulfill to enter a raffle
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GatingCondition {
+    Cw721 { address: String }, // Hold at least one NFT of the collection
+    Cw20 { address: String, min_balance: Uint128 },
+    Coin { denom: String, min_balance: Uint128 },
+    Allowlist { addresses: Vec<String> },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, strum_macros::Display)]
@@ -101,6 +175,7 @@ pub enum RaffleState {
     Finished,
     Claimed,
     Cancelled,
+    Failed,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -117,7 +192,16 @@ pub struct ContractInfo {
     pub lock: bool,        // Wether the contract can accept new raffles
     pub drand_url: String, // The drand provider url (to find the right entropy provider)
     pub verify_signature_contract: Addr, // The contract that can verify the entropy signature
-    pub random_pubkey: Binary, // The public key of the randomness provider, to verify entropy origin
+    #[serde(default = "default_drand_chain_hash")]
+    pub drand_chain_hash: String, // The drand beacon network new raffles are bound to, registered in the verifier contract
+    #[serde(default)]
+    pub max_participant_number: Option<u32>, // The default maximum number of tickets of a raffle
+    #[serde(default)]
+    pub last_series_id: Option<u64>,
+    #[serde(default)]
+    pub randomness_bounty: Option<AssetInfo>, // Paid from the reward pool to the first provider of valid randomness of each raffle
+    #[serde(default)]
+    pub reward_pool_fee: Decimal, // The share of the treasury fee kept in the reward pool, when paid in the bounty currency
 }
 
 
@@ -129,6 +213,11 @@ impl ContractInfo{
                 "The Total Fee rate should be lower than 1"
             ))
         }
+        if self.reward_pool_fee > Decimal::one(){
+            bail!(StdError::generic_err(
+                "The reward pool fee rate should be at most 1"
+            ))
+        }
         Ok(())
     }
 }
@@ -143,7 +232,13 @@ pub struct RaffleOptions {
     pub comment: Option<String>,
     pub max_participant_number: Option<u32>,
     pub max_ticket_per_address: Option<u32>,
+    #[serde(default)]
+    pub min_ticket_number: Option<u32>, // If less tickets are sold, the raffle fails and the tickets are refunded
     pub raffle_preview: u32,
+    #[serde(default)]
+    pub prize_slots: Vec<Vec<u32>>, // The asset indexes won by each winner. Default : all the assets go to a single winner
+    #[serde(default)]
+    pub gating: Vec<GatingCondition>, // All the conditions have to be fulfilled to buy tickets
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
@@ -155,7 +250,10 @@ pub struct RaffleOptionsMsg {
     pub comment: Option<String>,
     pub max_participant_number: Option<u32>,
     pub max_ticket_per_address: Option<u32>,
+    pub min_ticket_number: Option<u32>,
     pub raffle_preview: Option<u32>,
+    pub prize_slots: Option<Vec<Vec<u32>>>,
+    pub gating: Option<Vec<GatingCondition>>,
 }
 
 impl RaffleOptions {
@@ -179,8 +277,11 @@ impl RaffleOptions {
                 .unwrap_or(contract_info.minimum_raffle_timeout)
                 .max(contract_info.minimum_raffle_timeout),
             comment: raffle_options.comment,
-            max_participant_number: raffle_options.max_participant_number,
+            max_participant_number: raffle_options
+                .max_participant_number
+                .or(contract_info.max_participant_number),
             max_ticket_per_address: raffle_options.max_ticket_per_address,
+            min_ticket_number: raffle_options.min_ticket_number,
             raffle_preview: raffle_options
                 .raffle_preview
                 .map(|preview| {
@@ -191,6 +292,10 @@ impl RaffleOptions {
                     }
                 })
                 .unwrap_or(0u32),
+            prize_slots: raffle_options
+                .prize_slots
+                .unwrap_or_else(|| vec![(0..assets_len.try_into().unwrap()).collect()]),
+            gating: raffle_options.gating.unwrap_or_default(),
         }
     }
 
@@ -220,6 +325,9 @@ impl RaffleOptions {
             max_ticket_per_address: raffle_options
                 .max_ticket_per_address
                 .or(current_options.max_ticket_per_address),
+            min_ticket_number: raffle_options
+                .min_ticket_number
+                .or(current_options.min_ticket_number),
             raffle_preview: raffle_options
                 .raffle_preview
                 .map(|preview| {
@@ -230,6 +338,10 @@ impl RaffleOptions {
                     }
                 })
                 .unwrap_or(current_options.raffle_preview),
+            prize_slots: raffle_options
+                .prize_slots
+                .unwrap_or(current_options.prize_slots),
+            gating: raffle_options.gating.unwrap_or(current_options.gating),
         }
     }
 }
@@ -240,6 +352,16 @@ pub struct Randomness {
     pub randomness: [u8; 32],
     pub randomness_round: u64,
     pub randomness_owner: Addr,
+    #[serde(default)]
+    pub randomness_signature: Binary, // The drand signature the randomness was derived from
+}
+
+/// Tickets bought together are saved as a single range of consecutive ticket ids
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct TicketRange {
+    pub owner: Addr,
+    pub count: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -248,10 +370,95 @@ pub struct RaffleInfo {
     pub owner: Addr,
     pub assets: Vec<AssetInfo>,
     pub raffle_ticket_price: AssetInfo,
+    #[serde(default)]
+    pub additional_ticket_prices: Vec<AssetInfo>, // Tickets can also be bought at these prices, in other currencies
+    #[serde(default)]
+    pub collected_funds: Vec<AssetInfo>, // The ticket prices paid, in each currency
     pub number_of_tickets: u32,
+    #[serde(default)]
+    pub unique_participants: u32, // The number of addresses holding tickets
     pub randomness: Option<Randomness>,
-    pub winner: Option<Addr>,
+    #[serde(default, alias = "winner", deserialize_with = "deserialize_winners")]
+    pub winners: Vec<Addr>, // The winner of each prize slot, in order
     pub is_cancelled: bool,
     pub raffle_options: RaffleOptions,
+    #[serde(default)]
+    pub series_id: Option<u64>, // The raffle series this raffle is a round of
+    #[serde(default = "default_drand_chain_hash")]
+    pub drand_chain_hash: String, // The drand beacon network the randomness has to come from
+    #[serde(default)]
+    pub drand_scheme: DrandScheme,
+    #[serde(default = "default_drand_genesis_time")]
+    pub drand_genesis_time: Timestamp, // The timing of that beacon network when the raffle was created
+    #[serde(default = "default_drand_period")]
+    pub drand_period: u64,
+}
+
+/// Raffles created before prize slots were introduced saved a single optional `winner`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Winners {
+    Slots(Vec<Addr>),
+    Legacy(Option<Addr>),
+}
+
+fn deserialize_winners<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Addr>, D::Error> {
+    Ok(match Winners::deserialize(deserializer)? {
+        Winners::Slots(winners) => winners,
+        Winners::Legacy(winner) => winner.into_iter().collect(),
+    })
+}
+
+/// A series of raffles, started one after the other with assets deposited in advance
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RaffleSeries {
+    pub owner: Addr,
+    pub remaining_rounds: Vec<Vec<AssetInfo>>, // The assets of each round that didn't start yet
+    pub raffle_ticket_price: AssetInfo,
+    pub additional_ticket_prices: Vec<AssetInfo>,
+    pub raffle_options: RaffleOptionsMsg, // The options each round is created with
+    pub interval: u64, // The minimum time between the start of two rounds
+    pub next_round_start: Timestamp,
+    pub raffle_ids: Vec<u64>, // The raffles of the rounds already started, in order
+}
+
+impl RaffleInfo {
+    /// Returns all the prices at which tickets can be bought, one per currency
+    pub fn ticket_prices(&self) -> Vec<AssetInfo> {
+        let mut ticket_prices = vec![self.raffle_ticket_price.clone()];
+        ticket_prices.extend(self.additional_ticket_prices.clone());
+        ticket_prices
+    }
+
+    /// Returns the time at which ticket sales end
+    pub fn close_timestamp(&self) -> Timestamp {
+        self.raffle_options
+            .raffle_start_timestamp
+            .plus_seconds(self.raffle_options.raffle_duration)
+    }
+
+    /// Returns the first drand round published strictly after ticket sales end
+    /// Earlier rounds could be known before buying the last tickets, so they are not accepted as randomness
+    pub fn min_randomness_round(&self) -> u64 {
+        first_round_after(self.drand_genesis_time, self.drand_period, self.close_timestamp())
+    }
+
+    /// Returns the assets won in each prize slot
+    /// Raffles created without prize slots give all their assets to a single winner
+    pub fn prizes(&self) -> Vec<Vec<AssetInfo>> {
+        if self.raffle_options.prize_slots.is_empty() {
+            return vec![self.assets.clone()];
+        }
+        self.raffle_options
+            .prize_slots
+            .iter()
+            .map(|slot| {
+                slot.iter()
+                    .filter_map(|index| self.assets.get(*index as usize).cloned())
+                    .collect()
+            })
+            .collect()
+    }
 }
 